@@ -3,12 +3,20 @@ use std::time::SystemTime;
 use abasic_core::Interpreter;
 use clap::Parser;
 
+use crate::file_program_storage::FileProgramStorage;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct CliArgs {
     /// BASIC source file to execute.
     pub source_filename: Option<String>,
 
+    /// Evaluate the given lines as though they were typed in, then exit.
+    /// Multiple lines can be separated by newlines or by passing this
+    /// option more than once, e.g. `--eval "10 PRINT 1" --eval RUN`.
+    #[arg(short, long, value_name = "LINES", conflicts_with = "source_filename")]
+    pub eval: Vec<String>,
+
     /// Enter interactive mode after running source file.
     #[arg(short, long)]
     interactive: bool,
@@ -24,22 +32,43 @@ pub struct CliArgs {
     /// Enable line number tracing
     #[arg(short, long)]
     pub tracing: bool,
+
+    /// Record prompts, input, and output to the given file, in the order
+    /// they occur.
+    #[arg(long, value_name = "FILE")]
+    pub transcript: Option<String>,
 }
 
 impl CliArgs {
     pub fn is_interactive(&self) -> bool {
-        self.source_filename.is_none() || self.interactive
+        (self.source_filename.is_none() && self.eval.is_empty()) || self.interactive
+    }
+
+    /// Returns the lines given via `--eval`, in the order they should be
+    /// evaluated.
+    pub fn eval_lines(&self) -> Vec<String> {
+        self.eval
+            .iter()
+            .flat_map(|lines| lines.lines())
+            .map(|line| line.to_string())
+            .collect()
     }
 
     pub fn create_interpreter(&self) -> Interpreter {
         let mut interpreter = Interpreter::default();
+        self.configure_interpreter(&mut interpreter);
+        interpreter
+    }
+
+    /// Applies the options given on the command-line to the given
+    /// interpreter, e.g. one that was created by the analyzer.
+    pub fn configure_interpreter(&self, interpreter: &mut Interpreter) {
         interpreter.enable_warnings = self.warnings;
         interpreter.enable_tracing = self.tracing;
+        interpreter.program_storage = Some(Box::new(FileProgramStorage));
 
         let now = SystemTime::now();
         let seed = now.elapsed().unwrap().as_millis() as u64;
         interpreter.randomize(seed);
-
-        interpreter
     }
 }
@@ -0,0 +1,16 @@
+use abasic_core::ProgramStorage;
+
+/// Saves and loads programs as plain-text files, with names treated as
+/// paths relative to the current directory.
+pub struct FileProgramStorage;
+
+impl ProgramStorage for FileProgramStorage {
+    fn save(&mut self, name: &str, listing: &[String]) -> Result<(), String> {
+        std::fs::write(name, listing.concat()).map_err(|err| err.to_string())
+    }
+
+    fn load(&self, name: &str) -> Option<Vec<String>> {
+        let source = std::fs::read_to_string(name).ok()?;
+        Some(source.lines().map(|line| line.to_string()).collect())
+    }
+}
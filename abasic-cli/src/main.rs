@@ -1,4 +1,5 @@
 mod cli_args;
+mod file_program_storage;
 mod stdio_interpreter;
 mod stdio_printer;
 
@@ -1,17 +1,19 @@
-use std::io::{stdin, IsTerminal};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{stdin, IsTerminal, Write};
 use std::path::PathBuf;
 use std::sync::mpsc::channel;
 
 use crate::cli_args::CliArgs;
 use crate::stdio_printer::StdioPrinter;
 use abasic_core::{
-    Interpreter, InterpreterOutput, InterpreterState, SourceFileAnalyzer, TracedInterpreterError,
+    InputTarget, Interpreter, InterpreterOutput, InterpreterState, RunOutcome, SourceFileAnalyzer,
+    TracedInterpreterError,
 };
 use colored::*;
-use ctrlc;
 use rustyline::{error::ReadlineError, DefaultEditor};
 
-const HISTORY_FILENAME: &'static str = ".abasic-history.txt";
+const HISTORY_FILENAME: &str = ".abasic-history.txt";
 
 fn get_history_path() -> Option<PathBuf> {
     // Note that we're using the deprecated std::env::home_dir() here, which
@@ -35,6 +37,7 @@ pub struct StdioInterpreter {
     args: CliArgs,
     printer: StdioPrinter,
     interpreter: Interpreter,
+    transcript: Option<File>,
 }
 
 impl StdioInterpreter {
@@ -44,6 +47,17 @@ impl StdioInterpreter {
             args,
             printer: StdioPrinter::new(),
             interpreter,
+            transcript: None,
+        }
+    }
+
+    /// Writes the given text to the transcript file, if we're keeping one.
+    fn record_in_transcript<T: AsRef<str>>(&mut self, text: T) {
+        if let Some(transcript) = &mut self.transcript {
+            if let Err(err) = transcript.write_all(text.as_ref().as_bytes()) {
+                eprintln!("WARNING: Failed to write to transcript ({:?}).", err);
+                self.transcript = None;
+            }
         }
     }
 
@@ -51,12 +65,17 @@ impl StdioInterpreter {
         for output in self.interpreter.take_output() {
             match output {
                 InterpreterOutput::Print(string) => {
+                    self.record_in_transcript(&string);
                     self.printer.print(string);
                 }
                 InterpreterOutput::Trace(line) => {
-                    self.printer.print(format!("#{} ", line).blue().to_string());
+                    let trace = format!("#{} ", line);
+                    self.record_in_transcript(&trace);
+                    self.printer.print(trace.blue().to_string());
                 }
+                InterpreterOutput::Ended => {}
                 _ => {
+                    self.record_in_transcript(format!("{}\n", output));
                     self.printer.eprintln(output.to_string().yellow());
                 }
             }
@@ -81,6 +100,7 @@ impl StdioInterpreter {
         let messages = analyzer.take_messages();
         let lines = analyzer.take_source_file_lines();
         self.interpreter = analyzer.into_interpreter();
+        self.args.configure_interpreter(&mut self.interpreter);
         if self.args.skip_check {
             return Ok(());
         }
@@ -98,6 +118,17 @@ impl StdioInterpreter {
                         .yellow(),
                     );
                 }
+                abasic_core::DiagnosticMessage::Hint(file_line_number, _, message) => {
+                    self.printer.eprintln(
+                        format!(
+                            "Hint on line {} of '{}': {}",
+                            file_line_number + 1,
+                            filename,
+                            message
+                        )
+                        .blue(),
+                    );
+                }
                 abasic_core::DiagnosticMessage::Error(line_number, err) => {
                     if !errored {
                         self.printer.eprintln(format!(
@@ -120,6 +151,7 @@ impl StdioInterpreter {
     }
 
     fn show_error<T: AsRef<str>>(&mut self, err: TracedInterpreterError, line: Option<T>) {
+        self.record_in_transcript(format!("{}\n", err));
         self.printer.eprintln(err.to_string().red());
         for line in err.get_line_with_pointer_caret(&self.interpreter, line) {
             self.printer.eprintln(format!("| {line}").dimmed());
@@ -156,11 +188,19 @@ impl StdioInterpreter {
         ctrlc::set_handler(move || tx.send(()).expect("Could not send signal on channel."))
             .expect("Error setting Ctrl-C handler.");
 
-        let mut initial_command = None;
+        if let Some(filename) = &self.args.transcript {
+            let Ok(file) = File::create(filename) else {
+                println!("ERROR WRITING FILE: {}", filename);
+                return Err(1);
+            };
+            self.transcript = Some(file);
+        }
+
+        let mut initial_commands = VecDeque::from(self.args.eval_lines());
 
         if let Some(filename) = &self.args.source_filename.clone() {
-            self.load_source_file(&filename)?;
-            initial_command = Some("RUN");
+            self.load_source_file(filename)?;
+            initial_commands.push_back("RUN".to_string());
         }
 
         if self.args.is_interactive() {
@@ -176,13 +216,18 @@ impl StdioInterpreter {
             let result = match self.interpreter.get_state() {
                 InterpreterState::Idle => {
                     self.printer.print_buffered_output();
-                    let add_to_history = initial_command.is_none() && self.args.is_interactive();
-                    let readline = if let Some(command) = initial_command.take() {
-                        Ok(command.to_string())
+                    let add_to_history = initial_commands.is_empty() && self.args.is_interactive();
+                    let readline = if let Some(command) = initial_commands.pop_front() {
+                        Ok(command)
                     } else if self.args.is_interactive() {
                         rl.readline("] ")
                     } else {
-                        return Ok(());
+                        // If the program stopped before completing (e.g. via
+                        // `STOP`), treat it like being interrupted by CTRL-C.
+                        return match self.interpreter.last_run_outcome() {
+                            Some(RunOutcome::Broke | RunOutcome::Interrupted) => Err(1),
+                            _ => Ok(()),
+                        };
                     };
                     match readline {
                         Ok(line) => {
@@ -190,6 +235,7 @@ impl StdioInterpreter {
                                 if let Err(err) = rl.add_history_entry(line.as_str()) {
                                     eprintln!("WARNING: Failed to add history entry (${:?}).", err);
                                 }
+                                self.record_in_transcript(format!("] {}\n", line));
                             }
                             let result = self.interpreter.start_evaluating(&line);
                             last_line = Some(line);
@@ -210,10 +256,19 @@ impl StdioInterpreter {
                 }
                 InterpreterState::Running => self.interpreter.continue_evaluating(),
                 InterpreterState::AwaitingInput => {
-                    let prompt = format!("{}? ", self.printer.pop_buffered_output());
+                    // Reading a single keystroke would require putting the terminal
+                    // into raw mode, so for GET we'll just read a whole line without
+                    // the usual "?" prompt, and the interpreter will use its first
+                    // character.
+                    let question_mark = match self.interpreter.pending_input_target() {
+                        Some(InputTarget::Keystroke { .. }) => "",
+                        _ => "? ",
+                    };
+                    let prompt = format!("{}{}", self.printer.pop_buffered_output(), question_mark);
                     let readline = rl.readline(&prompt);
                     match readline {
                         Ok(line) => {
+                            self.record_in_transcript(format!("{}{}\n", question_mark, line));
                             self.interpreter.provide_input(line);
                             Ok(())
                         }
@@ -22,7 +22,7 @@ impl StdioPrinter {
 
     fn flush_line_buffer(&mut self) {
         std::io::stdout()
-            .write(self.line_buffer.as_bytes())
+            .write_all(self.line_buffer.as_bytes())
             .unwrap();
         self.line_buffer.clear();
     }
@@ -0,0 +1,218 @@
+use std::{
+    ffi::OsStr,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/// Writes the given BASIC program to a temporary file and returns its path.
+fn write_program(name: &str, program: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("abasic-cli-test-{}-{}", std::process::id(), name));
+    std::fs::write(&path, program).unwrap();
+    path
+}
+
+/// Runs the CLI on the given source file in interactive mode, feeding it the
+/// given stdin, and returns its stdout.
+fn run_interactive(path: &Path, stdin: &str) -> String {
+    run_interactive_with_args(path, &[], stdin)
+}
+
+/// Like `run_interactive()`, but passes the given extra arguments to the CLI.
+fn run_interactive_with_args(path: &Path, args: &[&OsStr], stdin: &str) -> String {
+    let mut all_args = vec!["-i".as_ref()];
+    all_args.extend(args);
+    all_args.push(path.as_os_str());
+    let output = run_cli(&all_args, stdin);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Runs the CLI with the given arguments, feeding it the given stdin.
+fn run_cli(args: &[&OsStr], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_abasic"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn list_outputs_one_newline_per_line() {
+    let path = write_program("list.bas", "20 print \"there\"\n10 rem hi\n30 end\n");
+    let stdout = run_interactive(&path, "list\n");
+    std::fs::remove_file(path).unwrap();
+    assert!(
+        stdout.ends_with("10 REM hi\n20 PRINT \"there\"\n30 END\n"),
+        "unexpected output: {stdout:?}"
+    );
+}
+
+#[test]
+fn files_with_crlf_line_endings_work() {
+    let path = write_program("crlf.bas", "10 rem hi\r\n20 print \"there\"\r\n");
+    let stdout = run_interactive(&path, "list\nrun\n");
+    std::fs::remove_file(path).unwrap();
+    assert!(
+        stdout.ends_with("10 REM hi\n20 PRINT \"there\"\nthere\n"),
+        "unexpected output: {stdout:?}"
+    );
+}
+
+#[test]
+fn transcript_interleaves_prompts_input_and_output() {
+    let path = write_program(
+        "transcript.bas",
+        "10 print \"name\";: input n$\n20 print \"hi \" n$\n",
+    );
+    let transcript_path = write_program("transcript.txt", "");
+    run_interactive_with_args(
+        &path,
+        &["--transcript".as_ref(), transcript_path.as_os_str()],
+        "bob\nprint 1 + 1\n",
+    );
+    let transcript = std::fs::read_to_string(&transcript_path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(transcript_path).unwrap();
+    assert_eq!(transcript, "name? bob\nhi bob\n] print 1 + 1\n2\n");
+}
+
+#[test]
+fn running_source_file_works() {
+    let path = write_program("run.bas", "10 input n$\n20 print \"hi \" n$\n");
+    let output = run_cli(&[path.as_os_str()], "bob\n");
+    std::fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi bob\n");
+}
+
+#[test]
+fn running_source_file_with_error_exits_with_failure() {
+    let path = write_program("error.bas", "10 print \"hi\"\n20 print 1 / 0\n");
+    let output = run_cli(&[path.as_os_str()], "");
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("DIVISION BY ZERO ERROR IN 20"),
+        "unexpected stderr: {stderr:?}"
+    );
+}
+
+#[test]
+fn running_source_file_that_stops_exits_with_failure() {
+    let path = write_program("stop.bas", "10 print \"hi\"\n20 stop\n30 print \"nope\"\n");
+    let output = run_cli(&[path.as_os_str()], "");
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("hi\n"));
+}
+
+#[test]
+fn running_empty_source_file_does_nothing() {
+    let path = write_program("empty.bas", "");
+    let output = run_cli(&[path.as_os_str()], "");
+    std::fs::remove_file(path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
+
+#[test]
+fn cont_without_running_fails_interactively() {
+    let output = run_cli(&[], "run\ncont\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with("Press CTRL-C to exit.\n"),
+        "unexpected output: {stdout:?}"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("CAN'T CONTINUE ERROR\n"),
+        "unexpected stderr: {stderr:?}"
+    );
+}
+
+#[test]
+fn options_apply_to_source_file() {
+    let path = write_program("tracing.bas", "10 print \"hi\"\n");
+    let output = run_cli(&["-t".as_ref(), path.as_os_str()], "");
+    std::fs::remove_file(path).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("#10 "), "unexpected output: {stdout:?}");
+}
+
+#[test]
+fn runtime_errors_show_caret_under_failing_operator() {
+    let path = write_program("caret.bas", "10 print 5/0\n");
+    let output = run_cli(&[path.as_os_str()], "");
+    std::fs::remove_file(path).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("| PRINT 5 / 0\n|         ^\n"),
+        "unexpected stderr: {stderr:?}"
+    );
+}
+
+#[test]
+fn save_and_load_use_files() {
+    let path =
+        std::env::temp_dir().join(format!("abasic-cli-test-{}-saved.bas", std::process::id()));
+    let path = path.to_str().unwrap();
+    let output = run_cli(
+        &[
+            "--eval".as_ref(),
+            format!("10 print 1\nsave \"{path}\"").as_ref(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "10 PRINT 1\n");
+
+    let output = run_cli(
+        &["--eval".as_ref(), format!("load \"{path}\"\nrun").as_ref()],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+}
+
+#[test]
+fn eval_runs_lines_and_exits() {
+    let output = run_cli(
+        &["--eval".as_ref(), "10 print 1\n20 print 2\nrun".as_ref()],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n2\n");
+
+    let output = run_cli(
+        &[
+            "-e".as_ref(),
+            "10 print \"hi\"".as_ref(),
+            "-e".as_ref(),
+            "run".as_ref(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+}
+
+#[test]
+fn eval_with_error_exits_with_failure() {
+    let output = run_cli(&["--eval".as_ref(), "print 5/0".as_ref()], "");
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -20,6 +20,9 @@ pub enum DiagnosticMessage {
     /// The first number is the file line number, then an optional program location,
     /// then the warning message.
     Warning(usize, Option<NumberedProgramLocation>, String),
+    /// Like a warning, but for code that's probably fine, and is only
+    /// flagged because it may behave in surprising ways.
+    Hint(usize, Option<NumberedProgramLocation>, String),
     /// The first number is the file line number, then the error that occurred.
     Error(usize, TracedInterpreterError),
 }
@@ -1,6 +1,8 @@
 use crate::{
     builtins::Builtin,
-    operators::{AddOrSubtractOp, EqualityOp, MultiplyOrDivideOp, UnaryOp},
+    operators::{
+        right_side_type_mismatch, AddOrSubtractOp, EqualityOp, MultiplyOrDivideOp, UnaryOp,
+    },
     program::{Program, ProgramLocation},
     symbol::Symbol,
     SyntaxError, Token, TracedInterpreterError,
@@ -52,6 +54,37 @@ impl<'a> ExpressionAnalyzer<'a> {
         Ok(result)
     }
 
+    fn evaluate_string_and_number_function_args(
+        &mut self,
+        required: usize,
+        optional: usize,
+    ) -> Result<(), TracedInterpreterError> {
+        self.program.expect_next_token(Token::LeftParen)?;
+        self.evaluate_expression()?.check(ValueType::String)?;
+        for i in 0..required + optional {
+            if i < required {
+                self.program.expect_next_token(Token::Comma)?;
+            } else if !self.program.accept_next_token(Token::Comma) {
+                break;
+            }
+            self.evaluate_expression()?.check_number()?;
+        }
+        self.program.expect_next_token(Token::RightParen)?;
+        Ok(())
+    }
+
+    fn evaluate_iif_function(&mut self) -> Result<ValueType, TracedInterpreterError> {
+        self.program.expect_next_token(Token::LeftParen)?;
+        // Like `IF`, the condition can be of any type.
+        self.evaluate_expression()?;
+        self.program.expect_next_token(Token::Comma)?;
+        let value_type = self.evaluate_expression()?;
+        self.program.expect_next_token(Token::Comma)?;
+        let value_type = self.evaluate_expression()?.check(value_type)?;
+        self.program.expect_next_token(Token::RightParen)?;
+        Ok(value_type)
+    }
+
     fn evaluate_user_defined_function_call(
         &mut self,
         function_name: &Symbol,
@@ -69,7 +102,7 @@ impl<'a> ExpressionAnalyzer<'a> {
         };
 
         self.symbol_accesses
-            .log_access(&function_name, &location, SymbolAccess::Read);
+            .log_access(function_name, location, SymbolAccess::Read);
         self.program.expect_next_token(Token::LeftParen)?;
         let arity = arg_names.len();
         for (i, arg) in arg_names.into_iter().enumerate() {
@@ -91,11 +124,32 @@ impl<'a> ExpressionAnalyzer<'a> {
     ) -> Result<Option<ValueType>, TracedInterpreterError> {
         if let Some(builtin) = Builtin::try_from(function_name) {
             match builtin {
-                Builtin::Abs | Builtin::Int | Builtin::Rnd => {
-                    self.evaluate_unary_number_function_arg()
-                }
+                Builtin::Abs
+                | Builtin::Atn
+                | Builtin::Cos
+                | Builtin::Exp
+                | Builtin::Int
+                | Builtin::Log
+                | Builtin::Peek
+                | Builtin::Pos
+                | Builtin::Rnd
+                | Builtin::Sin
+                | Builtin::Sqr
+                | Builtin::Tan => self.evaluate_unary_number_function_arg(),
+                Builtin::Chr => self
+                    .evaluate_unary_number_function_arg()
+                    .map(|_| ValueType::String),
+                Builtin::Left | Builtin::Right => self
+                    .evaluate_string_and_number_function_args(1, 0)
+                    .map(|_| ValueType::String),
+                Builtin::Asc | Builtin::Len => self
+                    .evaluate_string_and_number_function_args(0, 0)
+                    .map(|_| ValueType::Number),
+                Builtin::Mid => self
+                    .evaluate_string_and_number_function_args(1, 1)
+                    .map(|_| ValueType::String),
             }
-            .map(|value| Some(value))
+            .map(Some)
         } else {
             self.evaluate_user_defined_function_call(function_name, location)
         }
@@ -105,6 +159,8 @@ impl<'a> ExpressionAnalyzer<'a> {
         match self.program.next_unwrapped_token()? {
             Token::StringLiteral(_string) => Ok(ValueType::String),
             Token::NumericLiteral(_number) => Ok(ValueType::Number),
+            Token::Input | Token::Get => Err(SyntaxError::InputInExpression.into()),
+            Token::Iif => self.evaluate_iif_function(),
             Token::Symbol(symbol) => {
                 let symbol_location = self.program.get_prev_location();
                 let is_array_or_function_call =
@@ -114,7 +170,7 @@ impl<'a> ExpressionAnalyzer<'a> {
                         Ok(value)
                     } else {
                         self.evaluate_array_index()?;
-                        self.symbol_accesses.log_access(
+                        self.symbol_accesses.log_array_access(
                             &symbol,
                             &symbol_location,
                             SymbolAccess::Read,
@@ -127,7 +183,7 @@ impl<'a> ExpressionAnalyzer<'a> {
                     Ok(ValueType::from_variable_name(symbol))
                 }
             }
-            _ => Err(SyntaxError::UnexpectedToken.into()),
+            token => Err(SyntaxError::UnexpectedToken(Some(token)).into()),
         }
     }
 
@@ -148,8 +204,7 @@ impl<'a> ExpressionAnalyzer<'a> {
 
         if let Some(unary_op) = maybe_unary_op {
             match unary_op {
-                UnaryOp::Positive | UnaryOp::Negative => Ok(value.check_number()?),
-                UnaryOp::Not => Ok(value),
+                UnaryOp::Positive | UnaryOp::Negative | UnaryOp::Not => Ok(value.check_number()?),
             }
         } else {
             Ok(value)
@@ -185,10 +240,14 @@ impl<'a> ExpressionAnalyzer<'a> {
     fn evaluate_plus_or_minus_expression(&mut self) -> Result<ValueType, TracedInterpreterError> {
         let value = self.evaluate_multiply_or_divide_expression()?;
 
-        while let Some(_plus_or_minus) = self.program.try_next_token(AddOrSubtractOp::from_token) {
+        while let Some(plus_or_minus) = self.program.try_next_token(AddOrSubtractOp::from_token) {
             let second_operand = self.evaluate_multiply_or_divide_expression()?;
-            value.check_number()?;
-            second_operand.check_number()?;
+            if plus_or_minus == AddOrSubtractOp::Add && value == ValueType::String {
+                second_operand.check(ValueType::String)?;
+            } else {
+                value.check_number()?;
+                second_operand.check_number()?;
+            }
         }
 
         Ok(value)
@@ -199,7 +258,9 @@ impl<'a> ExpressionAnalyzer<'a> {
 
         while let Some(_equality_op) = self.program.try_next_token(EqualityOp::from_token) {
             let second_operand = self.evaluate_plus_or_minus_expression()?;
-            value.check(second_operand)?;
+            if value != second_operand {
+                return Err(right_side_type_mismatch(value == ValueType::String));
+            }
         }
 
         Ok(value)
@@ -1,13 +1,19 @@
 mod diagnostic_message;
 mod expression_analyzer;
+mod outline;
+mod program_metrics;
 mod source_file_analyzer;
 mod source_map;
 mod statement_analyzer;
 mod symbol_access;
 mod token_type;
 mod value_type;
+mod variable_symbol;
 
 pub use diagnostic_message::DiagnosticMessage;
-pub use source_file_analyzer::SourceFileAnalyzer;
+pub use outline::{OutlineSymbol, OutlineSymbolKind};
+pub use program_metrics::ProgramMetrics;
+pub use source_file_analyzer::{AnalyzerOptions, SourceFileAnalyzer};
 pub use source_map::SourceFileMap;
 pub use token_type::TokenType;
+pub use variable_symbol::VariableSymbol;
@@ -0,0 +1,109 @@
+use std::ops::Range;
+
+use crate::{program::Program, tokenizer::Token};
+
+use super::source_map::SourceFileMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlineSymbolKind {
+    /// A numbered line.
+    Line,
+    /// A `DEF FN` function definition.
+    Function,
+}
+
+/// An entry in the outline of a source file.
+#[derive(Debug, PartialEq)]
+pub struct OutlineSymbol {
+    /// For lines, this is the line number followed by the keyword of its
+    /// first statement, e.g. `10 PRINT`. For functions, it's the function's
+    /// name, e.g. `FNA`.
+    pub name: String,
+    /// Extra information about the symbol. For functions, this is its
+    /// argument names, e.g. `(X, Y)`.
+    pub detail: Option<String>,
+    pub kind: OutlineSymbolKind,
+    /// The line of the source file the symbol is on.
+    pub file_line_number: usize,
+    /// The range of the whole symbol within its line of the source file.
+    pub range: Range<usize>,
+    /// The range of the part of the symbol that identifies it, e.g. the
+    /// line number of a line or the name of a function.
+    pub selection_range: Range<usize>,
+    /// Any functions defined on a line are its children.
+    pub children: Vec<OutlineSymbol>,
+}
+
+impl OutlineSymbol {
+    pub(crate) fn from_program(program: &Program, source_file_map: &SourceFileMap) -> Vec<Self> {
+        let mut symbols = vec![];
+        for (line_number, file_line_number, ranges) in source_file_map.numbered_lines() {
+            let (Some(tokens), Some(token_ranges)) = (
+                program.get_numbered_line(line_number),
+                ranges.token_ranges.as_ref(),
+            ) else {
+                continue;
+            };
+            let name = match tokens.first() {
+                Some(first_token) => format!("{line_number} {}", statement_keyword(first_token)),
+                None => line_number.to_string(),
+            };
+            symbols.push(OutlineSymbol {
+                name,
+                detail: None,
+                kind: OutlineSymbolKind::Line,
+                file_line_number,
+                range: 0..ranges.length,
+                selection_range: 0..ranges.line_number_end,
+                children: functions_in_line(tokens, token_ranges, file_line_number),
+            });
+        }
+        symbols
+    }
+}
+
+fn statement_keyword(first_token: &Token) -> String {
+    match first_token {
+        // This is an assignment without the optional `LET`.
+        Token::Symbol(_) => "LET".to_string(),
+        // These tokens include their payloads when displayed.
+        Token::Remark(_) => "REM".to_string(),
+        Token::Data(_) => "DATA".to_string(),
+        token => token.to_string(),
+    }
+}
+
+fn functions_in_line(
+    tokens: &[Token],
+    token_ranges: &[Range<usize>],
+    file_line_number: usize,
+) -> Vec<OutlineSymbol> {
+    let mut functions = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        let (Token::Def, Some(Token::Symbol(name))) = (token, tokens.get(i + 1)) else {
+            continue;
+        };
+        let arg_names = tokens[i + 2..]
+            .iter()
+            .take_while(|&token| token != &Token::RightParen)
+            .filter_map(|token| match token {
+                Token::Symbol(arg_name) => Some(arg_name.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let statement_end = tokens[i..]
+            .iter()
+            .position(|token| token == &Token::Colon)
+            .map_or(tokens.len(), |offset| i + offset);
+        functions.push(OutlineSymbol {
+            name: name.to_string(),
+            detail: Some(format!("({})", arg_names.join(", "))),
+            kind: OutlineSymbolKind::Function,
+            file_line_number,
+            range: token_ranges[i].start..token_ranges[statement_end - 1].end,
+            selection_range: token_ranges[i + 1].clone(),
+            children: vec![],
+        });
+    }
+    functions
+}
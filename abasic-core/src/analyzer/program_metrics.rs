@@ -0,0 +1,39 @@
+use crate::{program::Program, tokenizer::Token};
+
+/// Simple metrics about a program's structure, which can give a rough
+/// sense of how complex it is.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProgramMetrics {
+    /// The number of numbered lines in the program.
+    pub lines: usize,
+    /// The number of places control can jump to another line, via `GOTO`,
+    /// `GOSUB`, or a line number after `THEN` or `ELSE`.
+    pub jumps: usize,
+    /// The number of `FOR` loops.
+    pub loops: usize,
+    /// The number of `DEF FN` function definitions.
+    pub function_definitions: usize,
+}
+
+impl ProgramMetrics {
+    pub fn from_program(program: &Program) -> Self {
+        let mut metrics = ProgramMetrics::default();
+        for (_, tokens) in program.list_tokens() {
+            metrics.lines += 1;
+            let mut prev_token: Option<&Token> = None;
+            for token in tokens {
+                match (prev_token, token) {
+                    (_, Token::Goto | Token::Gosub) => metrics.jumps += 1,
+                    (Some(Token::Then | Token::Else), Token::NumericLiteral(_)) => {
+                        metrics.jumps += 1
+                    }
+                    (_, Token::For) => metrics.loops += 1,
+                    (_, Token::Def) => metrics.function_definitions += 1,
+                    _ => {}
+                }
+                prev_token = Some(token);
+            }
+        }
+        metrics
+    }
+}
@@ -1,18 +1,52 @@
 use std::ops::Range;
 
 use crate::{
-    line_number_parser::parse_line_number, program::Program, string_manager::StringManager,
-    tokenizer::Tokenizer, DiagnosticMessage, Interpreter, SourceFileMap, TokenType,
+    builtins::Builtin,
+    line_number_parser::parse_line_number,
+    program::{NumberedProgramLocation, Program},
+    program_lines::line_number_reference,
+    string_manager::StringManager,
+    tokenizer::Tokenizer,
+    DiagnosticMessage, Interpreter, SourceFileMap, Token, TokenType, TracedInterpreterError,
 };
 
 use super::{
+    outline::OutlineSymbol,
+    program_metrics::ProgramMetrics,
     source_map::SourceLineRanges,
     statement_analyzer::StatementAnalyzer,
     symbol_access::{SymbolAccessMap, SymbolAccessWarning},
+    token_type::classify_tokens,
+    value_type::ValueType,
+    variable_symbol::VariableSymbol,
 };
 
+/// Options for enabling optional diagnostics and language features, which
+/// are all off by default.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct AnalyzerOptions {
+    /// Whether to hint when a function parameter has the same name as a
+    /// variable used elsewhere in the program.
+    pub enable_shadowing_hints: bool,
+    /// Whether to warn when a `FOR` loop has no `NEXT` after it in the
+    /// program. Applesoft BASIC doesn't consider this an error, but it's
+    /// usually a mistake.
+    pub enable_unclosed_loop_warnings: bool,
+    /// Whether to hint when a `GOTO`, `GOSUB`, `THEN`, or `ELSE` jumps to
+    /// a line that starts with `REM` or `DATA`, which does nothing and
+    /// often indicates an off-by-one line number.
+    pub enable_no_op_jump_hints: bool,
+    /// Whether to support Applesoft BASIC's graphics statements, like
+    /// `Interpreter::enable_graphics`.
+    pub enable_graphics: bool,
+    /// Whether to support Applesoft BASIC statements that aren't supported
+    /// by default, like `Interpreter::enable_applesoft_statements`.
+    pub enable_applesoft_statements: bool,
+}
+
 #[derive(Default)]
 pub struct SourceFileAnalyzer {
+    options: AnalyzerOptions,
     lines: Vec<String>,
     line_tokens: Vec<Vec<(TokenType, Range<usize>)>>,
     program: Program,
@@ -23,21 +57,69 @@ pub struct SourceFileAnalyzer {
 }
 
 impl SourceFileAnalyzer {
+    /// Analyzes the given source file. Both Unix (LF) and Windows (CRLF)
+    /// line endings are supported.
     pub fn analyze(contents: String) -> Self {
         Self::analyze_lines(
             contents
                 .split('\n')
-                .map(|s| s.to_owned())
+                .map(|s| s.strip_suffix('\r').unwrap_or(s).to_owned())
                 .collect::<Vec<_>>(),
         )
     }
 
     pub fn analyze_lines(lines: Vec<String>) -> Self {
-        let mut analyzer = SourceFileAnalyzer::default();
+        Self::analyze_lines_with_options(lines, AnalyzerOptions::default())
+    }
+
+    pub fn analyze_lines_with_options(lines: Vec<String>, options: AnalyzerOptions) -> Self {
+        let mut analyzer = SourceFileAnalyzer {
+            options,
+            ..Default::default()
+        };
         analyzer.run(lines);
         analyzer
     }
 
+    /// Tokenizes a single line of source code, without analyzing it in the
+    /// context of a whole program. This is much faster than a full analysis,
+    /// which makes it useful for things like syntax highlighting as the user
+    /// types.
+    ///
+    /// The line number, if any, is returned as a numeric literal. The line
+    /// is tokenized the same way the given interpreter would tokenize it,
+    /// e.g. `WHILE` is only a keyword if the interpreter enables it.
+    pub fn tokenize_line(
+        line: &str,
+        interpreter: &Interpreter,
+    ) -> Result<Vec<(Token, TokenType, Range<usize>)>, TracedInterpreterError> {
+        let mut tokens = vec![];
+        let mut line_number_end = 0;
+        if let Some((line_number, end)) = parse_line_number(line) {
+            tokens.push((
+                Token::NumericLiteral(line_number as f64),
+                TokenType::Number,
+                0..end,
+            ));
+            line_number_end = end;
+        }
+        let mut string_manager = StringManager::default();
+        let (line_tokens, ranges) = Tokenizer::new(line, &mut string_manager)
+            .skip_bytes(line_number_end)
+            .lenient_operators(interpreter.enable_lenient_operators)
+            .at_keyword(interpreter.enable_print_at)
+            .iif_keyword(interpreter.enable_iif)
+            .while_keywords(interpreter.enable_while_loops)
+            .graphics_keywords(interpreter.enable_graphics)
+            .applesoft_keywords(interpreter.enable_applesoft_statements)
+            .remaining_tokens_and_ranges()?;
+        let token_types = classify_tokens(&line_tokens);
+        for ((token, token_type), range) in line_tokens.into_iter().zip(token_types).zip(ranges) {
+            tokens.push((token, token_type, range));
+        }
+        Ok(tokens)
+    }
+
     pub fn messages(&self) -> &Vec<DiagnosticMessage> {
         &self.messages
     }
@@ -62,6 +144,94 @@ impl SourceFileAnalyzer {
         &self.line_tokens
     }
 
+    pub fn metrics(&self) -> ProgramMetrics {
+        ProgramMetrics::from_program(&self.program)
+    }
+
+    /// Returns an outline of the source file, with an entry for each
+    /// numbered line and any functions defined on it.
+    pub fn outline(&self) -> Vec<OutlineSymbol> {
+        OutlineSymbol::from_program(&self.program, &self.source_file_map)
+    }
+
+    /// Returns the file line number and range of the definition of whatever
+    /// is at the given column of the given file line. This is the line
+    /// referenced by e.g. `GOTO 100`, the first assignment to a variable,
+    /// or the `DEF` of a function.
+    pub fn find_definition(
+        &self,
+        file_line_number: usize,
+        column: usize,
+    ) -> Option<(usize, Range<usize>)> {
+        let location = self
+            .source_file_map
+            .map_source_to_location(file_line_number, column)?;
+        let tokens = self.program.get_numbered_line(location.line)?;
+        if let Some(line_number) = line_number_reference(tokens, location.token_index) {
+            return self.source_file_map.map_line_number_to_source(line_number);
+        }
+        let definition = self.symbol_accesses.find_definition(location)?;
+        self.source_file_map
+            .map_location_to_source(&definition.into())
+    }
+
+    /// Returns a Markdown description of whatever is at the given column of
+    /// the given file line, if it's a variable, array, or function.
+    pub fn hover(&self, file_line_number: usize, column: usize) -> Option<String> {
+        let location = self
+            .source_file_map
+            .map_source_to_location(file_line_number, column)?;
+        let tokens = self.program.get_numbered_line(location.line)?;
+        let Some(Token::Symbol(symbol)) = tokens.get(location.token_index) else {
+            return None;
+        };
+        let is_followed_by_paren = tokens.get(location.token_index + 1) == Some(&Token::LeftParen);
+        if is_followed_by_paren {
+            if let Some(builtin) = Builtin::try_from(symbol) {
+                return Some(format!(
+                    "`{}`\n\n{}",
+                    builtin.signature(),
+                    builtin.description()
+                ));
+            }
+        }
+        let is_string = ValueType::from_variable_name(symbol) == ValueType::String;
+        if self.program.get_function_body(symbol).is_some() {
+            let value_type = if is_string { "string" } else { "numeric" };
+            return Some(format!("`{symbol}`: {value_type} function"));
+        }
+        let variable = VariableSymbol {
+            name: symbol.to_string(),
+            is_string,
+            is_array: is_followed_by_paren,
+        };
+        Some(format!("`{}`: {}", variable.name, variable.detail()))
+    }
+
+    /// Returns every variable and array used in the source file, sorted by
+    /// name. Functions defined via `DEF` aren't included.
+    pub fn variables(&self) -> Vec<VariableSymbol> {
+        let scalars = self
+            .symbol_accesses
+            .scalar_symbols()
+            .filter(|symbol| self.program.get_function_body(symbol).is_none())
+            .map(|symbol| (symbol, false));
+        let arrays = self
+            .symbol_accesses
+            .array_symbols()
+            .map(|symbol| (symbol, true));
+        let mut variables = scalars
+            .chain(arrays)
+            .map(|(symbol, is_array)| VariableSymbol {
+                name: symbol.to_string(),
+                is_string: ValueType::from_variable_name(symbol) == ValueType::String,
+                is_array,
+            })
+            .collect::<Vec<_>>();
+        variables.sort_by(|a, b| (&a.name, a.is_array).cmp(&(&b.name, b.is_array)));
+        variables
+    }
+
     fn warn_line<T: AsRef<str>>(&mut self, line_number: usize, message: T) {
         self.messages.push(DiagnosticMessage::Warning(
             line_number,
@@ -90,16 +260,23 @@ impl SourceFileAnalyzer {
             };
             let mut line_tokens: Vec<(TokenType, Range<usize>)> =
                 vec![(TokenType::Number, 0..line_number_end)];
-            if self.program.has_line_number(basic_line_number) {
+            // Note that we check the source file map rather than the program
+            // here, since lines that fail to tokenize or have no statements
+            // are never added to the program, but are still duplicates.
+            if self.source_file_map.has_line(basic_line_number) {
                 self.warn_line(i, "Redefinition of pre-existing BASIC line.");
             }
             let tokenize_result = Tokenizer::new(line, &mut self.string_manager)
                 .skip_bytes(line_number_end)
+                .graphics_keywords(self.options.enable_graphics)
+                .applesoft_keywords(self.options.enable_applesoft_statements)
                 .remaining_tokens_and_ranges();
             match tokenize_result {
                 Ok((tokens, token_ranges)) => {
-                    for (token, range) in tokens.iter().zip(&token_ranges) {
-                        line_tokens.push((token.into(), range.clone()));
+                    for (token_type, range) in
+                        classify_tokens(&tokens).into_iter().zip(&token_ranges)
+                    {
+                        line_tokens.push((token_type, range.clone()));
                     }
                     source_line_ranges.token_ranges = Some(token_ranges);
                     if tokens.is_empty() {
@@ -138,6 +315,89 @@ impl SourceFileAnalyzer {
             }
         }
         self.populate_symbol_access_warnings();
+        if self.options.enable_shadowing_hints {
+            self.populate_shadowing_hints();
+        }
+        if self.options.enable_unclosed_loop_warnings {
+            self.populate_unclosed_loop_warnings();
+        }
+        if self.options.enable_no_op_jump_hints {
+            self.populate_no_op_jump_hints();
+        }
+    }
+
+    fn populate_no_op_jump_hints(&mut self) {
+        let mut hints = vec![];
+        for (basic_line_number, file_line_number, _) in self.source_file_map.numbered_lines() {
+            let Some(tokens) = self.program.get_numbered_line(basic_line_number) else {
+                continue;
+            };
+            for token_index in 0..tokens.len() {
+                let Some(target) = line_number_reference(tokens, token_index) else {
+                    continue;
+                };
+                let Some(target_tokens) = self.program.get_numbered_line(target) else {
+                    continue;
+                };
+                let statement = match target_tokens.first() {
+                    Some(Token::Remark(_)) => "REM",
+                    Some(Token::Data(_)) => "DATA",
+                    _ => continue,
+                };
+                hints.push(DiagnosticMessage::Hint(
+                    file_line_number,
+                    Some(NumberedProgramLocation {
+                        line: basic_line_number,
+                        token_index,
+                    }),
+                    format!(
+                        "Line {target} starts with a {statement} statement, which does nothing \
+                        when run."
+                    ),
+                ));
+            }
+        }
+        self.messages.extend(hints);
+    }
+
+    /// Note that we're only looking at the program's listing here, so a loop
+    /// that's exited early via `GOTO` is fine as long as there's a `NEXT`
+    /// for it somewhere after the `FOR`.
+    fn populate_unclosed_loop_warnings(&mut self) {
+        for (symbol, location) in self.symbol_accesses.get_unclosed_loops() {
+            let source_line = self
+                .source_file_map
+                .map_location_to_source(&location.into())
+                .unwrap()
+                .0;
+            self.messages.push(DiagnosticMessage::Warning(
+                source_line,
+                Some(location),
+                format!("FOR loop over '{symbol}' has no matching NEXT."),
+            ));
+        }
+    }
+
+    /// Function parameters are looked up on a stack that's shared with
+    /// any functions they call, rather than being lexically scoped, which
+    /// can be surprising when they shadow a variable.
+    fn populate_shadowing_hints(&mut self) {
+        for (symbol, location) in self.symbol_accesses.get_shadowing_function_parameters() {
+            let source_line = self
+                .source_file_map
+                .map_location_to_source(&location.into())
+                .unwrap()
+                .0;
+            self.messages.push(DiagnosticMessage::Hint(
+                source_line,
+                Some(location),
+                format!(
+                    "Parameter '{symbol}' shadows a variable of the same name. Any function \
+                    called while this one is being evaluated will also see the parameter \
+                    instead of the variable."
+                ),
+            ));
+        }
     }
 
     fn populate_symbol_access_warnings(&mut self) {
@@ -145,6 +405,9 @@ impl SourceFileAnalyzer {
             let message = match warning {
                 SymbolAccessWarning::UndefinedSymbol => format!("'{symbol}' is never defined."),
                 SymbolAccessWarning::UnusedSymbol => format!("'{symbol}' is never used."),
+                SymbolAccessWarning::UsedBeforeDefined => {
+                    format!("'{symbol}' is used before it's defined.")
+                }
             };
             let source_line = self
                 .source_file_map
@@ -161,6 +424,9 @@ impl SourceFileAnalyzer {
 
     pub fn into_interpreter(mut self) -> Interpreter {
         self.program.reset_runtime_state();
-        Interpreter::from_program(self.program, self.string_manager)
+        let mut interpreter = Interpreter::from_program(self.program, self.string_manager);
+        interpreter.enable_graphics = self.options.enable_graphics;
+        interpreter.enable_applesoft_statements = self.options.enable_applesoft_statements;
+        interpreter
     }
 }
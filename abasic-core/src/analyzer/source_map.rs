@@ -1,7 +1,7 @@
 use std::{collections::HashMap, ops::Range};
 
 use crate::{
-    program::{ProgramLine, ProgramLocation},
+    program::{NumberedProgramLocation, ProgramLine, ProgramLocation},
     DiagnosticMessage, InterpreterError, SyntaxError,
 };
 
@@ -30,6 +30,31 @@ impl SourceFileMap {
         self.file_line_ranges.push(ranges);
     }
 
+    /// Returns whether the given BASIC line has been added to the map.
+    pub(crate) fn has_line(&self, basic_line_number: u64) -> bool {
+        self.basic_lines_to_file_lines
+            .contains_key(&basic_line_number)
+    }
+
+    /// Returns the number of every BASIC line defined in the source file,
+    /// along with its file line number and ranges, in the order they
+    /// appear in the file.
+    pub(crate) fn numbered_lines(&self) -> Vec<(u64, usize, &SourceLineRanges)> {
+        let mut lines = self
+            .basic_lines_to_file_lines
+            .iter()
+            .map(|(&basic_line_number, &file_line_number)| {
+                (
+                    basic_line_number,
+                    file_line_number,
+                    &self.file_line_ranges[file_line_number],
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.sort_by_key(|&(_, file_line_number, _)| file_line_number);
+        lines
+    }
+
     pub fn map_location_to_source(
         &self,
         location: &ProgramLocation,
@@ -55,9 +80,44 @@ impl SourceFileMap {
         None
     }
 
+    /// Returns the file line number of the given BASIC line, along with the
+    /// range of its line number.
+    pub fn map_line_number_to_source(
+        &self,
+        basic_line_number: u64,
+    ) -> Option<(usize, Range<usize>)> {
+        let &file_line_number = self.basic_lines_to_file_lines.get(&basic_line_number)?;
+        let line_number_end = self.file_line_ranges[file_line_number].line_number_end;
+        Some((file_line_number, 0..line_number_end))
+    }
+
+    /// Returns the program location of the token at the given column of the
+    /// given file line, if there is one. A column just past the end of a
+    /// token counts as being on it, since that's where a cursor would be
+    /// after typing it.
+    pub fn map_source_to_location(
+        &self,
+        file_line_number: usize,
+        column: usize,
+    ) -> Option<NumberedProgramLocation> {
+        let (&basic_line_number, _) = self
+            .basic_lines_to_file_lines
+            .iter()
+            .find(|(_, &line)| line == file_line_number)?;
+        let token_ranges = self.file_line_ranges[file_line_number]
+            .token_ranges
+            .as_ref()?;
+        let token_index = token_ranges
+            .iter()
+            .position(|range| range.contains(&column))
+            .or_else(|| token_ranges.iter().position(|range| range.end == column))?;
+        Some(NumberedProgramLocation::new(basic_line_number, token_index))
+    }
+
     pub fn map_to_source(&self, message: &DiagnosticMessage) -> Option<(usize, Range<usize>)> {
         match message {
-            DiagnosticMessage::Warning(file_line_number, location, _) => {
+            DiagnosticMessage::Warning(file_line_number, location, _)
+            | DiagnosticMessage::Hint(file_line_number, location, _) => {
                 if let Some(location) = location {
                     self.map_location_to_source(&(*location).into())
                 } else {
@@ -66,12 +126,9 @@ impl SourceFileMap {
                 }
             }
             DiagnosticMessage::Error(file_line_number, err) => {
-                match &err.error {
-                    InterpreterError::Syntax(SyntaxError::Tokenization(t)) => {
-                        let range = t.string_range(self.file_line_ranges[*file_line_number].length);
-                        return Some((*file_line_number, range));
-                    }
-                    _ => {}
+                if let InterpreterError::Syntax(SyntaxError::Tokenization(t)) = &err.error {
+                    let range = t.string_range(self.file_line_ranges[*file_line_number].length);
+                    return Some((*file_line_number, range));
                 }
                 if let Some(location) = err.location {
                     self.map_location_to_source(&location)
@@ -1,4 +1,5 @@
 use crate::{
+    builtins::{is_unparenthesized_print_tab_or_spc, MID_ASSIGNMENT_TARGET, PRINT_SPC, PRINT_TAB},
     program::{Program, ProgramLocation},
     symbol::Symbol,
     InterpreterError, SyntaxError, Token, TracedInterpreterError,
@@ -33,37 +34,63 @@ impl<'a> StatementAnalyzer<'a> {
     }
 
     pub fn evaluate_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        match self.program().next_token() {
-            Some(Token::Stop) => Ok(()),
-            Some(Token::Dim) => self.evaluate_dim_statement(),
-            Some(Token::Print) | Some(Token::QuestionMark) => self.evaluate_print_statement(),
-            Some(Token::Input) => self.evaluate_input_statement(),
-            Some(Token::If) => self.evaluate_if_statement(),
-            Some(Token::Goto | Token::Gosub) => self.evaluate_goto_or_gosub_statement(),
-            Some(Token::Return) => Ok(()),
+        let Some(token) = self.program().next_token() else {
+            return Ok(());
+        };
+        match token {
+            // These statements change our location in the program at runtime,
+            // so the interpreter won't check what comes after them.
+            Token::Stop => return Ok(()),
+            Token::If => return self.evaluate_if_statement(),
+            Token::Goto | Token::Gosub => return self.evaluate_goto_or_gosub_statement(),
+            Token::Return | Token::Resume => return Ok(()),
             // Dartmouth BASIC only allowed END at the very end of a program,
             // while Applesoft allowed it anywhere. We'll do the latter.
-            Some(Token::End) => Ok(()),
-            Some(Token::For) => self.evaluate_for_statement(),
-            Some(Token::Next) => self.evaluate_next_statement(),
-            Some(Token::Restore) => Ok(self.program().reset_data_cursor()),
-            Some(Token::Def) => self.evaluate_def_statement(),
-            Some(Token::Read) => self.evaluate_read_statement(),
-            Some(Token::Remark(_)) => Ok(()),
-            Some(Token::Colon) => Ok(()),
-            Some(Token::Data(_)) => Ok(()),
-            Some(Token::Let) => self.evaluate_let_statement(),
-            Some(Token::Symbol(symbol)) => self.evaluate_assignment_statement(symbol),
-            Some(_) => Err(SyntaxError::UnexpectedToken.into()),
-            None => Ok(()),
+            Token::End => return Ok(()),
+            Token::Colon => return Ok(()),
+            Token::While => {
+                self.evaluate_expression()?;
+            }
+            Token::Wend => {}
+            Token::Dim => self.evaluate_dim_statement()?,
+            Token::Print | Token::QuestionMark => self.evaluate_print_statement()?,
+            Token::Input | Token::Get => self.evaluate_input_statement()?,
+            Token::For => self.evaluate_for_statement()?,
+            Token::Next => self.evaluate_next_statement()?,
+            Token::Restore => self.program().reset_data_cursor(),
+            Token::Def => self.evaluate_def_statement()?,
+            Token::Gr | Token::Hgr | Token::Pop | Token::Clear => {}
+            Token::Color | Token::Hcolor => {
+                self.program().expect_next_token(Token::Equals)?;
+                self.evaluate_expression()?.check_number()?;
+            }
+            Token::Call => {
+                self.evaluate_expression()?.check_number()?;
+            }
+            Token::Poke => {
+                self.evaluate_expression()?.check_number()?;
+                self.program().expect_next_token(Token::Comma)?;
+                self.evaluate_expression()?.check_number()?;
+            }
+            Token::Onerr => {
+                self.program().expect_next_token(Token::Goto)?;
+                self.evaluate_goto_or_gosub_statement()?;
+            }
+            Token::Read => self.evaluate_read_statement()?,
+            Token::Remark(_) => {}
+            Token::Data(_) => {}
+            Token::Let => self.evaluate_let_statement()?,
+            Token::Symbol(symbol) => self.evaluate_assignment_statement(symbol)?,
+            token => return Err(SyntaxError::UnexpectedToken(Some(token)).into()),
         }
+        self.program().expect_end_of_statement()
     }
 
     fn program(&mut self) -> &mut Program {
-        &mut self.program
+        self.program
     }
 
-    fn expression_analyser(&mut self) -> ExpressionAnalyzer {
+    fn expression_analyser(&mut self) -> ExpressionAnalyzer<'_> {
         ExpressionAnalyzer::new(self.program, self.symbol_accesses)
     }
 
@@ -75,9 +102,7 @@ impl<'a> StatementAnalyzer<'a> {
         if self.program().peek_next_token() != Some(Token::LeftParen) {
             Ok(None)
         } else {
-            self.expression_analyser()
-                .evaluate_array_index()
-                .map(|arity| Some(arity))
+            self.expression_analyser().evaluate_array_index().map(Some)
         }
     }
 
@@ -109,14 +134,15 @@ impl<'a> StatementAnalyzer<'a> {
         // TODO: Do something with the arity.
         let _unused_for_now = lvalue.array_index_arity;
 
-        self.log_lvalue_access(&lvalue);
+        self.log_lvalue_access(&lvalue, SymbolAccess::Write);
         ValueType::from_variable_name(lvalue.symbol_name).check(rvalue)?;
         Ok(())
     }
 
     fn evaluate_let_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(symbol_name)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(symbol_name)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         self.evaluate_assignment_statement(symbol_name)?;
         Ok(())
@@ -126,6 +152,10 @@ impl<'a> StatementAnalyzer<'a> {
         &mut self,
         symbol_name: Symbol,
     ) -> Result<(), TracedInterpreterError> {
+        if symbol_name.as_str() == MID_ASSIGNMENT_TARGET {
+            return self.evaluate_mid_assignment_statement();
+        }
+
         let symbol_location = self.program.get_prev_location();
         let lvalue = LValue {
             symbol_name,
@@ -145,9 +175,29 @@ impl<'a> StatementAnalyzer<'a> {
         Ok(())
     }
 
+    fn evaluate_mid_assignment_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        self.program().expect_next_token(Token::LeftParen)?;
+        let lvalue = self.parse_lvalue()?;
+        // The string is read from as well as written to.
+        self.log_lvalue_access(&lvalue, SymbolAccess::Read);
+        ValueType::from_variable_name(&lvalue.symbol_name).check(ValueType::String)?;
+        self.program().expect_next_token(Token::Comma)?;
+        self.evaluate_expression()?.check_number()?;
+        if self.program().accept_next_token(Token::Comma) {
+            self.evaluate_expression()?.check_number()?;
+        }
+        self.program().expect_next_token(Token::RightParen)?;
+        self.program().expect_next_token(Token::Equals)?;
+        let value = self.evaluate_expression()?;
+        ValueType::String.check(value)?;
+        self.log_lvalue_access(&lvalue, SymbolAccess::Write);
+        Ok(())
+    }
+
     fn parse_lvalue(&mut self) -> Result<LValue, TracedInterpreterError> {
-        let Some(Token::Symbol(symbol_name)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(symbol_name)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         let symbol_location = self.program.get_prev_location();
 
@@ -171,18 +221,27 @@ impl<'a> StatementAnalyzer<'a> {
         Ok(())
     }
 
-    fn log_lvalue_access(&mut self, lvalue: &LValue) {
-        self.symbol_accesses.log_access(
-            &lvalue.symbol_name,
-            &lvalue.symbol_location,
-            SymbolAccess::Write,
-        );
+    fn log_lvalue_access(&mut self, lvalue: &LValue, access: SymbolAccess) {
+        if lvalue.array_index_arity.is_some() {
+            self.symbol_accesses.log_array_access(
+                &lvalue.symbol_name,
+                &lvalue.symbol_location,
+                access,
+            );
+        } else {
+            self.symbol_accesses
+                .log_access(&lvalue.symbol_name, &lvalue.symbol_location, access);
+        }
     }
 
     fn evaluate_input_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        // TODO: Support multiple comma-separated items.
-        let lvalue = self.parse_lvalue()?;
-        self.log_lvalue_access(&lvalue);
+        loop {
+            let lvalue = self.parse_lvalue()?;
+            self.log_lvalue_access(&lvalue, SymbolAccess::Write);
+            if !self.program().accept_next_token(Token::Comma) {
+                break;
+            }
+        }
         Ok(())
     }
 
@@ -193,18 +252,37 @@ impl<'a> StatementAnalyzer<'a> {
     /// arrays to be dynamically sized based on user input and such.
     fn evaluate_dim_statement(&mut self) -> Result<(), TracedInterpreterError> {
         let lvalue = self.parse_lvalue()?;
-        self.log_lvalue_access(&lvalue);
+        self.log_lvalue_access(&lvalue, SymbolAccess::Write);
         // TODO: Do something with the array index arity.
         Ok(())
     }
 
     fn evaluate_print_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        if self.program().accept_next_token(Token::At) {
+            self.evaluate_expression()?.check_number()?;
+            self.program().expect_next_token(Token::Comma)?;
+            self.evaluate_expression()?.check_number()?;
+        }
         while let Some(token) = self.program().peek_next_token() {
             match token {
                 Token::Colon | Token::Else => break,
                 Token::Semicolon | Token::Comma => {
                     self.program().next_token().unwrap();
                 }
+                Token::Symbol(symbol)
+                    if symbol.as_str() == PRINT_TAB || symbol.as_str() == PRINT_SPC =>
+                {
+                    self.program().next_token().unwrap();
+                    if !self.program().accept_next_token(Token::LeftParen)
+                        || self.evaluate_expression()?.check_number().is_err()
+                        || !self.program().accept_next_token(Token::RightParen)
+                    {
+                        return Err(SyntaxError::InvalidTabOrSpcArgument.into());
+                    }
+                }
+                Token::Symbol(symbol) if is_unparenthesized_print_tab_or_spc(symbol.as_str()) => {
+                    return Err(SyntaxError::InvalidTabOrSpcArgument.into());
+                }
                 _ => {
                     self.evaluate_expression()?;
                 }
@@ -229,14 +307,15 @@ impl<'a> StatementAnalyzer<'a> {
     }
 
     fn evaluate_for_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(symbol)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(symbol)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
-        self.symbol_accesses.log_access(
-            &symbol,
-            &self.program.get_prev_location(),
-            SymbolAccess::Write,
-        );
+        let symbol_location = self.program.get_prev_location();
+        self.symbol_accesses
+            .log_access(&symbol, &symbol_location, SymbolAccess::Write);
+        self.symbol_accesses
+            .log_loop_start(&symbol, &symbol_location);
         ValueType::from_variable_name(&symbol).check_number()?;
         self.program().expect_next_token(Token::Equals)?;
         let _from_value = self.evaluate_expression()?.check_number()?;
@@ -251,21 +330,33 @@ impl<'a> StatementAnalyzer<'a> {
     }
 
     fn evaluate_next_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(symbol)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
-        };
-        self.symbol_accesses.log_access(
-            &symbol,
-            &self.program.get_prev_location(),
-            SymbolAccess::Read,
-        );
-        ValueType::from_variable_name(&symbol).check_number()?;
+        if !matches!(self.program().peek_next_token(), Some(Token::Symbol(_))) {
+            self.symbol_accesses.log_loop_end(None);
+        }
+        while let Some(Token::Symbol(symbol)) = self.program().peek_next_token() {
+            self.program().next_token();
+            self.symbol_accesses.log_access(
+                &symbol,
+                &self.program.get_prev_location(),
+                SymbolAccess::Read,
+            );
+            self.symbol_accesses.log_loop_end(Some(&symbol));
+            ValueType::from_variable_name(&symbol).check_number()?;
+            if !self.program().accept_next_token(Token::Comma) {
+                break;
+            }
+            // A comma must be followed by another loop variable.
+            if !matches!(self.program().peek_next_token(), Some(Token::Symbol(_))) {
+                return Err(SyntaxError::UnexpectedToken(self.program().next_token()).into());
+            }
+        }
         Ok(())
     }
 
     fn evaluate_def_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(function_name)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(function_name)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         self.symbol_accesses.log_access(
             &function_name,
@@ -276,23 +367,31 @@ impl<'a> StatementAnalyzer<'a> {
         let mut arg_names: Vec<Symbol> = vec![];
         loop {
             // Note that in Applesoft BASIC, all functions must have at least one argument.
-            let Some(Token::Symbol(arg_name)) = self.program().next_token() else {
-                return Err(SyntaxError::UnexpectedToken.into());
+            let token = self.program().next_token();
+            let Some(Token::Symbol(arg_name)) = token else {
+                return Err(SyntaxError::UnexpectedToken(token).into());
             };
+            self.symbol_accesses
+                .log_function_parameter(&arg_name, &self.program.get_prev_location());
             arg_names.push(arg_name);
             match self.program().next_token() {
                 Some(Token::Comma) => {
                     // Keep looping to parse additional arguments.
                 }
                 Some(Token::RightParen) => break,
-                _ => return Err(SyntaxError::UnexpectedToken.into()),
+                token => return Err(SyntaxError::UnexpectedToken(token).into()),
             }
         }
         self.program().expect_next_token(Token::Equals)?;
-        self.program().define_function(function_name, arg_names)?;
-
-        // Evaluate the function body.
-        self.evaluate_expression()?;
+        self.symbol_accesses.log_function_body_start(&arg_names);
+        let define_result = self
+            .program()
+            .define_function(function_name.clone(), arg_names);
+
+        // Evaluate the function body, which must match the function's type.
+        let body_result = define_result.and_then(|_| self.evaluate_expression());
+        self.symbol_accesses.log_function_body_end();
+        body_result?.check_variable_name(function_name)?;
 
         Ok(())
     }
@@ -10,6 +10,8 @@ pub enum SymbolAccessWarning {
     UndefinedSymbol,
     /// A symbol is written to (i.e., defined), but never read from.
     UnusedSymbol,
+    /// A symbol is read from before it's first written to, in program order.
+    UsedBeforeDefined,
 }
 
 pub enum SymbolAccess {
@@ -21,32 +23,168 @@ pub enum SymbolAccess {
 struct SymbolAccessLocations {
     writes: Vec<NumberedProgramLocation>,
     reads: Vec<NumberedProgramLocation>,
+    /// The first read of the symbol, if it happened before any writes.
+    first_read_before_write: Option<NumberedProgramLocation>,
+}
+
+impl SymbolAccessLocations {
+    fn log(&mut self, location: NumberedProgramLocation, access: SymbolAccess) {
+        match access {
+            SymbolAccess::Read => {
+                if self.writes.is_empty() && self.reads.is_empty() {
+                    self.first_read_before_write = Some(location);
+                }
+                self.reads.push(location);
+            }
+            SymbolAccess::Write => self.writes.push(location),
+        }
+    }
 }
 
 #[derive(Default)]
-pub struct SymbolAccessMap(HashMap<Symbol, SymbolAccessLocations>);
+pub struct SymbolAccessMap {
+    accesses: HashMap<Symbol, SymbolAccessLocations>,
+    /// Arrays live in a separate namespace from scalar variables, e.g. `A(1)`
+    /// has nothing to do with `A`.
+    array_accesses: HashMap<Symbol, SymbolAccessLocations>,
+    /// The parameters of the function whose body is currently being analyzed.
+    function_body_parameters: Vec<Symbol>,
+    function_parameters: Vec<(Symbol, NumberedProgramLocation)>,
+    open_loops: Vec<(Symbol, NumberedProgramLocation)>,
+}
 
 impl SymbolAccessMap {
+    pub fn log_function_parameter(&mut self, symbol: &Symbol, location: &ProgramLocation) {
+        self.function_parameters
+            .push((symbol.clone(), (*location).try_into().unwrap()));
+    }
+
+    /// Returns every function parameter that has the same name as a
+    /// variable that's assigned to elsewhere in the program.
+    pub fn get_shadowing_function_parameters(&self) -> Vec<(Symbol, NumberedProgramLocation)> {
+        self.function_parameters
+            .iter()
+            .filter(|(symbol, _)| {
+                self.accesses
+                    .get(symbol)
+                    .is_some_and(|locations| !locations.writes.is_empty())
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Logs the start of a `FOR` loop. Like the interpreter, this forgets
+    /// about any existing loop with the same name, along with any loops
+    /// nested inside it.
+    pub fn log_loop_start(&mut self, symbol: &Symbol, location: &ProgramLocation) {
+        self.forget_loop_with_name(symbol);
+        self.open_loops
+            .push((symbol.clone(), (*location).try_into().unwrap()));
+    }
+
+    /// Logs a `NEXT` for the loop with the given name, or for the innermost
+    /// loop if no name is given.
+    pub fn log_loop_end(&mut self, symbol: Option<&Symbol>) {
+        match symbol {
+            Some(symbol) => self.forget_loop_with_name(symbol),
+            None => {
+                self.open_loops.pop();
+            }
+        }
+    }
+
+    fn forget_loop_with_name(&mut self, symbol: &Symbol) {
+        if let Some(i) = self.open_loops.iter().rposition(|(s, _)| s == symbol) {
+            self.open_loops.truncate(i);
+        }
+    }
+
+    /// Returns every `FOR` loop that doesn't have a `NEXT` after it in the
+    /// program listing.
+    pub fn get_unclosed_loops(&self) -> Vec<(Symbol, NumberedProgramLocation)> {
+        self.open_loops.clone()
+    }
+
+    /// Logs the start of a function body. Until the body ends, reads of the
+    /// given parameters refer to the function's arguments rather than to any
+    /// variables, so they aren't logged.
+    pub fn log_function_body_start(&mut self, parameters: &[Symbol]) {
+        self.function_body_parameters = parameters.to_vec();
+    }
+
+    pub fn log_function_body_end(&mut self) {
+        self.function_body_parameters.clear();
+    }
+
     pub fn log_access(
         &mut self,
         symbol: &Symbol,
         location: &ProgramLocation,
         access: SymbolAccess,
     ) {
-        let entry = self.0.entry(symbol.clone()).or_default();
-        let target = match access {
-            SymbolAccess::Read => &mut entry.reads,
-            SymbolAccess::Write => &mut entry.writes,
-        };
+        if matches!(access, SymbolAccess::Read) && self.function_body_parameters.contains(symbol) {
+            return;
+        }
 
         // We're analyzing code, so we should always be passed in a
         // numbered program location.
-        target.push((*location).try_into().unwrap());
+        self.accesses
+            .entry(symbol.clone())
+            .or_default()
+            .log((*location).try_into().unwrap(), access);
+    }
+
+    pub fn log_array_access(
+        &mut self,
+        symbol: &Symbol,
+        location: &ProgramLocation,
+        access: SymbolAccess,
+    ) {
+        self.array_accesses
+            .entry(symbol.clone())
+            .or_default()
+            .log((*location).try_into().unwrap(), access);
+    }
+
+    /// Returns the name of every scalar variable (or function) that's
+    /// accessed anywhere in the program.
+    pub fn scalar_symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.accesses.keys()
+    }
+
+    /// Returns the name of every array that's accessed anywhere in the
+    /// program.
+    pub fn array_symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.array_accesses.keys()
+    }
+
+    fn all_accesses(&self) -> impl Iterator<Item = (&Symbol, &SymbolAccessLocations)> {
+        self.accesses.iter().chain(self.array_accesses.iter())
+    }
+
+    /// If a symbol is accessed at the given location, returns the location
+    /// where it's first written to (i.e., defined), if any.
+    pub fn find_definition(
+        &self,
+        location: NumberedProgramLocation,
+    ) -> Option<NumberedProgramLocation> {
+        let is_at_location = |other: &NumberedProgramLocation| {
+            other.line == location.line && other.token_index == location.token_index
+        };
+        let (_, locations) = self.all_accesses().find(|(_, locations)| {
+            locations.reads.iter().any(is_at_location)
+                || locations.writes.iter().any(is_at_location)
+        })?;
+        locations
+            .writes
+            .iter()
+            .min_by_key(|other| (other.line, other.token_index))
+            .copied()
     }
 
     pub fn get_warnings(&self) -> Vec<(SymbolAccessWarning, Symbol, NumberedProgramLocation)> {
         let mut warnings = vec![];
-        for (symbol, locations) in &self.0 {
+        for (symbol, locations) in self.all_accesses() {
             if locations.reads.is_empty() && !locations.writes.is_empty() {
                 for &location in &locations.writes {
                     warnings.push((SymbolAccessWarning::UnusedSymbol, symbol.clone(), location));
@@ -59,8 +197,15 @@ impl SymbolAccessMap {
                         location,
                     ));
                 }
+            } else if let Some(location) = locations.first_read_before_write {
+                warnings.push((
+                    SymbolAccessWarning::UsedBeforeDefined,
+                    symbol.clone(),
+                    location,
+                ));
             }
         }
+        warnings.sort_by_key(|(_, _, location)| (location.line, location.token_index));
         warnings
     }
 }
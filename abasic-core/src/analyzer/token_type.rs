@@ -9,9 +9,65 @@ pub enum TokenType {
     Comment,
     Keyword,
     Delimiter,
+    /// A comma in a `PRINT` statement, which moves to the next print zone
+    /// rather than separating items in a list.
+    PrintDelimiter,
     Data,
 }
 
+impl TokenType {
+    /// Every token type, in a stable order. Clients like the LSP server can
+    /// use this to build their legend of token types, and `index()` to
+    /// refer to entries in it.
+    pub const ALL: [TokenType; 9] = [
+        TokenType::Symbol,
+        TokenType::String,
+        TokenType::Number,
+        TokenType::Operator,
+        TokenType::Comment,
+        TokenType::Keyword,
+        TokenType::Delimiter,
+        TokenType::PrintDelimiter,
+        TokenType::Data,
+    ];
+
+    /// Returns the position of this token type in `TokenType::ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Classifies each of the given tokens from a single line of code.
+///
+/// This is mostly the same as converting each token to a `TokenType`
+/// individually, but it takes context into account where needed, e.g. to
+/// distinguish a comma in a `PRINT` statement from one in a list.
+pub fn classify_tokens(tokens: &[Token]) -> Vec<TokenType> {
+    let mut in_print_statement = false;
+    let mut paren_depth: usize = 0;
+    tokens
+        .iter()
+        .map(|token| {
+            match token {
+                Token::Print | Token::QuestionMark => {
+                    in_print_statement = true;
+                    paren_depth = 0;
+                }
+                Token::Colon | Token::Then | Token::Else => {
+                    in_print_statement = false;
+                }
+                Token::LeftParen => paren_depth += 1,
+                Token::RightParen => paren_depth = paren_depth.saturating_sub(1),
+                Token::Comma if in_print_statement && paren_depth == 0 => {
+                    return TokenType::PrintDelimiter;
+                }
+                _ => {}
+            }
+            token.into()
+        })
+        .collect()
+}
+
 impl From<&Token> for TokenType {
     fn from(value: &Token) -> Self {
         match value {
@@ -54,6 +110,21 @@ impl From<&Token> for TokenType {
             Token::Read => TokenType::Keyword,
             Token::Restore => TokenType::Keyword,
             Token::Def => TokenType::Keyword,
+            Token::Gr => TokenType::Keyword,
+            Token::Hgr => TokenType::Keyword,
+            Token::Color => TokenType::Keyword,
+            Token::Hcolor => TokenType::Keyword,
+            Token::Call => TokenType::Keyword,
+            Token::Poke => TokenType::Keyword,
+            Token::At => TokenType::Keyword,
+            Token::Get => TokenType::Keyword,
+            Token::Onerr => TokenType::Keyword,
+            Token::Resume => TokenType::Keyword,
+            Token::Iif => TokenType::Keyword,
+            Token::While => TokenType::Keyword,
+            Token::Wend => TokenType::Keyword,
+            Token::Pop => TokenType::Keyword,
+            Token::Clear => TokenType::Keyword,
             Token::Remark(_) => TokenType::Comment,
             Token::Symbol(_) => TokenType::Symbol,
             Token::StringLiteral(_) => TokenType::String,
@@ -0,0 +1,20 @@
+/// A variable or array used somewhere in a source file, e.g. for offering
+/// completions.
+#[derive(Debug, PartialEq)]
+pub struct VariableSymbol {
+    pub name: String,
+    /// Whether the variable holds strings, i.e. its name ends with `$`.
+    pub is_string: bool,
+    /// Whether this is an array, e.g. `A` in `A(1)`. Note that arrays live
+    /// in a separate namespace from scalar variables of the same name.
+    pub is_array: bool,
+}
+
+impl VariableSymbol {
+    /// Returns a short description of the variable, e.g. `string array`.
+    pub fn detail(&self) -> String {
+        let value_type = if self.is_string { "string" } else { "numeric" };
+        let kind = if self.is_array { "array" } else { "variable" };
+        format!("{value_type} {kind}")
+    }
+}
@@ -34,7 +34,7 @@ impl Arrays {
         // because we might actually error when creating the default value.
         if !self.0.contains_key(array_name) {
             let array = ValueArray::default_for_variable_and_dimensionality(
-                &array_name.as_str(),
+                array_name.as_str(),
                 dimensions,
             )?;
             self.0.insert(array_name.clone(), array);
@@ -58,18 +58,18 @@ impl Arrays {
     pub fn get_value_at_index(
         &mut self,
         array_name: &Symbol,
-        index: &Vec<usize>,
+        index: &[usize],
     ) -> Result<Value, TracedInterpreterError> {
         self.maybe_create_default_array(array_name, index.len())?;
         let array = self.0.get(array_name).unwrap();
 
-        Ok(array.get(index)?)
+        array.get(index)
     }
 
     pub fn set_value_at_index(
         &mut self,
         array_name: &Symbol,
-        index: &Vec<usize>,
+        index: &[usize],
         value: Value,
     ) -> Result<(), TracedInterpreterError> {
         value.validate_type_matches_variable_name(array_name.as_str())?;
@@ -138,7 +138,7 @@ pub struct DimArray<T: Default + Clone> {
 
 impl<T: Default + Clone> DimArray<T> {
     pub fn new(max_indices: &[usize]) -> Result<Self, InterpreterError> {
-        if max_indices.len() == 0 {
+        if max_indices.is_empty() {
             // Technically Applesoft BASIC returns a syntax error for this
             // but bad subscript seems more specific.
             return Err(InterpreterError::BadSubscript);
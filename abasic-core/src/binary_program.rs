@@ -0,0 +1,325 @@
+use std::{error::Error, fmt::Display, rc::Rc};
+
+use crate::{data::DataElement, string_manager::StringManager, tokenizer::Token};
+
+/// Every binary program starts with these bytes.
+const MAGIC: &[u8; 4] = b"ABAS";
+
+/// The version of the binary format. This should be incremented whenever
+/// the format changes in a way that makes it incompatible with previous
+/// versions (e.g., the opcode of a token changes).
+const VERSION: u8 = 1;
+
+/// Tokens without any payload are encoded as their index in this list.
+/// New tokens should only ever be added to the end of it, to preserve
+/// compatibility with existing files.
+const SIMPLE_TOKENS: &[Token] = &[
+    Token::Dim,
+    Token::Let,
+    Token::Print,
+    Token::Input,
+    Token::Goto,
+    Token::Gosub,
+    Token::Return,
+    Token::Colon,
+    Token::Semicolon,
+    Token::Comma,
+    Token::QuestionMark,
+    Token::LeftParen,
+    Token::RightParen,
+    Token::Plus,
+    Token::Minus,
+    Token::Multiply,
+    Token::Divide,
+    Token::Caret,
+    Token::Equals,
+    Token::NotEquals,
+    Token::LessThan,
+    Token::LessThanOrEqualTo,
+    Token::GreaterThan,
+    Token::GreaterThanOrEqualTo,
+    Token::And,
+    Token::Or,
+    Token::Not,
+    Token::If,
+    Token::Then,
+    Token::Else,
+    Token::End,
+    Token::Stop,
+    Token::For,
+    Token::To,
+    Token::Step,
+    Token::Next,
+    Token::Read,
+    Token::Restore,
+    Token::Def,
+    Token::Gr,
+    Token::Hgr,
+    Token::Color,
+    Token::Hcolor,
+    Token::Call,
+    Token::Poke,
+    Token::At,
+    Token::Get,
+    Token::Onerr,
+    Token::Resume,
+    Token::Iif,
+    Token::While,
+    Token::Wend,
+    Token::Pop,
+    Token::Clear,
+];
+
+// Tokens with payloads are encoded with these opcodes, which are placed at
+// the end of the opcode space so they never collide with simple tokens.
+const REMARK_OPCODE: u8 = 0xf0;
+const SYMBOL_OPCODE: u8 = 0xf1;
+const STRING_LITERAL_OPCODE: u8 = 0xf2;
+const NUMERIC_LITERAL_OPCODE: u8 = 0xf3;
+const DATA_OPCODE: u8 = 0xf4;
+
+const DATA_STRING_TAG: u8 = 0;
+const DATA_NUMBER_TAG: u8 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum BinaryProgramError {
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    InvalidOpcode(u8),
+    InvalidString,
+    /// A number in the program is NaN or infinite, which the tokenizer
+    /// never produces.
+    NonFiniteNumber,
+    /// The program has a line number larger than the interpreter allows.
+    LineNumberTooLarge(u64),
+    /// The program uses a token from a dialect feature that isn't enabled
+    /// in the interpreter, e.g. `WHILE` when `enable_while_loops` is off.
+    DisabledToken(Token),
+}
+
+impl Error for BinaryProgramError {}
+
+impl Display for BinaryProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryProgramError::InvalidHeader => write!(f, "NOT A BINARY PROGRAM"),
+            BinaryProgramError::UnsupportedVersion(version) => {
+                write!(f, "UNSUPPORTED BINARY PROGRAM VERSION {version}")
+            }
+            BinaryProgramError::UnexpectedEnd => write!(f, "UNEXPECTED END OF BINARY PROGRAM"),
+            BinaryProgramError::InvalidOpcode(opcode) => write!(f, "INVALID OPCODE {opcode}"),
+            BinaryProgramError::InvalidString => write!(f, "INVALID STRING"),
+            BinaryProgramError::NonFiniteNumber => write!(f, "INVALID NUMBER"),
+            BinaryProgramError::LineNumberTooLarge(line_number) => {
+                write!(f, "LINE NUMBER {line_number} TOO LARGE")
+            }
+            BinaryProgramError::DisabledToken(token) => write!(f, "'{token}' IS NOT ENABLED"),
+        }
+    }
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("value should fit in 32 bits");
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn token(&mut self, token: &Token) {
+        match token {
+            Token::Remark(comment) => {
+                self.u8(REMARK_OPCODE);
+                self.string(comment);
+            }
+            Token::Symbol(symbol) => {
+                self.u8(SYMBOL_OPCODE);
+                self.string(symbol.as_str());
+            }
+            Token::StringLiteral(string) => {
+                self.u8(STRING_LITERAL_OPCODE);
+                self.string(string);
+            }
+            Token::NumericLiteral(number) => {
+                self.u8(NUMERIC_LITERAL_OPCODE);
+                self.f64(*number);
+            }
+            Token::Data(elements) => {
+                self.u8(DATA_OPCODE);
+                self.u32(elements.len());
+                for element in elements.iter() {
+                    match element {
+                        DataElement::String(string) => {
+                            self.u8(DATA_STRING_TAG);
+                            self.string(string);
+                        }
+                        DataElement::Number(number) => {
+                            self.u8(DATA_NUMBER_TAG);
+                            self.f64(*number);
+                        }
+                    }
+                }
+            }
+            _ => {
+                let opcode = SIMPLE_TOKENS
+                    .iter()
+                    .position(|simple_token| simple_token == token)
+                    .expect("all tokens without payloads should be in SIMPLE_TOKENS");
+                self.u8(opcode as u8);
+            }
+        }
+    }
+}
+
+/// Encodes the given numbered lines into a compact binary format, similar
+/// in spirit to the tokenized format Applesoft BASIC stored programs in.
+///
+/// The format is the magic bytes `ABAS`, a version byte, and the number of
+/// lines, followed by each line's number, its number of tokens, and the
+/// tokens themselves. Each token is a single-byte opcode, followed by its
+/// payload (if any). All integers are little-endian.
+pub fn encode_program(lines: &[(u64, &Vec<Token>)]) -> Vec<u8> {
+    let mut encoder = Encoder { bytes: vec![] };
+    encoder.bytes.extend_from_slice(MAGIC);
+    encoder.u8(VERSION);
+    encoder.u32(lines.len());
+    for (line_number, tokens) in lines {
+        encoder.u64(*line_number);
+        encoder.u32(tokens.len());
+        for token in tokens.iter() {
+            encoder.token(token);
+        }
+    }
+    encoder.bytes
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    string_manager: &'a mut StringManager,
+}
+
+impl<'a> Decoder<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], BinaryProgramError> {
+        if self.bytes.len() < N {
+            return Err(BinaryProgramError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(taken.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, BinaryProgramError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, BinaryProgramError> {
+        Ok(u32::from_le_bytes(self.take()?) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64, BinaryProgramError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, BinaryProgramError> {
+        let number = f64::from_le_bytes(self.take()?);
+        if !number.is_finite() {
+            return Err(BinaryProgramError::NonFiniteNumber);
+        }
+        Ok(number)
+    }
+
+    fn string(&mut self) -> Result<Rc<String>, BinaryProgramError> {
+        let len = self.u32()?;
+        if self.bytes.len() < len {
+            return Err(BinaryProgramError::UnexpectedEnd);
+        }
+        let (string, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let Ok(string) = std::str::from_utf8(string) else {
+            return Err(BinaryProgramError::InvalidString);
+        };
+        Ok(self.string_manager.from_str(string))
+    }
+
+    fn token(&mut self) -> Result<Token, BinaryProgramError> {
+        let opcode = self.u8()?;
+        let token = match opcode {
+            REMARK_OPCODE => Token::Remark(self.string()?),
+            SYMBOL_OPCODE => Token::Symbol(self.string()?.into()),
+            STRING_LITERAL_OPCODE => Token::StringLiteral(self.string()?),
+            NUMERIC_LITERAL_OPCODE => Token::NumericLiteral(self.f64()?),
+            DATA_OPCODE => {
+                let len = self.u32()?;
+                let mut elements = Vec::with_capacity(len.min(self.bytes.len()));
+                for _ in 0..len {
+                    elements.push(match self.u8()? {
+                        DATA_STRING_TAG => DataElement::String(self.string()?),
+                        DATA_NUMBER_TAG => DataElement::Number(self.f64()?),
+                        tag => return Err(BinaryProgramError::InvalidOpcode(tag)),
+                    });
+                }
+                Token::Data(Rc::new(elements))
+            }
+            _ => SIMPLE_TOKENS
+                .get(opcode as usize)
+                .ok_or(BinaryProgramError::InvalidOpcode(opcode))?
+                .clone(),
+        };
+        Ok(token)
+    }
+}
+
+/// Decodes a program encoded via `encode_program`, returning its numbered
+/// lines.
+pub fn decode_program(
+    bytes: &[u8],
+    string_manager: &mut StringManager,
+) -> Result<Vec<(u64, Vec<Token>)>, BinaryProgramError> {
+    let mut decoder = Decoder {
+        bytes,
+        string_manager,
+    };
+    if &decoder
+        .take::<4>()
+        .map_err(|_| BinaryProgramError::InvalidHeader)?
+        != MAGIC
+    {
+        return Err(BinaryProgramError::InvalidHeader);
+    }
+    let version = decoder.u8()?;
+    if version != VERSION {
+        return Err(BinaryProgramError::UnsupportedVersion(version));
+    }
+    let num_lines = decoder.u32()?;
+    let mut lines = vec![];
+    for _ in 0..num_lines {
+        let line_number = decoder.u64()?;
+        let num_tokens = decoder.u32()?;
+        let mut tokens = Vec::with_capacity(num_tokens.min(decoder.bytes.len()));
+        for _ in 0..num_tokens {
+            tokens.push(decoder.token()?);
+        }
+        lines.push((line_number, tokens));
+    }
+    Ok(lines)
+}
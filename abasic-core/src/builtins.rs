@@ -1,18 +1,118 @@
 use crate::symbol::Symbol;
 
+/// The name of the pseudo-variable that can be assigned to in order to
+/// replace part of a string, e.g. `MID$(A$, 2, 3) = "XYZ"`.
+pub const MID_ASSIGNMENT_TARGET: &str = "MID$";
+
+/// The name of the pseudo-function that can be used in `PRINT` statements to
+/// move to a column, e.g. `PRINT TAB(5);"X"`.
+pub const PRINT_TAB: &str = "TAB";
+
+/// The name of the pseudo-function that can be used in `PRINT` statements to
+/// output spaces, e.g. `PRINT "A";SPC(3);"B"`.
+pub const PRINT_SPC: &str = "SPC";
+
+/// Returns whether the given symbol is `TAB` or `SPC` followed only by
+/// digits. This is almost certainly a missing parenthesis, since line
+/// crunching turns e.g. `PRINT TAB 5` into a reference to `TAB5`.
+pub fn is_unparenthesized_print_tab_or_spc(name: &str) -> bool {
+    [PRINT_TAB, PRINT_SPC].iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|rest| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
 pub enum Builtin {
     Abs,
+    Asc,
+    Atn,
+    Chr,
+    Cos,
+    Exp,
     Int,
+    Left,
+    Len,
+    Log,
+    Mid,
+    Peek,
+    Pos,
+    Right,
     Rnd,
+    Sin,
+    Sqr,
+    Tan,
 }
 
 impl Builtin {
     pub fn try_from(value: &Symbol) -> Option<Builtin> {
         Some(match value.as_str() {
             "ABS" => Builtin::Abs,
+            "ASC" => Builtin::Asc,
+            "ATN" => Builtin::Atn,
+            "CHR$" => Builtin::Chr,
+            "COS" => Builtin::Cos,
+            "EXP" => Builtin::Exp,
             "INT" => Builtin::Int,
+            "LEFT$" => Builtin::Left,
+            "LEN" => Builtin::Len,
+            "LOG" => Builtin::Log,
+            "MID$" => Builtin::Mid,
+            "PEEK" => Builtin::Peek,
+            "POS" => Builtin::Pos,
+            "RIGHT$" => Builtin::Right,
             "RND" => Builtin::Rnd,
+            "SIN" => Builtin::Sin,
+            "SQR" => Builtin::Sqr,
+            "TAN" => Builtin::Tan,
             _ => return None,
         })
     }
+
+    /// Returns how the builtin is called, e.g. `MID$(A$, START[, LENGTH])`.
+    pub fn signature(&self) -> &'static str {
+        match self {
+            Builtin::Abs => "ABS(X)",
+            Builtin::Asc => "ASC(A$)",
+            Builtin::Atn => "ATN(X)",
+            Builtin::Chr => "CHR$(X)",
+            Builtin::Cos => "COS(X)",
+            Builtin::Exp => "EXP(X)",
+            Builtin::Int => "INT(X)",
+            Builtin::Left => "LEFT$(A$, LENGTH)",
+            Builtin::Len => "LEN(A$)",
+            Builtin::Log => "LOG(X)",
+            Builtin::Mid => "MID$(A$, START[, LENGTH])",
+            Builtin::Peek => "PEEK(ADDRESS)",
+            Builtin::Pos => "POS(X)",
+            Builtin::Right => "RIGHT$(A$, LENGTH)",
+            Builtin::Rnd => "RND(X)",
+            Builtin::Sin => "SIN(X)",
+            Builtin::Sqr => "SQR(X)",
+            Builtin::Tan => "TAN(X)",
+        }
+    }
+
+    /// Returns a one-line description of what the builtin does.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Builtin::Abs => "Returns the absolute value of a number.",
+            Builtin::Asc => "Returns the character code of the first character of a string.",
+            Builtin::Atn => "Returns the arctangent of a number, in radians.",
+            Builtin::Chr => "Returns a string containing the character with the given code.",
+            Builtin::Cos => "Returns the cosine of an angle in radians.",
+            Builtin::Exp => "Returns e raised to the given power.",
+            Builtin::Int => "Returns the largest integer less than or equal to a number.",
+            Builtin::Left => "Returns the given number of characters from the start of a string.",
+            Builtin::Len => "Returns the number of characters in a string.",
+            Builtin::Log => "Returns the natural logarithm of a number.",
+            Builtin::Mid => "Returns part of a string, starting at the given 1-based position.",
+            Builtin::Peek => "Returns the value at a memory address.",
+            Builtin::Pos => "Returns the cursor's current column. The argument is ignored.",
+            Builtin::Right => "Returns the given number of characters from the end of a string.",
+            Builtin::Rnd => "Returns a random number between 0 and 1.",
+            Builtin::Sin => "Returns the sine of an angle in radians.",
+            Builtin::Sqr => "Returns the square root of a number.",
+            Builtin::Tan => "Returns the tangent of an angle in radians.",
+        }
+    }
 }
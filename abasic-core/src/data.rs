@@ -30,12 +30,24 @@ impl DataIterator {
         }
     }
 
+    /// Returns the location of the `DATA` statement that the most recently
+    /// read element came from. Note that we only move on to the next chunk
+    /// when another element is requested, so this stays accurate even when
+    /// the element was the last one in its chunk.
     pub fn current_location(&self) -> Option<ProgramLocation> {
-        if let Some(chunk) = self.chunks.get(self.chunk_index) {
-            Some(chunk.location)
-        } else {
-            None
-        }
+        self.chunks
+            .get(self.chunk_index)
+            .map(|chunk| chunk.location)
+    }
+
+    /// Returns the number of elements that haven't been read yet.
+    pub fn remaining(&self) -> usize {
+        self.chunks
+            .iter()
+            .skip(self.chunk_index)
+            .map(|chunk| chunk.data.len())
+            .sum::<usize>()
+            .saturating_sub(self.chunk_item_index)
     }
 }
 
@@ -44,9 +56,7 @@ impl Iterator for DataIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let Some(chunk) = self.chunks.get(self.chunk_index) else {
-                return None;
-            };
+            let chunk = self.chunks.get(self.chunk_index)?;
             let Some(element) = chunk.data.get(self.chunk_item_index) else {
                 self.chunk_item_index = 0;
                 self.chunk_index += 1;
@@ -64,7 +74,7 @@ pub enum DataElement {
     Number(f64),
 }
 
-pub fn data_elements_to_string(elements: &Vec<DataElement>) -> String {
+pub fn data_elements_to_string(elements: &[DataElement]) -> String {
     elements
         .iter()
         .map(|element| match element {
@@ -89,6 +99,7 @@ struct DataParser<'a> {
     bytes_chomped: usize,
     current_element: String,
     is_finished: bool,
+    ignore_quotes: bool,
     string_manager: Option<&'a mut StringManager>,
 }
 
@@ -107,7 +118,13 @@ impl<'a> DataParser<'a> {
 
         let element = if self.state != ParseState::InDoubleQuotedString {
             string_value = string_value.trim().to_string();
-            if let Ok(number) = string_value.parse::<f64>() {
+            // Rust happily parses things like "inf" and "NaN", which aren't
+            // numbers as far as BASIC is concerned.
+            if let Some(number) = string_value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+            {
                 DataElement::Number(number)
             } else {
                 self.make_string_data_element(string_value)
@@ -130,13 +147,9 @@ impl<'a> DataParser<'a> {
                         self.push_current_element();
                     }
                 }
-                '"' => {
-                    if self.current_element.trim().is_empty() {
-                        self.current_element.clear();
-                        self.state = ParseState::InDoubleQuotedString;
-                    } else {
-                        self.current_element.push(char);
-                    }
+                '"' if !self.ignore_quotes && self.current_element.trim().is_empty() => {
+                    self.current_element.clear();
+                    self.state = ParseState::InDoubleQuotedString;
                 }
                 _ => {
                     self.current_element.push(char);
@@ -162,9 +175,7 @@ impl<'a> DataParser<'a> {
             return;
         }
 
-        if self.current_element.len() > 0 {
-            self.push_current_element();
-        } else if self.elements.len() == 0 {
+        if !self.current_element.is_empty() || self.elements.is_empty() {
             self.push_current_element();
         }
 
@@ -184,9 +195,29 @@ pub fn parse_data_until_colon(
     value: &str,
     string_manager: Option<&mut StringManager>,
 ) -> (Vec<DataElement>, usize) {
-    let mut parser = DataParser::default();
+    parse_data(value, string_manager, false)
+}
+
+/// Like `parse_data_until_colon()`, but double quotes are treated like
+/// any other character, so a comma always separates elements, even if
+/// it's between quotes.
+pub fn parse_data_until_colon_ignoring_quotes(
+    value: &str,
+    string_manager: Option<&mut StringManager>,
+) -> (Vec<DataElement>, usize) {
+    parse_data(value, string_manager, true)
+}
 
-    parser.string_manager = string_manager;
+fn parse_data(
+    value: &str,
+    string_manager: Option<&mut StringManager>,
+    ignore_quotes: bool,
+) -> (Vec<DataElement>, usize) {
+    let mut parser = DataParser {
+        ignore_quotes,
+        string_manager,
+        ..Default::default()
+    };
 
     for char in value.chars() {
         parser.parse_char(char);
@@ -220,7 +251,7 @@ mod tests {
     use std::rc::Rc;
 
     use crate::{
-        data::{parse_data_until_colon, DataChunk},
+        data::{parse_data_until_colon, parse_data_until_colon_ignoring_quotes, DataChunk},
         program::NumberedProgramLocation,
     };
 
@@ -265,6 +296,25 @@ mod tests {
         assert_eq!(iterator.next(), None);
     }
 
+    #[test]
+    fn data_iterator_remaining_works() {
+        let mut iterator = DataIterator::new(vec![
+            DataChunk::new(
+                NumberedProgramLocation::new(10, 0).into(),
+                Rc::new(vec![string("hi"), number(1.0)]),
+            ),
+            DataChunk::new(NumberedProgramLocation::new(20, 0).into(), Rc::new(vec![])),
+            DataChunk::new(
+                NumberedProgramLocation::new(30, 0).into(),
+                Rc::new(vec![string("boop")]),
+            ),
+        ]);
+        for expected in [3, 2, 1, 0, 0] {
+            assert_eq!(iterator.remaining(), expected);
+            iterator.next();
+        }
+    }
+
     #[test]
     fn non_empty_data_iterator_works() {
         let mut iterator = DataIterator::new(vec![
@@ -310,6 +360,9 @@ mod tests {
     fn parsing_one_unquoted_string_works() {
         assert_parse_all_data(" boop", &[string("boop")]);
         assert_parse_all_data(" 123boop", &[string("123boop")]);
+        assert_parse_all_data("inf", &[string("inf")]);
+        assert_parse_all_data("NaN", &[string("NaN")]);
+        assert_parse_all_data("1e999", &[string("1e999")]);
     }
 
     #[test]
@@ -392,4 +445,18 @@ mod tests {
         assert_parse_partial_data(" foo😊:blah", &[string("foo😊")], 8, ":blah");
         assert_parse_partial_data(" \"foo😊\":blah", &[string("foo😊")], 10, ":blah");
     }
+
+    #[test]
+    fn parsing_commas_in_quoted_strings_works() {
+        assert_parse_all_data("\"a, b\"", &[string("a, b")]);
+    }
+
+    #[test]
+    fn parsing_while_ignoring_quotes_works() {
+        let value = "\"a, b\":c";
+        assert_eq!(
+            parse_data_until_colon_ignoring_quotes(value, None),
+            (vec![string("\"a"), string("b\"")], 6)
+        );
+    }
 }
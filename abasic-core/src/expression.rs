@@ -1,10 +1,12 @@
+use std::rc::Rc;
+
 use crate::{
     builtins::Builtin,
     operators::{
         evaluate_exponent, evaluate_logical_and, evaluate_logical_or, AddOrSubtractOp, EqualityOp,
         MultiplyOrDivideOp, UnaryOp,
     },
-    program::Program,
+    program::{Program, ProgramLocation},
     symbol::Symbol,
     value::Value,
     variables::Variables,
@@ -24,6 +26,18 @@ impl<'a> ExpressionEvaluator<'a> {
         self.evaluate_logical_or_expression()
     }
 
+    /// Evaluates the condition of a conditional construct like `IF` or
+    /// `IIF`, so that they all treat values as true or false the same way.
+    pub fn evaluate_condition(&mut self) -> Result<bool, TracedInterpreterError> {
+        let value = self.evaluate_expression()?;
+        if self.interpreter.enable_strict_conditions {
+            if let Value::String(_) = value {
+                return Err(InterpreterError::TypeMismatch.into());
+            }
+        }
+        Ok(value.to_bool())
+    }
+
     pub fn evaluate_array_index(&mut self) -> Result<Vec<usize>, TracedInterpreterError> {
         let mut indices: Vec<usize> = vec![];
         self.program().expect_next_token(Token::LeftParen)?;
@@ -62,6 +76,61 @@ impl<'a> ExpressionEvaluator<'a> {
         Ok(f(arg).into())
     }
 
+    /// Like `evaluate_unary_number_function`, but raises an overflow error
+    /// if the result is too large to represent.
+    fn evaluate_overflowable_unary_number_function<F: Fn(f64) -> f64>(
+        &mut self,
+        f: F,
+    ) -> Result<Value, TracedInterpreterError> {
+        let arg = self.evaluate_unary_number_function_arg()?;
+        let result = f(arg);
+        if !result.is_finite() {
+            return Err(InterpreterError::Overflow.into());
+        }
+        Ok(result.into())
+    }
+
+    /// Evaluates the arguments to a function that takes a string followed by
+    /// `required` numbers and then up to `optional` additional numbers, e.g.
+    /// `MID$(A$, START[, LENGTH])`.
+    fn evaluate_string_and_number_function_args(
+        &mut self,
+        required: usize,
+        optional: usize,
+    ) -> Result<(Rc<String>, Vec<f64>), TracedInterpreterError> {
+        self.program().expect_next_token(Token::LeftParen)?;
+        let string: Rc<String> = self.evaluate_expression()?.try_into()?;
+        let mut numbers = Vec::with_capacity(required + optional);
+        for i in 0..required + optional {
+            if i < required {
+                self.program().expect_next_token(Token::Comma)?;
+            } else if !self.program().accept_next_token(Token::Comma) {
+                break;
+            }
+            numbers.push(self.evaluate_expression()?.try_into()?);
+        }
+        self.program().expect_next_token(Token::RightParen)?;
+        Ok((string, numbers))
+    }
+
+    /// Evaluates `IIF(condition, a, b)`. Unlike `IF`, both `a` and `b` are
+    /// always evaluated, and they must be of the same type.
+    fn evaluate_iif_function(&mut self) -> Result<Value, TracedInterpreterError> {
+        self.program().expect_next_token(Token::LeftParen)?;
+        let condition = self.evaluate_condition()?;
+        self.program().expect_next_token(Token::Comma)?;
+        let if_true = self.evaluate_expression()?;
+        self.program().expect_next_token(Token::Comma)?;
+        let if_false = self.evaluate_expression()?;
+        self.program().expect_next_token(Token::RightParen)?;
+        match (&if_true, &if_false) {
+            (Value::Number(_), Value::Number(_)) | (Value::String(_), Value::String(_)) => {
+                Ok(if condition { if_true } else { if_false })
+            }
+            _ => Err(InterpreterError::TypeMismatch.into()),
+        }
+    }
+
     fn evaluate_user_defined_function_call(
         &mut self,
         function_name: &Symbol,
@@ -94,6 +163,10 @@ impl<'a> ExpressionEvaluator<'a> {
         self.program()
             .pop_function_call_off_stack_and_return_from_it();
 
+        // Make sure that e.g. FNA$ always returns a string, so callers can
+        // rely on the function name to know what type of value they'll get.
+        value.validate_type_matches_variable_name(function_name.as_str())?;
+
         Ok(Some(value))
     }
 
@@ -104,13 +177,94 @@ impl<'a> ExpressionEvaluator<'a> {
         if let Some(builtin) = Builtin::try_from(function_name) {
             match builtin {
                 Builtin::Abs => self.evaluate_unary_number_function(|num| num.abs()),
+                Builtin::Asc => {
+                    let (string, _) = self.evaluate_string_and_number_function_args(0, 0)?;
+                    let Some(char) = string.chars().next() else {
+                        return Err(InterpreterError::IllegalQuantity.into());
+                    };
+                    Ok((char as u32 as f64).into())
+                }
+                Builtin::Atn => self.evaluate_unary_number_function(|num| num.atan()),
+                Builtin::Chr => {
+                    // Unlike Applesoft BASIC, which only deals in bytes, we take a
+                    // Unicode code point here, so e.g. `CHR$(128512)` is an emoji.
+                    // `ASC` is its inverse.
+                    let number = self.evaluate_unary_number_function_arg()?;
+                    if number < 0.0 {
+                        return Err(InterpreterError::IllegalQuantity.into());
+                    }
+                    let Some(char) = char::from_u32(number as u32) else {
+                        return Err(InterpreterError::IllegalQuantity.into());
+                    };
+                    Ok(String::from(char).into())
+                }
+                Builtin::Cos => self.evaluate_unary_number_function(|num| num.cos()),
+                Builtin::Exp => self.evaluate_overflowable_unary_number_function(|num| num.exp()),
                 Builtin::Int => self.evaluate_unary_number_function(|num| num.floor()),
+                Builtin::Left => {
+                    let (string, numbers) = self.evaluate_string_and_number_function_args(1, 0)?;
+                    let length = to_string_length(numbers[0])?;
+                    Ok(string.chars().take(length).collect::<String>().into())
+                }
+                Builtin::Len => {
+                    let (string, _) = self.evaluate_string_and_number_function_args(0, 0)?;
+                    Ok((string.chars().count() as f64).into())
+                }
+                Builtin::Log => {
+                    let number = self.evaluate_unary_number_function_arg()?;
+                    if number <= 0.0 {
+                        return Err(InterpreterError::IllegalQuantity.into());
+                    }
+                    Ok(number.ln().into())
+                }
+                Builtin::Mid => {
+                    let (string, numbers) = self.evaluate_string_and_number_function_args(1, 1)?;
+                    let start = to_string_length(numbers[0])? - 1;
+                    let length = match numbers.get(1) {
+                        Some(&length) => to_string_length(length)?,
+                        None => usize::MAX,
+                    };
+                    Ok(string
+                        .chars()
+                        .skip(start)
+                        .take(length)
+                        .collect::<String>()
+                        .into())
+                }
+                Builtin::Peek => {
+                    let address = self.evaluate_unary_number_function_arg()?;
+                    Ok((self.interpreter.peek(address)? as f64).into())
+                }
+                Builtin::Pos => {
+                    // The argument is ignored, but it still needs to be a number.
+                    self.evaluate_unary_number_function_arg()?;
+                    Ok((self.interpreter.get_column() as f64).into())
+                }
+                Builtin::Right => {
+                    let (string, numbers) = self.evaluate_string_and_number_function_args(1, 0)?;
+                    let length = to_string_length(numbers[0])?;
+                    let total_length = string.chars().count();
+                    Ok(string
+                        .chars()
+                        .skip(total_length.saturating_sub(length))
+                        .collect::<String>()
+                        .into())
+                }
                 Builtin::Rnd => {
                     let number = self.evaluate_unary_number_function_arg()?;
                     Ok(self.interpreter.rng.rnd(number)?.into())
                 }
+                Builtin::Sin => self.evaluate_unary_number_function(|num| num.sin()),
+                Builtin::Sqr => {
+                    let number = self.evaluate_unary_number_function_arg()?;
+                    if number < 0.0 {
+                        return Err(InterpreterError::IllegalQuantity.into());
+                    }
+                    Ok(number.sqrt().into())
+                }
+                Builtin::Tan => self.evaluate_overflowable_unary_number_function(|num| num.tan()),
             }
-            .map(|value| Some(value))
+            .map(Some)
         } else {
             self.evaluate_user_defined_function_call(function_name)
         }
@@ -120,6 +274,8 @@ impl<'a> ExpressionEvaluator<'a> {
         match self.program().next_unwrapped_token()? {
             Token::StringLiteral(string) => Ok(string.into()),
             Token::NumericLiteral(number) => Ok(number.into()),
+            Token::Input | Token::Get => Err(SyntaxError::InputInExpression.into()),
+            Token::Iif => self.evaluate_iif_function(),
             Token::Symbol(symbol) => {
                 let is_array_or_function_call =
                     self.program().peek_next_token() == Some(Token::LeftParen);
@@ -127,9 +283,8 @@ impl<'a> ExpressionEvaluator<'a> {
                     if let Some(value) = self.evaluate_function_call(&symbol)? {
                         Ok(value)
                     } else {
+                        self.interpreter.check_undeclared_array_use(&symbol)?;
                         let index = self.evaluate_array_index()?;
-                        self.interpreter
-                            .maybe_log_warning_about_undeclared_array_use(&symbol);
                         self.interpreter.arrays.get_value_at_index(&symbol, &index)
                     }
                 } else if let Some(value) = self.program().find_variable_value_in_stack(&symbol) {
@@ -143,7 +298,7 @@ impl<'a> ExpressionEvaluator<'a> {
                     Ok(self.interpreter.variables.get(&symbol))
                 }
             }
-            _ => Err(SyntaxError::UnexpectedToken.into()),
+            token => Err(SyntaxError::UnexpectedToken(Some(token)).into()),
         }
     }
 
@@ -157,6 +312,8 @@ impl<'a> ExpressionEvaluator<'a> {
         }
     }
 
+    /// Note that unary operators bind more tightly than exponentiation, so
+    /// e.g. `-2^2` is 4, just like in Applesoft BASIC.
     fn evaluate_unary_operator(&mut self) -> Result<Value, TracedInterpreterError> {
         let maybe_unary_op = self.program().try_next_token(UnaryOp::from_token);
 
@@ -173,8 +330,9 @@ impl<'a> ExpressionEvaluator<'a> {
         let mut value = self.evaluate_unary_operator()?;
 
         while self.program().accept_next_token(Token::Caret) {
+            let op_location = self.program().get_prev_location();
             let power = self.evaluate_unary_operator()?;
-            value = evaluate_exponent(value, power)?;
+            value = at_operator(evaluate_exponent(value, power), op_location)?;
         }
 
         Ok(value)
@@ -187,8 +345,9 @@ impl<'a> ExpressionEvaluator<'a> {
             .program()
             .try_next_token(MultiplyOrDivideOp::from_token)
         {
+            let op_location = self.program().get_prev_location();
             let second_operand = self.evaluate_exponent_expression()?;
-            value = op.evaluate(&value, &second_operand)?;
+            value = at_operator(op.evaluate(&value, &second_operand), op_location)?;
         }
 
         Ok(value)
@@ -198,8 +357,14 @@ impl<'a> ExpressionEvaluator<'a> {
         let mut value = self.evaluate_multiply_or_divide_expression()?;
 
         while let Some(plus_or_minus) = self.program().try_next_token(AddOrSubtractOp::from_token) {
+            let op_location = self.program().get_prev_location();
             let second_operand = self.evaluate_multiply_or_divide_expression()?;
-            value = plus_or_minus.evaluate(&value, &second_operand)?;
+            let result = plus_or_minus.evaluate(
+                &value,
+                &second_operand,
+                &mut self.interpreter.string_manager,
+            );
+            value = at_operator(result, op_location)?;
         }
 
         Ok(value)
@@ -209,8 +374,9 @@ impl<'a> ExpressionEvaluator<'a> {
         let mut value = self.evaluate_plus_or_minus_expression()?;
 
         while let Some(equality_op) = self.program().try_next_token(EqualityOp::from_token) {
+            let op_location = self.program().get_prev_location();
             let second_operand = self.evaluate_plus_or_minus_expression()?;
-            value = equality_op.evaluate(&value, &second_operand)?;
+            value = at_operator(equality_op.evaluate(&value, &second_operand), op_location)?;
         }
 
         Ok(value)
@@ -220,8 +386,9 @@ impl<'a> ExpressionEvaluator<'a> {
         let mut value = self.evaluate_equality_expression()?;
 
         while self.program().accept_next_token(Token::And) {
+            let op_location = self.program().get_prev_location();
             let second_operand = self.evaluate_equality_expression()?;
-            value = evaluate_logical_and(&value, &second_operand)?;
+            value = at_operator(evaluate_logical_and(&value, &second_operand), op_location)?;
         }
 
         Ok(value)
@@ -233,10 +400,30 @@ impl<'a> ExpressionEvaluator<'a> {
         let mut value = self.evaluate_logical_and_expression()?;
 
         while self.program().accept_next_token(Token::Or) {
+            let op_location = self.program().get_prev_location();
             let second_operand = self.evaluate_logical_and_expression()?;
-            value = evaluate_logical_or(&value, &second_operand)?;
+            value = at_operator(evaluate_logical_or(&value, &second_operand), op_location)?;
         }
 
         Ok(value)
     }
 }
+
+/// Converts the given number to a 1-based string position or length, as used
+/// by functions like `LEFT$` and `MID$`.
+fn to_string_length(value: f64) -> Result<usize, InterpreterError> {
+    if !value.is_finite() || value < 1.0 {
+        Err(InterpreterError::IllegalQuantity)
+    } else {
+        Ok(value as usize)
+    }
+}
+
+/// Makes any error raised when evaluating a binary operator point at the
+/// operator itself, rather than at the end of its second operand.
+fn at_operator(
+    result: Result<Value, TracedInterpreterError>,
+    location: ProgramLocation,
+) -> Result<Value, TracedInterpreterError> {
+    result.map_err(|err| err.with_default_location(location))
+}
@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+use crate::{InterpreterError, TracedInterpreterError};
+
+/// The number of colors available in Applesoft's low-resolution
+/// graphics mode (set via `COLOR=`).
+const LOW_RES_COLORS: f64 = 16.0;
+
+/// The number of colors available in Applesoft's high-resolution
+/// graphics mode (set via `HCOLOR=`).
+const HIGH_RES_COLORS: f64 = 8.0;
+
+/// The color that `GR` and `HGR` clear the screen to.
+pub const BLACK: u8 = 0;
+
+/// The Applesoft ROM routine that clears the high-resolution screen to
+/// the current `HCOLOR`, invoked via `CALL 62454`.
+pub const CLEAR_HIGH_RES_TO_HCOLOR_ADDRESS: f64 = 62454.0;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GraphicsMode {
+    LowRes,
+    HighRes,
+}
+
+impl Display for GraphicsMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphicsMode::LowRes => write!(f, "LOW-RES"),
+            GraphicsMode::HighRes => write!(f, "HIGH-RES"),
+        }
+    }
+}
+
+/// Keeps track of the current drawing colors. We don't actually keep track
+/// of any pixels; it's up to clients to render the structured graphics
+/// output that the interpreter emits.
+#[derive(Debug, Default)]
+pub struct Graphics {
+    low_res_color: u8,
+    high_res_color: u8,
+}
+
+impl Graphics {
+    fn parse_color(value: f64, num_colors: f64) -> Result<u8, TracedInterpreterError> {
+        if value < 0.0 || value >= num_colors {
+            Err(InterpreterError::IllegalQuantity.into())
+        } else {
+            Ok(value as u8)
+        }
+    }
+
+    pub fn color(&self, mode: GraphicsMode) -> u8 {
+        match mode {
+            GraphicsMode::LowRes => self.low_res_color,
+            GraphicsMode::HighRes => self.high_res_color,
+        }
+    }
+
+    pub fn reset_low_res_color(&mut self) {
+        self.low_res_color = BLACK;
+    }
+
+    pub fn set_color(
+        &mut self,
+        mode: GraphicsMode,
+        value: f64,
+    ) -> Result<(), TracedInterpreterError> {
+        match mode {
+            GraphicsMode::LowRes => {
+                self.low_res_color = Graphics::parse_color(value, LOW_RES_COLORS)?;
+            }
+            GraphicsMode::HighRes => {
+                self.high_res_color = Graphics::parse_color(value, HIGH_RES_COLORS)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,27 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    hash::{BuildHasher, Hasher, RandomState},
+    ops::RangeInclusive,
+    rc::Rc,
+};
+
 use crate::{
     arrays::Arrays,
-    data::{parse_data_until_colon, DataElement},
+    binary_program::{decode_program, encode_program, BinaryProgramError},
+    data::{parse_data_until_colon, parse_data_until_colon_ignoring_quotes, DataElement},
     expression::ExpressionEvaluator,
-    interpreter_error::TracedInterpreterError,
+    graphics::Graphics,
+    interpreter_error::{InterpreterError, TracedInterpreterError},
     interpreter_output::InterpreterOutput,
+    keyboard::{Keyboard, CLEAR_KEYBOARD_STROBE_ADDRESS, KEYBOARD_ADDRESS},
     line_number_parser::parse_line_number,
-    program::Program,
+    program::{Program, ProgramLine, ProgramLocation, DEFAULT_STACK_LIMIT},
+    program_storage::ProgramStorage,
     random::Rng,
     statement::StatementEvaluator,
     string_manager::StringManager,
     symbol::Symbol,
+    syntax_error::SyntaxError,
     tokenizer::{Token, Tokenizer},
     value::Value,
     variables::Variables,
@@ -24,33 +36,271 @@ pub enum InterpreterState {
     NewInterpreterRequested,
 }
 
-#[derive(Default)]
+/// Describes the input that an interpreter in the `AwaitingInput` state
+/// is waiting for, so clients can present an appropriate way to enter it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InputTarget {
+    /// A line of text for `INPUT`. If `is_string` is false, the line will
+    /// need to be a number, or the user will be asked to re-enter it.
+    Line { is_string: bool },
+    /// A single keystroke for `GET`. If `is_string` is false, the keystroke
+    /// will need to be a digit.
+    Keystroke { is_string: bool },
+}
+
+impl InputTarget {
+    pub(crate) fn for_variable(name: &Symbol) -> Self {
+        InputTarget::Line {
+            is_string: name.as_str().ends_with('$'),
+        }
+    }
+
+    pub(crate) fn keystroke_for_variable(name: &Symbol) -> Self {
+        InputTarget::Keystroke {
+            is_string: name.as_str().ends_with('$'),
+        }
+    }
+
+    /// The token of the statement that requested this kind of input.
+    fn statement_token(&self) -> Token {
+        match self {
+            InputTarget::Line { .. } => Token::Input,
+            InputTarget::Keystroke { .. } => Token::Get,
+        }
+    }
+}
+
+/// Describes how the most recent evaluation ended.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum RunOutcome {
+    /// Evaluation ran to completion, or nothing has been evaluated yet.
+    #[default]
+    Completed,
+    /// The program executed a `STOP` statement or reached a breakpoint, and
+    /// can be resumed via `CONT`.
+    Broke,
+    /// The program is waiting for input to be provided.
+    AwaitingInput,
+    /// Evaluation ended with an error.
+    Errored,
+    /// Evaluation was interrupted by the client, e.g. because the user
+    /// pressed CTRL-C.
+    Interrupted,
+}
+
+/// After an error is trapped via `ONERR GOTO`, its code can be read from
+/// this address, like in Applesoft BASIC.
+const ERROR_CODE_ADDRESS: f64 = 222.0;
+
+/// After an error is trapped via `ONERR GOTO`, the low and high bytes of the
+/// line number it occurred on can be read from these addresses.
+const ERROR_LINE_LOW_BYTE_ADDRESS: f64 = 218.0;
+const ERROR_LINE_HIGH_BYTE_ADDRESS: f64 = 219.0;
+
+/// The default starting line number and increment for `RENUMBER`.
+const DEFAULT_RENUMBER_INCREMENT: u64 = 10;
+
+/// The largest line number Applesoft BASIC permits.
+const DEFAULT_MAX_LINE_NUMBER: u64 = 63999;
+
+/// The largest stack depth that can be configured via `MAXSTACK`.
+const MAX_CONFIGURABLE_STACK_LIMIT: usize = 10000;
+
+/// Applesoft BASIC only permits 239 characters per line, but we'll be
+/// more generous than that.
+const DEFAULT_MAX_LINE_LENGTH: usize = 1024;
+
 pub struct Interpreter {
     input: Option<String>,
+    pending_input_target: Option<InputTarget>,
+    /// How many of the variables in the current `INPUT` statement have
+    /// been filled by previous lines of input.
+    pub(crate) input_items_assigned: usize,
+    queued_input: VecDeque<String>,
     output: Vec<InterpreterOutput>,
+    /// Printed output that's being collected so it can be emitted all at
+    /// once, e.g. for the items of a single `PRINT` statement.
+    print_buffer: Option<String>,
+    column: usize,
+    output_bytes: usize,
     state: InterpreterState,
-    string_manager: StringManager,
+    last_run_outcome: RunOutcome,
+    pub(crate) string_manager: StringManager,
     pub(crate) program: Program,
     pub(crate) rng: Rng,
     pub(crate) variables: Variables,
     pub(crate) arrays: Arrays,
+    pub(crate) graphics: Graphics,
+    keyboard: Keyboard,
+    changed_variables: HashSet<Symbol>,
+    changed_arrays: HashSet<Symbol>,
+    error_code: u8,
+    error_line: u64,
+    line_execution_counts: BTreeMap<u64, u64>,
+    /// Lines that the program will break at before running, as though they
+    /// started with `STOP`.
+    breakpoints: BTreeSet<u64>,
+    /// Whether we're resuming from a break via `CONT`, in which case we
+    /// shouldn't immediately break again at the same breakpoint.
+    skip_next_breakpoint: bool,
     pub enable_warnings: bool,
     pub enable_tracing: bool,
+    pub enable_input_echo: bool,
+    pub enable_strict_arrays: bool,
+    pub enable_profiling: bool,
+    /// Whether `IF` conditions must be numbers, as they are in some BASIC
+    /// dialects. If disabled, non-empty strings are treated as true.
+    pub enable_strict_conditions: bool,
+    /// Whether `><`, `=>`, and `=<` are accepted as aliases for `<>`, `>=`,
+    /// and `<=`, as they are in some BASIC dialects.
+    pub enable_lenient_operators: bool,
+    /// Whether `PRINT AT row, column` is supported, as it is in some BASIC
+    /// dialects. This makes `AT` a reserved word.
+    pub enable_print_at: bool,
+    /// Whether `PRINT` puts a space before non-negative numbers, as many
+    /// BASIC dialects do, so that they line up with negative ones.
+    pub enable_number_sign_spacing: bool,
+    /// Whether the `IIF(condition, a, b)` function is supported, as it is in
+    /// some BASIC dialects. Note that both `a` and `b` are always evaluated,
+    /// regardless of the condition. This makes `IIF` a reserved word.
+    pub enable_iif: bool,
+    /// Whether `WHILE condition ... WEND` loops are supported, as they are
+    /// in some BASIC dialects. This makes `WHILE` and `WEND` reserved words.
+    pub enable_while_loops: bool,
+    /// Whether Applesoft BASIC's graphics statements (`GR`, `HGR`, `COLOR=`,
+    /// `HCOLOR=`, and `CALL`) are supported. This makes them reserved words,
+    /// so e.g. `GRADE` is tokenized as `GR` followed by `ADE`.
+    pub enable_graphics: bool,
+    /// Whether Applesoft BASIC's `GET`, `POP`, `CLEAR`, `POKE`, `ONERR GOTO`,
+    /// and `RESUME` statements are supported. This makes them reserved
+    /// words, so e.g. `TARGET` is tokenized as `TAR` followed by `GET`.
+    /// Note that `CLEAR` is always available as a command.
+    pub enable_applesoft_statements: bool,
+    /// Whether `INPUT` treats double quotes like any other character, so
+    /// that commas always separate values, as in raw Applesoft BASIC. If
+    /// disabled, a quoted value like `"A, B"` is read as a single string.
+    pub enable_raw_input: bool,
+    /// Defining a line with a number larger than this raises a syntax error.
+    /// Defaults to 63999, like Applesoft BASIC.
+    pub max_line_number: u64,
+    /// Entering a line longer than this many bytes raises a syntax error.
+    pub max_line_length: usize,
+    /// If set, the maximum number of bytes that `PRINT` can output each
+    /// time evaluation is started, e.g. via `RUN`. This is useful for
+    /// running untrusted programs that could otherwise print forever.
+    pub max_output_bytes: Option<usize>,
+    /// How deep `GOSUB`, `FOR`, `WHILE`, and function calls can nest before
+    /// a stack overflow is raised. Changes take effect the next time
+    /// evaluation is started. Note that function calls can never nest
+    /// deeper than the default limit, since they recurse in Rust.
+    pub max_stack_depth: usize,
+    /// Where `SAVE` and `LOAD` persist programs. If not set, those commands
+    /// raise an error.
+    pub program_storage: Option<Box<dyn ProgramStorage>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter {
+            input: None,
+            pending_input_target: None,
+            input_items_assigned: 0,
+            queued_input: VecDeque::new(),
+            output: vec![],
+            print_buffer: None,
+            column: 0,
+            output_bytes: 0,
+            state: InterpreterState::default(),
+            last_run_outcome: RunOutcome::default(),
+            string_manager: StringManager::default(),
+            program: Program::default(),
+            rng: Rng::default(),
+            variables: Variables::default(),
+            arrays: Arrays::default(),
+            graphics: Graphics::default(),
+            keyboard: Keyboard::default(),
+            changed_variables: HashSet::new(),
+            changed_arrays: HashSet::new(),
+            error_code: 0,
+            error_line: 0,
+            line_execution_counts: BTreeMap::new(),
+            breakpoints: BTreeSet::new(),
+            skip_next_breakpoint: false,
+            enable_warnings: false,
+            enable_tracing: false,
+            enable_input_echo: false,
+            enable_strict_arrays: false,
+            enable_profiling: false,
+            enable_strict_conditions: false,
+            enable_lenient_operators: false,
+            enable_print_at: false,
+            enable_number_sign_spacing: false,
+            enable_iif: false,
+            enable_while_loops: false,
+            enable_graphics: false,
+            enable_applesoft_statements: false,
+            enable_raw_input: false,
+            max_line_number: DEFAULT_MAX_LINE_NUMBER,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            max_output_bytes: None,
+            max_stack_depth: DEFAULT_STACK_LIMIT,
+            program_storage: None,
+        }
+    }
 }
 
 impl core::fmt::Debug for Interpreter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Interpreter")
             .field("input", &self.input)
+            .field("pending_input_target", &self.pending_input_target)
+            .field("input_items_assigned", &self.input_items_assigned)
+            .field("queued_input", &self.queued_input)
             .field("output", &self.output)
+            .field("print_buffer", &self.print_buffer)
+            .field("column", &self.column)
+            .field("output_bytes", &self.output_bytes)
             .field("state", &self.state)
+            .field("last_run_outcome", &self.last_run_outcome)
             .field("string_manager", &self.string_manager)
             .field("program", &self.program)
             .field("rng", &self.rng)
             .field("variables", &self.variables)
             .field("arrays", &self.arrays)
+            .field("graphics", &self.graphics)
+            .field("keyboard", &self.keyboard)
+            .field("changed_variables", &self.changed_variables)
+            .field("changed_arrays", &self.changed_arrays)
+            .field("error_code", &self.error_code)
+            .field("error_line", &self.error_line)
+            .field("line_execution_counts", &self.line_execution_counts)
+            .field("breakpoints", &self.breakpoints)
+            .field("skip_next_breakpoint", &self.skip_next_breakpoint)
             .field("enable_warnings", &self.enable_warnings)
             .field("enable_tracing", &self.enable_tracing)
+            .field("enable_input_echo", &self.enable_input_echo)
+            .field("enable_strict_arrays", &self.enable_strict_arrays)
+            .field("enable_profiling", &self.enable_profiling)
+            .field("enable_strict_conditions", &self.enable_strict_conditions)
+            .field("enable_lenient_operators", &self.enable_lenient_operators)
+            .field("enable_print_at", &self.enable_print_at)
+            .field(
+                "enable_number_sign_spacing",
+                &self.enable_number_sign_spacing,
+            )
+            .field("enable_iif", &self.enable_iif)
+            .field("enable_while_loops", &self.enable_while_loops)
+            .field("enable_graphics", &self.enable_graphics)
+            .field(
+                "enable_applesoft_statements",
+                &self.enable_applesoft_statements,
+            )
+            .field("enable_raw_input", &self.enable_raw_input)
+            .field("max_line_number", &self.max_line_number)
+            .field("max_line_length", &self.max_line_length)
+            .field("max_output_bytes", &self.max_output_bytes)
+            .field("max_stack_depth", &self.max_stack_depth)
+            .field("program_storage", &self.program_storage.is_some())
             .finish()
     }
 }
@@ -69,13 +319,75 @@ impl Interpreter {
     }
 
     pub(crate) fn output(&mut self, output: InterpreterOutput) {
+        match &output {
+            InterpreterOutput::Print(string) => self.advance_column(string),
+            // Tracing is displayed inline, but it's not really part of the
+            // program's output, so we won't count it.
+            InterpreterOutput::Trace(_) => {}
+            // This isn't displayed at all.
+            InterpreterOutput::Ended => {}
+            InterpreterOutput::MoveCursor { column, .. } => {
+                self.column = *column;
+            }
+            // Everything else is displayed on its own line.
+            _ => {
+                self.column = 0;
+            }
+        }
         self.output.push(output);
     }
 
+    fn advance_column(&mut self, string: &str) {
+        for char in string.chars() {
+            self.column = match char {
+                '\n' | '\r' => 0,
+                '\x08' => self.column.saturating_sub(1),
+                // This is how terminals and browsers display tabs by default.
+                '\t' => (self.column / 8 + 1) * 8,
+                _ => self.column + 1,
+            };
+        }
+    }
+
+    /// Starts collecting printed output instead of emitting it right away.
+    /// The current column is still kept up to date, so anything which
+    /// depends on it (e.g. `POS`) sees the effects of earlier output.
+    pub(crate) fn start_print_buffer(&mut self) {
+        self.print_buffer = Some(String::new());
+    }
+
+    /// Emits any output collected since `start_print_buffer` was called as
+    /// a single `InterpreterOutput::Print`.
+    pub(crate) fn flush_print_buffer(&mut self) {
+        if let Some(string) = self.print_buffer.take() {
+            if !string.is_empty() {
+                // The column was already advanced when this was buffered.
+                self.output.push(InterpreterOutput::Print(string));
+            }
+        }
+    }
+
+    /// Returns the column (starting at zero) that the next character of
+    /// printed output will appear at, assuming it's being displayed on a
+    /// screen that's infinitely wide.
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    /// Takes the input provided for a `GET` statement, exactly as it was
+    /// provided.
+    pub(crate) fn take_raw_input(&mut self) -> Option<String> {
+        self.input.take()
+    }
+
     pub(crate) fn take_input(&mut self) -> Option<(Vec<DataElement>, bool)> {
         if let Some(input) = self.input.take() {
-            let (elements, bytes_read) =
-                parse_data_until_colon(input.as_str(), Some(&mut self.string_manager));
+            let parse = if self.enable_raw_input {
+                parse_data_until_colon_ignoring_quotes
+            } else {
+                parse_data_until_colon
+            };
+            let (elements, bytes_read) = parse(input.as_str(), Some(&mut self.string_manager));
             let has_leftover_input = bytes_read < input.len();
             Some((elements, has_leftover_input))
         } else {
@@ -85,7 +397,7 @@ impl Interpreter {
 
     pub(crate) fn warn<T: AsRef<str>>(&mut self, message: T) {
         if self.enable_warnings {
-            self.output.push(InterpreterOutput::Warning(
+            self.output(InterpreterOutput::Warning(
                 message.as_ref().to_string(),
                 self.program.get_line_number(),
             ));
@@ -96,48 +408,198 @@ impl Interpreter {
         ExpressionEvaluator::new(self).evaluate_expression()
     }
 
-    pub(crate) fn maybe_log_warning_about_undeclared_array_use(&mut self, array_name: &Symbol) {
-        if self.enable_warnings && !self.arrays.has(array_name) {
+    /// Applesoft BASIC automatically creates arrays that are used without
+    /// being declared via DIM, which is convenient but also a notorious
+    /// source of bugs. By default we'll just warn about it, but in strict mode
+    /// we'll raise an error.
+    pub(crate) fn check_undeclared_array_use(
+        &mut self,
+        array_name: &Symbol,
+    ) -> Result<(), TracedInterpreterError> {
+        if !self.arrays.has(array_name) {
+            if self.enable_strict_arrays {
+                return Err(InterpreterError::UndimensionedArray.into());
+            }
             self.warn(format!("Use of undeclared array '{}'.", array_name));
         }
+        Ok(())
+    }
+
+    /// If profiling is enabled and we're at the beginning of a numbered
+    /// line, records that the line is being executed.
+    pub(crate) fn maybe_record_line_execution(&mut self) {
+        if !self.enable_profiling || self.input.is_some() {
+            // If we have input, we're resuming an INPUT statement that
+            // was rewound, so this line's execution was already recorded.
+            return;
+        }
+        let location = self.program.get_location();
+        if location.token_index != 0 {
+            return;
+        }
+        if let Some(line_number) = self.program.get_line_number() {
+            *self.line_execution_counts.entry(line_number).or_default() += 1;
+        }
     }
 
-    pub(crate) fn rewind_program_and_await_input(&mut self) {
-        // We need to rewind to before the INPUT token, so that when we resume
-        // execution after input has been retrieved, we will get back to this
-        // point in the code. This is a hack, but I want to be able to run this
-        // in async contexts without having to explicitly make every single part
-        // of this interpreter use async/await.
-        self.program.rewind_before_token(Token::Input);
+    /// Implements `CLEAR`, which, like in Applesoft BASIC, forgets all
+    /// variables, arrays, and functions, along with any GOSUB or FOR stacks
+    /// and the DATA cursor, but leaves the program itself alone.
+    pub(crate) fn clear(&mut self) {
+        self.variables = Variables::default();
+        self.arrays = Arrays::default();
+        self.program.clear_runtime_state();
+    }
+
+    /// Makes the program break when it reaches the given line, before
+    /// running any of it. Like `STOP`, it can then be resumed via `CONT`.
+    pub fn set_breakpoint(&mut self, line: u64) {
+        self.breakpoints.insert(line);
+    }
+
+    /// Removes a breakpoint previously set via `set_breakpoint`.
+    pub fn clear_breakpoint(&mut self, line: u64) {
+        self.breakpoints.remove(&line);
+    }
+
+    /// Returns whether we're at the beginning of a line with a breakpoint.
+    /// Resuming a rewound `INPUT` statement doesn't count.
+    fn is_at_breakpoint(&self) -> bool {
+        if self.input.is_some() || self.program.get_location().token_index != 0 {
+            return false;
+        }
+        self.program
+            .get_line_number()
+            .is_some_and(|line_number| self.breakpoints.contains(&line_number))
+    }
+
+    /// Returns the numbers of all the program's lines, in ascending order.
+    pub fn line_numbers(&self) -> Vec<u64> {
+        self.program.line_numbers()
+    }
+
+    /// Returns the tokens that make up the body of the user-defined function
+    /// with the given name (e.g. `FNA`), if it has been defined.
+    pub fn get_function_body(&self, name: &str) -> Option<Vec<Token>> {
+        let name: Symbol = Rc::new(name.to_ascii_uppercase()).into();
+        self.program
+            .get_function_body(&name)
+            .map(|body| body.to_vec())
+    }
+
+    /// Returns how many `DATA` elements are left for `READ` to consume,
+    /// which can help figure out the cause of an out of data error.
+    pub fn remaining_data_elements(&self) -> usize {
+        self.program.remaining_data_elements()
+    }
+
+    /// Returns the number of times each numbered line has been executed
+    /// since the program was last run, sorted by line number. This is only
+    /// tracked if profiling is enabled.
+    pub fn get_line_execution_counts(&self) -> Vec<(u64, u64)> {
+        self.line_execution_counts
+            .iter()
+            .map(|(&line, &count)| (line, count))
+            .collect()
+    }
+
+    pub(crate) fn rewind_program_and_await_input(&mut self, target: InputTarget) {
+        // We need to rewind to before the INPUT (or GET) token, so that when we
+        // resume execution after input has been retrieved, we will get back to
+        // this point in the code. This is a hack, but I want to be able to run
+        // this in async contexts without having to explicitly make every single
+        // part of this interpreter use async/await.
+        self.program.rewind_before_token(target.statement_token());
         self.state = InterpreterState::AwaitingInput;
+        self.pending_input_target = Some(target);
+    }
+
+    /// Returns what kind of input the interpreter is waiting for, if it's
+    /// in the `AwaitingInput` state.
+    pub fn pending_input_target(&self) -> Option<InputTarget> {
+        if self.state == InterpreterState::AwaitingInput {
+            self.pending_input_target
+        } else {
+            None
+        }
     }
 
+    /// Interrupts the program, e.g. because the user pressed CTRL-C. The
+    /// program can be resumed via `CONT`.
     pub fn break_at_current_location(&mut self) {
+        self.break_with_outcome(RunOutcome::Interrupted);
+    }
+
+    /// Breaks because the program executed a `STOP` statement or reached a
+    /// breakpoint.
+    pub(crate) fn stop_at_current_location(&mut self) {
+        self.break_with_outcome(RunOutcome::Broke);
+    }
+
+    fn break_with_outcome(&mut self, outcome: RunOutcome) {
         self.state = InterpreterState::Idle;
-        self.output
-            .push(InterpreterOutput::Break(self.program.get_line_number()));
+        self.last_run_outcome = outcome;
+        self.output(InterpreterOutput::Break(self.program.get_line_number()));
         self.program.break_at_current_location();
     }
 
     fn run_next_statement(&mut self) -> Result<(), TracedInterpreterError> {
         self.state = InterpreterState::Running;
+        self.changed_variables.clear();
+        self.changed_arrays.clear();
+        let skip_breakpoint = std::mem::take(&mut self.skip_next_breakpoint);
         if self.program.has_next_token() {
+            if !skip_breakpoint && self.is_at_breakpoint() {
+                self.stop_at_current_location();
+                return Ok(());
+            }
+            self.program.mark_statement_start();
             StatementEvaluator::new(self).evaluate_statement()?;
+            self.provide_queued_input();
         }
-        if !self.program.has_next_token() {
-            if !self.program.next_line() {
-                self.program.set_and_goto_immediate_line(vec![]);
-                self.return_to_idle_state();
-            }
+        if !self.program.has_next_token() && !self.program.next_line() {
+            self.program.set_and_goto_immediate_line(vec![]);
+            self.return_to_idle_state();
         }
 
         Ok(())
     }
 
+    pub(crate) fn log_variable_change(&mut self, symbol: Symbol) {
+        self.changed_variables.insert(symbol);
+    }
+
+    pub(crate) fn log_array_change(&mut self, symbol: Symbol) {
+        self.changed_arrays.insert(symbol);
+    }
+
+    /// Returns the names of the variables that were assigned to by the most
+    /// recently evaluated statement, in alphabetical order. This is intended
+    /// for debuggers that want to highlight what each step changed.
+    pub fn changed_variables(&self) -> Vec<String> {
+        sorted_symbol_names(&self.changed_variables)
+    }
+
+    /// Like `changed_variables()`, but for arrays that were created or had
+    /// any of their elements assigned to.
+    pub fn changed_arrays(&self) -> Vec<String> {
+        sorted_symbol_names(&self.changed_arrays)
+    }
+
     pub fn get_state(&self) -> InterpreterState {
         self.state
     }
 
+    /// Returns how the most recent evaluation ended, or `None` if the
+    /// interpreter is still in the `Running` state.
+    pub fn last_run_outcome(&self) -> Option<RunOutcome> {
+        match self.state {
+            InterpreterState::Running => None,
+            InterpreterState::AwaitingInput => Some(RunOutcome::AwaitingInput),
+            _ => Some(self.last_run_outcome),
+        }
+    }
+
     fn maybe_process_command(&mut self, line: &str) -> Result<bool, TracedInterpreterError> {
         let Some(first_word) = line.split_ascii_whitespace().next() else {
             return Ok(false);
@@ -146,26 +608,43 @@ impl Interpreter {
         // In Applesoft BASIC, commands like LIST and RUN are actually full-fledged
         // BASIC tokens and statements that can be executed through numbered lines.
         // That feels like overkill so for now we're just doing this.
+        let args = line.trim_start()[first_word.len()..].trim();
         match first_word.to_ascii_uppercase().as_str() {
             "RUN" => {
                 self.variables = Variables::default();
                 self.arrays = Arrays::default();
+                self.queued_input.clear();
+                self.line_execution_counts.clear();
                 self.program.run_from_first_numbered_line();
                 self.run_next_statement()?;
             }
+            // This is also a statement if `enable_applesoft_statements` is on,
+            // but we don't want typing it to depend on that.
+            "CLEAR" => self.clear(),
+            "DELETE" => {
+                let Some(range) = parse_line_number_range(args) else {
+                    return Err(SyntaxError::UnexpectedToken(None).into());
+                };
+                self.breakpoints.retain(|line| !range.contains(line));
+                self.program.delete_numbered_lines(range);
+                self.string_manager.gc();
+            }
+            "RENUMBER" => self.renumber(args)?,
+            "SAVE" => self.save(args)?,
+            "LOAD" => self.load(args)?,
             "LIST" => {
-                self.output.extend(
-                    self.program
-                        .list()
-                        .into_iter()
-                        .map(|line| InterpreterOutput::Print(line)),
-                );
+                for line in self.program.list() {
+                    self.print(line);
+                }
             }
             "NEW" => {
+                self.breakpoints.clear();
+                self.queued_input.clear();
                 self.state = InterpreterState::NewInterpreterRequested;
             }
             "CONT" => {
                 self.program.continue_from_breakpoint()?;
+                self.skip_next_breakpoint = true;
                 self.run_next_statement()?;
             }
             "TRACE" => {
@@ -174,6 +653,19 @@ impl Interpreter {
             "NOTRACE" => {
                 self.enable_tracing = false;
             }
+            // This is deliberately only a command rather than a statement: it
+            // configures the interpreter itself (like TRACE), and the new limit
+            // is only applied when the next line is evaluated (e.g. by `RUN`), so
+            // changing it from inside a running program wouldn't do anything.
+            "MAXSTACK" => {
+                let Ok(depth) = args.parse::<usize>() else {
+                    return Err(SyntaxError::UnexpectedToken(None).into());
+                };
+                if !(1..=MAX_CONFIGURABLE_STACK_LIMIT).contains(&depth) {
+                    return Err(InterpreterError::IllegalQuantity.into());
+                }
+                self.max_stack_depth = depth;
+            }
             "INTERNALS" => self.print(format!("{:#?}\n", self)),
             "STATS" => self.print(format!(
                 "Total string data: {} bytes\n",
@@ -183,50 +675,277 @@ impl Interpreter {
                 return Ok(false);
             }
         };
-        return Ok(true);
+        Ok(true)
     }
 
-    fn postprocess_result<T>(
+    /// Implements `RENUMBER [start][,increment]`. Both arguments default
+    /// to 10.
+    fn renumber(&mut self, args: &str) -> Result<(), TracedInterpreterError> {
+        let mut parts = args.split(',').map(|arg| match arg.trim() {
+            "" => Some(DEFAULT_RENUMBER_INCREMENT),
+            arg => arg.parse::<u64>().ok(),
+        });
+        let start = parts.next().flatten();
+        let increment = parts.next().unwrap_or(Some(DEFAULT_RENUMBER_INCREMENT));
+        let (Some(start), Some(increment), None) = (start, increment, parts.next()) else {
+            return Err(SyntaxError::UnexpectedToken(None).into());
+        };
+        if increment == 0 {
+            return Err(InterpreterError::IllegalQuantity.into());
+        }
+        let line_count = self.program.line_numbers().len() as u64;
+        let last_line_number = line_count
+            .saturating_sub(1)
+            .checked_mul(increment)
+            .and_then(|offset| offset.checked_add(start));
+        if last_line_number.is_none_or(|last| last > self.max_line_number) {
+            return Err(SyntaxError::LineNumberTooLarge.into());
+        }
+        // Breakpoints follow their lines to their new numbers. Any that
+        // aren't on an existing line are dropped.
+        let old_line_numbers = self.program.line_numbers();
+        self.breakpoints = old_line_numbers
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| self.breakpoints.contains(line))
+            .map(|(i, _)| start + i as u64 * increment)
+            .collect();
+        for (line_number, target) in self.program.renumber_lines(start, increment) {
+            self.output(InterpreterOutput::Warning(
+                format!("Reference to nonexistent line {target}."),
+                Some(line_number),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Implements `SAVE "name"`.
+    fn save(&mut self, args: &str) -> Result<(), TracedInterpreterError> {
+        let name = parse_program_name(args)?;
+        let Some(storage) = &mut self.program_storage else {
+            return Err(InterpreterError::Unimplemented.into());
+        };
+        storage
+            .save(name, &self.program.list())
+            .map_err(|message| InterpreterError::IoError(message).into())
+    }
+
+    /// Implements `LOAD "name"`, which replaces the current program (and
+    /// forgets all variables) with a previously saved one.
+    fn load(&mut self, args: &str) -> Result<(), TracedInterpreterError> {
+        let name = parse_program_name(args)?;
+        let Some(storage) = &self.program_storage else {
+            return Err(InterpreterError::Unimplemented.into());
+        };
+        let Some(listing) = storage.load(name) else {
+            return Err(InterpreterError::FileNotFound.into());
+        };
+        // Load into a fresh program, so that if the listing turns out to be
+        // invalid, the current program is left untouched.
+        let new_program = self.new_program();
+        let old_program = std::mem::replace(&mut self.program, new_program);
+        if let Err(err) = self.load_program(&listing.join("\n")) {
+            self.program = old_program;
+            self.string_manager.gc();
+            return Err(err);
+        }
+        drop(old_program);
+        self.breakpoints.clear();
+        self.queued_input.clear();
+        self.variables = Variables::default();
+        self.arrays = Arrays::default();
+        self.string_manager.gc();
+        Ok(())
+    }
+
+    /// Returns whether the given token can be produced by tokenizing code
+    /// with our current dialect settings.
+    fn is_token_enabled(&self, token: &Token) -> bool {
+        match token {
+            Token::At => self.enable_print_at,
+            Token::Iif => self.enable_iif,
+            Token::While | Token::Wend => self.enable_while_loops,
+            Token::Gr | Token::Hgr | Token::Color | Token::Hcolor | Token::Call => {
+                self.enable_graphics
+            }
+            Token::Get | Token::Pop | Token::Clear | Token::Poke | Token::Onerr | Token::Resume => {
+                self.enable_applesoft_statements
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns an empty program configured with our settings.
+    fn new_program(&self) -> Program {
+        let mut program = Program::default();
+        program.set_stack_limit(self.max_stack_depth);
+        program
+    }
+
+    fn postprocess_result(
         &mut self,
-        result: Result<T, TracedInterpreterError>,
-    ) -> Result<T, TracedInterpreterError> {
+        result: Result<(), TracedInterpreterError>,
+    ) -> Result<(), TracedInterpreterError> {
         if let Err(mut err) = result {
             self.program.populate_error_location(&mut err);
+            if self.try_to_trap_error(&err) {
+                return Ok(());
+            }
+            // Errors are always displayed on their own line.
+            self.column = 0;
+            // Input meant for the program that errored shouldn't be fed to
+            // whatever runs next.
+            self.queued_input.clear();
             self.return_to_idle_state();
+            self.last_run_outcome = RunOutcome::Errored;
             Err(err)
         } else {
             result
         }
     }
 
+    /// Sends the program to its `ONERR GOTO` handler, if it has one and the
+    /// error can be trapped. Returns whether the error was trapped.
+    fn try_to_trap_error(&mut self, err: &TracedInterpreterError) -> bool {
+        let Some(error_code) = err.error.applesoft_error_code() else {
+            return false;
+        };
+        if !self.program.trap_error() {
+            return false;
+        }
+        self.error_code = error_code;
+        self.error_line = err.line_number().unwrap_or_default();
+        self.state = InterpreterState::Running;
+        true
+    }
+
     fn return_to_idle_state(&mut self) {
         self.string_manager.gc();
         self.state = InterpreterState::Idle;
     }
 
+    /// Prints output on behalf of the program, enforcing `max_output_bytes`.
+    pub(crate) fn print_program_output(
+        &mut self,
+        string: String,
+    ) -> Result<(), TracedInterpreterError> {
+        self.output_bytes += string.len();
+        if let Some(max_output_bytes) = self.max_output_bytes {
+            if self.output_bytes > max_output_bytes {
+                return Err(InterpreterError::OutputLimitExceeded.into());
+            }
+        }
+        self.print(string);
+        Ok(())
+    }
+
     pub(crate) fn print(&mut self, string: String) {
-        self.output.push(InterpreterOutput::Print(string));
+        if let Some(buffer) = &mut self.print_buffer {
+            buffer.push_str(&string);
+            self.advance_column(&string);
+        } else {
+            self.output(InterpreterOutput::Print(string));
+        }
     }
 
+    /// Provides input requested by the program.
+    ///
+    /// If input echoing is enabled, the input will also be printed, followed
+    /// by a newline. This is useful for keeping transcripts of interactive
+    /// sessions. Backspace and delete characters will erase the character
+    /// preceding them in the echoed text, the way a terminal would display
+    /// them, but note that the input provided to the program is unchanged.
     pub fn provide_input(&mut self, input: String) {
         assert_eq!(self.state, InterpreterState::AwaitingInput);
+        let is_keystroke = matches!(
+            self.pending_input_target,
+            Some(InputTarget::Keystroke { .. })
+        );
+        // Like Applesoft BASIC, keystrokes for `GET` aren't echoed.
+        if self.enable_input_echo && !is_keystroke {
+            let mut echo = String::with_capacity(input.len() + 1);
+            for char in input.chars() {
+                match char {
+                    '\x08' | '\x7f' => {
+                        echo.pop();
+                    }
+                    _ => echo.push(char),
+                }
+            }
+            echo.push('\n');
+            self.print(echo);
+        }
         self.input = Some(input);
+        self.pending_input_target = None;
         self.state = InterpreterState::Running;
     }
 
+    /// Queues up input to be provided to the program, one line each time it
+    /// would otherwise start awaiting input. If it's already awaiting input,
+    /// the first line is provided immediately.
+    ///
+    /// This is useful for replaying recorded sessions without having to
+    /// provide each line of input individually. Any input that hasn't been
+    /// consumed is discarded if evaluation errors or is stopped, or if the
+    /// program is run, replaced via `NEW`, or loaded.
+    pub fn queue_input<I: IntoIterator<Item = String>>(&mut self, lines: I) {
+        self.queued_input.extend(lines);
+        self.provide_queued_input();
+    }
+
+    fn provide_queued_input(&mut self) {
+        if self.state == InterpreterState::AwaitingInput {
+            if let Some(input) = self.queued_input.pop_front() {
+                self.provide_input(input);
+            }
+        }
+    }
+
     pub fn continue_evaluating(&mut self) -> Result<(), TracedInterpreterError> {
         assert_eq!(self.state, InterpreterState::Running);
         let result = self.run_next_statement();
         self.postprocess_result(result)
     }
 
+    /// Like `continue_evaluating`, but keeps running statements until
+    /// at least `token_budget` tokens have been evaluated, or until the
+    /// interpreter is no longer running.
+    ///
+    /// This allows callers that need to cooperatively yield (e.g. in a
+    /// browser) to bound the amount of work done per call more precisely
+    /// than counting statements, since a single statement can be much more
+    /// expensive than another.
+    ///
+    /// Note that evaluation is only ever suspended at statement boundaries,
+    /// since expressions are evaluated recursively and can't be paused
+    /// partway through. This means that at least one statement is always
+    /// run, and the budget can be exceeded by up to one statement's worth
+    /// of tokens (plus any tokens evaluated in the bodies of functions
+    /// it calls).
+    pub fn continue_evaluating_with_token_budget(
+        &mut self,
+        token_budget: usize,
+    ) -> Result<(), TracedInterpreterError> {
+        assert_eq!(self.state, InterpreterState::Running);
+        let start = self.program.tokens_evaluated();
+        loop {
+            let result = self.run_next_statement();
+            self.postprocess_result(result)?;
+            let tokens_evaluated = self.program.tokens_evaluated().wrapping_sub(start);
+            if self.state != InterpreterState::Running || tokens_evaluated >= token_budget {
+                return Ok(());
+            }
+        }
+    }
+
     /// Start evaluating the given line of code.
     ///
     /// Note that this is expected to be a *line*, i.e. it shouldn't contain
     /// any newlines (if it does, a syntax error will be raised).
     ///
     /// This only *starts* evaluation. In order to keep running it to completion,
-    /// the caller must also call `continue_evaluating` for as long as the
+    /// the caller must also call `continue_evaluating` (or
+    /// `continue_evaluating_with_token_budget`) for as long as the
     /// interpreter's state is `InterpreterState::Running`.
     pub fn start_evaluating<T: AsRef<str>>(
         &mut self,
@@ -240,16 +959,22 @@ impl Interpreter {
     /// were evaluating at the time of stopping, if any.
     pub fn stop_evaluating(&mut self) -> Option<u64> {
         let line_number = self.program.get_line_number();
+        self.queued_input.clear();
         self.program.set_and_goto_immediate_line(vec![]);
         self.run_next_statement().unwrap();
+        self.last_run_outcome = RunOutcome::Interrupted;
         line_number
     }
 
     fn evaluate_impl<T: AsRef<str>>(&mut self, line: T) -> Result<(), TracedInterpreterError> {
         assert_eq!(self.state, InterpreterState::Idle);
         self.program.set_and_goto_immediate_line(vec![]);
+        self.last_run_outcome = RunOutcome::Completed;
+        self.check_line_length(line.as_ref())?;
+        self.output_bytes = 0;
+        self.program.set_stack_limit(self.max_stack_depth);
 
-        if self.maybe_process_command(line.as_ref().to_uppercase().as_str())? {
+        if self.maybe_process_command(line.as_ref())? {
             return Ok(());
         }
 
@@ -262,9 +987,16 @@ impl Interpreter {
 
         let tokens = Tokenizer::new(line, &mut self.string_manager)
             .skip_bytes(skip_bytes)
+            .lenient_operators(self.enable_lenient_operators)
+            .at_keyword(self.enable_print_at)
+            .iif_keyword(self.enable_iif)
+            .while_keywords(self.enable_while_loops)
+            .graphics_keywords(self.enable_graphics)
+            .applesoft_keywords(self.enable_applesoft_statements)
             .remaining_tokens()?;
 
         if let Some(line_number) = maybe_line_number {
+            self.check_line_number(line_number)?;
             let had_existing_line = self.program.has_line_number(line_number);
             self.program.set_numbered_line(line_number, tokens);
             if had_existing_line {
@@ -279,7 +1011,212 @@ impl Interpreter {
         Ok(())
     }
 
+    fn check_line_length(&self, line: &str) -> Result<(), TracedInterpreterError> {
+        if line.len() > self.max_line_length {
+            return Err(SyntaxError::LineTooLong.into());
+        }
+        Ok(())
+    }
+
+    fn check_line_number(&self, line_number: u64) -> Result<(), TracedInterpreterError> {
+        if line_number > self.max_line_number {
+            return Err(SyntaxError::LineNumberTooLarge.into());
+        }
+        Ok(())
+    }
+
+    /// Loads the given multi-line program source into the interpreter, as
+    /// though each of its lines had been entered individually.
+    ///
+    /// Both Unix (LF) and Windows (CRLF) line endings are supported. Blank
+    /// lines are skipped. Since lines without a line number can't be part
+    /// of a program, they raise an error rather than being executed
+    /// immediately.
+    ///
+    /// If a line fails to load, its error will be returned with its
+    /// `source_line` set to the offending line; any lines before it will
+    /// have already been loaded.
+    ///
+    /// The interpreter must be idle.
+    pub fn load_program(&mut self, source: &str) -> Result<(), TracedInterpreterError> {
+        if self.state != InterpreterState::Idle {
+            return Err(InterpreterError::Busy.into());
+        }
+        let mut result = Ok(());
+        for line in source.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            result = self
+                .load_program_line(line)
+                .map_err(|err| err.with_source_line(line));
+            if result.is_err() {
+                break;
+            }
+        }
+        self.string_manager.gc();
+        result
+    }
+
+    fn load_program_line(&mut self, line: &str) -> Result<(), TracedInterpreterError> {
+        self.check_line_length(line)?;
+        let Some((line_number, end_index)) = parse_line_number(line) else {
+            return Err(SyntaxError::MissingLineNumber.into());
+        };
+        self.check_line_number(line_number)?;
+        let tokens = Tokenizer::new(line, &mut self.string_manager)
+            .skip_bytes(end_index)
+            .lenient_operators(self.enable_lenient_operators)
+            .at_keyword(self.enable_print_at)
+            .iif_keyword(self.enable_iif)
+            .while_keywords(self.enable_while_loops)
+            .graphics_keywords(self.enable_graphics)
+            .applesoft_keywords(self.enable_applesoft_statements)
+            .remaining_tokens()
+            .map_err(|err| {
+                TracedInterpreterError::with_location(
+                    SyntaxError::Tokenization(err).into(),
+                    ProgramLocation {
+                        line: ProgramLine::Line(line_number),
+                        token_index: 0,
+                    },
+                )
+            })?;
+        self.program.set_numbered_line(line_number, tokens);
+        Ok(())
+    }
+
+    /// Returns the current program in a compact tokenized binary format,
+    /// which can be loaded more quickly than source code.
+    pub fn save_binary_program(&self) -> Vec<u8> {
+        encode_program(&self.program.list_tokens())
+    }
+
+    /// Replaces the current program with one previously returned by
+    /// `save_binary_program`. If the data is invalid, or isn't something
+    /// `load_program` would accept with our current settings (e.g. it has
+    /// line numbers that are too large, or uses dialect features that aren't
+    /// enabled), the current program is left untouched.
+    pub fn load_binary_program(&mut self, bytes: &[u8]) -> Result<(), BinaryProgramError> {
+        assert_eq!(self.state, InterpreterState::Idle);
+        let lines = decode_program(bytes, &mut self.string_manager)?;
+        for (line_number, tokens) in &lines {
+            if *line_number > self.max_line_number {
+                return Err(BinaryProgramError::LineNumberTooLarge(*line_number));
+            }
+            if let Some(token) = tokens.iter().find(|token| !self.is_token_enabled(token)) {
+                return Err(BinaryProgramError::DisabledToken(token.clone()));
+            }
+        }
+        self.program = self.new_program();
+        for (line_number, tokens) in lines {
+            self.program.set_numbered_line(line_number, tokens);
+        }
+        self.string_manager.gc();
+        Ok(())
+    }
+
+    /// Seeds the random number generator used by `RND`. Interpreters
+    /// seeded with the same value will produce the same random numbers.
+    ///
+    /// Each interpreter owns its generator, so seeding (or calling `RND`
+    /// in) one interpreter never affects the sequence of another.
     pub fn randomize(&mut self, seed: u64) {
         self.rng = Rng::new(seed);
     }
+
+    /// Reseeds the random number generator used by `RND`, without affecting
+    /// the program or its variables. If no seed is given, an arbitrary one
+    /// is chosen.
+    pub fn reset_rng(&mut self, seed: Option<u64>) {
+        let seed = seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+        self.randomize(seed);
+    }
+
+    /// Queues a key press (as an ASCII code) for programs that poll the
+    /// keyboard via `PEEK`.
+    pub fn push_key(&mut self, key: u8) {
+        self.keyboard.push_key(key);
+    }
+
+    /// Reads from memory. Only a few memory-mapped addresses are supported.
+    pub(crate) fn peek(&mut self, address: f64) -> Result<u8, TracedInterpreterError> {
+        let address = normalize_address(address)?;
+        if address == KEYBOARD_ADDRESS {
+            Ok(self.keyboard.peek())
+        } else if address == ERROR_CODE_ADDRESS {
+            Ok(self.error_code)
+        } else if address == ERROR_LINE_LOW_BYTE_ADDRESS {
+            Ok((self.error_line & 0xff) as u8)
+        } else if address == ERROR_LINE_HIGH_BYTE_ADDRESS {
+            Ok((self.error_line >> 8 & 0xff) as u8)
+        } else {
+            Err(InterpreterError::Unimplemented.into())
+        }
+    }
+
+    /// Writes to memory. Only a few memory-mapped addresses are supported.
+    pub(crate) fn poke(&mut self, address: f64, value: f64) -> Result<(), TracedInterpreterError> {
+        if !(0.0..256.0).contains(&value) {
+            return Err(InterpreterError::IllegalQuantity.into());
+        }
+        if normalize_address(address)? == CLEAR_KEYBOARD_STROBE_ADDRESS {
+            self.keyboard.clear_strobe();
+            Ok(())
+        } else {
+            Err(InterpreterError::Unimplemented.into())
+        }
+    }
+}
+
+/// Converts a memory address to an integer between 0 and 65535. Like in
+/// Applesoft BASIC, negative addresses wrap around, so e.g. `PEEK(-16384)`
+/// is the same as `PEEK(49152)`.
+pub(crate) fn normalize_address(address: f64) -> Result<f64, TracedInterpreterError> {
+    let address = address.trunc();
+    if !(-65535.0..=65535.0).contains(&address) {
+        return Err(InterpreterError::IllegalQuantity.into());
+    }
+    Ok(if address < 0.0 {
+        address + 65536.0
+    } else {
+        address
+    })
+}
+
+fn sorted_symbol_names(symbols: &HashSet<Symbol>) -> Vec<String> {
+    let mut names = symbols
+        .iter()
+        .map(|symbol| symbol.to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Parses a line number range like `100,200`, or a single line number.
+fn parse_line_number_range(args: &str) -> Option<RangeInclusive<u64>> {
+    let mut line_numbers = args.split(',').map(|arg| arg.trim().parse::<u64>().ok());
+    let start = line_numbers.next()??;
+    let end = match line_numbers.next() {
+        Some(end) => end?,
+        None => start,
+    };
+    if line_numbers.next().is_some() {
+        return None;
+    }
+    Some(start..=end)
+}
+
+/// Parses the name given to `SAVE` or `LOAD`. Like Apple DOS, the name
+/// doesn't need to be quoted, but it can be, in which case the closing
+/// quote is optional.
+fn parse_program_name(args: &str) -> Result<&str, TracedInterpreterError> {
+    let name = match args.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"').unwrap_or(quoted),
+        None => args,
+    };
+    if name.is_empty() {
+        return Err(SyntaxError::UnexpectedToken(None).into());
+    }
+    Ok(name)
 }
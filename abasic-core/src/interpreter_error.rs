@@ -14,7 +14,15 @@ use crate::{
 pub struct TracedInterpreterError {
     pub error: InterpreterError,
     pub location: Option<ProgramLocation>,
-    backtrace: Backtrace,
+    /// Optional extra detail about the error, e.g. which operand of an
+    /// expression was the wrong type.
+    pub context: Option<&'static str>,
+    /// The source code of the line the error occurred on, if it never made
+    /// it into the program (e.g. because it failed to load).
+    pub source_line: Option<String>,
+    /// This is boxed to keep errors (and therefore the results that contain
+    /// them) small.
+    backtrace: Box<Backtrace>,
 }
 
 impl TracedInterpreterError {
@@ -22,7 +30,50 @@ impl TracedInterpreterError {
         TracedInterpreterError {
             error,
             location: Some(location),
-            backtrace: Backtrace::capture(),
+            context: None,
+            source_line: None,
+            backtrace: Box::new(Backtrace::capture()),
+        }
+    }
+
+    /// Sets the error's location, unless it already has one.
+    pub fn with_default_location(mut self, location: ProgramLocation) -> Self {
+        if self.location.is_none() {
+            self.location = Some(location);
+        }
+        self
+    }
+
+    pub fn with_context(mut self, context: &'static str) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    pub fn with_source_line<T: AsRef<str>>(mut self, source_line: T) -> Self {
+        self.source_line = Some(source_line.as_ref().to_owned());
+        self
+    }
+
+    /// Returns the error's message, including any context, but without the
+    /// " IN <line>" suffix that's part of its `Display` implementation. This
+    /// is useful for front-ends that want to present the line number
+    /// themselves (see `line_number`).
+    pub fn message(&self) -> String {
+        match self.context {
+            Some(context) => format!("{} ({})", self.error, context),
+            None => self.error.to_string(),
+        }
+    }
+
+    /// Returns the number of the program line the error occurred on, if
+    /// any. Errors in immediate mode don't have one.
+    pub fn line_number(&self) -> Option<u64> {
+        match self.location {
+            Some(ProgramLocation {
+                line: ProgramLine::Line(line),
+                ..
+            }) => Some(line),
+            _ => None,
         }
     }
 
@@ -33,23 +84,23 @@ impl TracedInterpreterError {
     ///
     /// Note that `line` is the most recent line passed to the interpreter, if any.
     /// This is used to retrieve information about tokenization errors, which aren't
-    /// owned by the interpreter.
+    /// owned by the interpreter (even if they have a location, since the line they
+    /// refer to never made it into the program). If the error has its own
+    /// `source_line`, that's used instead.
     pub fn get_line_with_pointer_caret<T: AsRef<str>>(
         &self,
         interpreter: &Interpreter,
         line: Option<T>,
     ) -> Vec<String> {
-        if let Some(location) = self.location {
-            let lines = interpreter.program.get_line_with_pointer_caret(location);
-            if !lines.is_empty() {
-                return lines;
-            }
-        }
-        if let Some(line) = line {
-            if let InterpreterError::Syntax(SyntaxError::Tokenization(tok)) = &self.error {
-                let range = tok.string_range(line.as_ref().len());
+        let line = match &self.source_line {
+            Some(source_line) => Some(source_line.as_str()),
+            None => line.as_ref().map(|line| line.as_ref()),
+        };
+        if let InterpreterError::Syntax(SyntaxError::Tokenization(tok)) = &self.error {
+            if let Some(line) = line {
+                let range = tok.string_range(line.len());
                 return vec![
-                    line.as_ref().to_owned(),
+                    line.to_owned(),
                     format!(
                         "{}{}",
                         " ".repeat(range.start),
@@ -57,6 +108,15 @@ impl TracedInterpreterError {
                     ),
                 ];
             }
+            return vec![];
+        }
+        if let Some(source_line) = &self.source_line {
+            // We don't know which part of the line caused the error, so
+            // point at all of it.
+            return vec![source_line.clone(), "^".repeat(source_line.len())];
+        }
+        if let Some(location) = self.location {
+            return interpreter.program.get_line_with_pointer_caret(location);
         }
         vec![]
     }
@@ -78,11 +138,83 @@ pub enum InterpreterError {
     NextWithoutFor,
     BadSubscript,
     IllegalQuantity,
+    Overflow,
+    StringTooLong,
     Unimplemented,
     DivisionByZero,
     RedimensionedArray,
+    /// Applesoft BASIC doesn't have this type, since it automatically
+    /// creates arrays that haven't been declared. We raise it when an
+    /// array is used before being declared via DIM in strict mode.
+    UndimensionedArray,
     CannotContinue,
     IllegalDirect,
+    /// Applesoft BASIC doesn't have this type. We raise it when a program
+    /// prints more than the interpreter's configured maximum output.
+    OutputLimitExceeded,
+    /// Applesoft BASIC doesn't have this type. We raise it when `RESUME` is
+    /// executed without an error having been trapped via `ONERR GOTO`.
+    CannotResume,
+    /// Applesoft BASIC doesn't have this type, since it doesn't support
+    /// `WHILE` loops. We raise it when a `WHILE` loop's condition is false
+    /// and there's no `WEND` after it to skip to.
+    WhileWithoutWend,
+    /// Applesoft BASIC doesn't have this type, since it doesn't support
+    /// `WHILE` loops.
+    WendWithoutWhile,
+    /// Applesoft BASIC doesn't have this type, but Apple DOS does. We raise
+    /// it when `LOAD` is given the name of a program that hasn't been saved.
+    FileNotFound,
+    /// Applesoft BASIC doesn't have this type, but Apple DOS does. We raise
+    /// it when `SAVE` fails; the argument describes why.
+    IoError(String),
+    /// Applesoft BASIC doesn't have this type. We raise it when a program
+    /// is loaded while the interpreter isn't idle, e.g. while it's running
+    /// or waiting for input.
+    Busy,
+}
+
+impl InterpreterError {
+    /// Returns the code that Applesoft BASIC uses for this kind of error,
+    /// which programs can retrieve via `PEEK(222)` after trapping it with
+    /// `ONERR GOTO`. Errors that can't be trapped return `None`.
+    pub fn applesoft_error_code(&self) -> Option<u8> {
+        let code = match self {
+            InterpreterError::NextWithoutFor => 0,
+            // Applesoft BASIC has no `WHILE` loops, so it has no codes for
+            // these. They're mismatched loop boundaries, so we'll use the
+            // code of the error that's most like them.
+            InterpreterError::WhileWithoutWend | InterpreterError::WendWithoutWhile => 0,
+            InterpreterError::Syntax(_)
+            | InterpreterError::DataTypeMismatch
+            | InterpreterError::Unimplemented => 16,
+            InterpreterError::ReturnWithoutGosub => 22,
+            InterpreterError::OutOfData => 42,
+            InterpreterError::IllegalQuantity => 53,
+            InterpreterError::Overflow => 69,
+            InterpreterError::StringTooLong => 176,
+            InterpreterError::OutOfMemory(_) => 77,
+            InterpreterError::UndefinedStatement => 90,
+            InterpreterError::BadSubscript | InterpreterError::UndimensionedArray => 107,
+            InterpreterError::RedimensionedArray => 120,
+            InterpreterError::DivisionByZero => 133,
+            InterpreterError::IllegalDirect => 149,
+            InterpreterError::TypeMismatch => 163,
+            InterpreterError::CannotContinue => 210,
+            // This is the code Apple DOS uses.
+            InterpreterError::FileNotFound => 6,
+            InterpreterError::IoError(_) => 8,
+            // This is a safety limit, so programs shouldn't be able to get
+            // around it.
+            InterpreterError::OutputLimitExceeded => return None,
+            // Trapping this would send a handler that calls `RESUME` into
+            // an infinite loop.
+            InterpreterError::CannotResume => return None,
+            // This is raised by the API rather than by programs.
+            InterpreterError::Busy => return None,
+        };
+        Some(code)
+    }
 }
 
 impl From<TokenizationError> for TracedInterpreterError {
@@ -117,7 +249,9 @@ impl From<SyntaxError> for TracedInterpreterError {
         TracedInterpreterError {
             error: value.into(),
             location: None,
-            backtrace: Backtrace::capture(),
+            context: None,
+            source_line: None,
+            backtrace: Box::new(Backtrace::capture()),
         }
     }
 }
@@ -133,7 +267,9 @@ impl From<OutOfMemoryError> for TracedInterpreterError {
         TracedInterpreterError {
             error: value.into(),
             location: None,
-            backtrace: Backtrace::capture(),
+            context: None,
+            source_line: None,
+            backtrace: Box::new(Backtrace::capture()),
         }
     }
 }
@@ -143,16 +279,18 @@ impl From<InterpreterError> for TracedInterpreterError {
         TracedInterpreterError {
             error: value,
             location: None,
-            backtrace: Backtrace::capture(),
+            context: None,
+            source_line: None,
+            backtrace: Box::new(Backtrace::capture()),
         }
     }
 }
 
 impl Error for TracedInterpreterError {}
 
-impl Display for TracedInterpreterError {
+impl Display for InterpreterError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.error {
+        match self {
             InterpreterError::Syntax(err) => {
                 write!(f, "{}", err)?;
             }
@@ -186,24 +324,57 @@ impl Display for TracedInterpreterError {
             InterpreterError::IllegalQuantity => {
                 write!(f, "ILLEGAL QUANTITY ERROR")?;
             }
+            InterpreterError::Overflow => {
+                write!(f, "OVERFLOW ERROR")?;
+            }
+            InterpreterError::StringTooLong => {
+                write!(f, "STRING TOO LONG ERROR")?;
+            }
             InterpreterError::DivisionByZero => {
                 write!(f, "DIVISION BY ZERO ERROR")?;
             }
             InterpreterError::RedimensionedArray => {
                 write!(f, "REDIM'D ARRAY ERROR")?;
             }
+            InterpreterError::UndimensionedArray => {
+                write!(f, "UNDIM'D ARRAY ERROR")?;
+            }
             InterpreterError::CannotContinue => {
                 write!(f, "CAN'T CONTINUE ERROR")?;
             }
             InterpreterError::IllegalDirect => {
                 write!(f, "ILLEGAL DIRECT ERROR")?;
             }
+            InterpreterError::OutputLimitExceeded => {
+                write!(f, "OUTPUT LIMIT EXCEEDED ERROR")?;
+            }
+            InterpreterError::CannotResume => {
+                write!(f, "CAN'T RESUME ERROR")?;
+            }
+            InterpreterError::WhileWithoutWend => {
+                write!(f, "WHILE WITHOUT WEND ERROR")?;
+            }
+            InterpreterError::WendWithoutWhile => {
+                write!(f, "WEND WITHOUT WHILE ERROR")?;
+            }
+            InterpreterError::FileNotFound => {
+                write!(f, "FILE NOT FOUND ERROR")?;
+            }
+            InterpreterError::IoError(message) => {
+                write!(f, "I/O ERROR ({message})")?;
+            }
+            InterpreterError::Busy => {
+                write!(f, "INTERPRETER BUSY ERROR")?;
+            }
         }
-        if let Some(ProgramLocation {
-            line: ProgramLine::Line(line),
-            ..
-        }) = self.location
-        {
+        Ok(())
+    }
+}
+
+impl Display for TracedInterpreterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())?;
+        if let Some(line) = self.line_number() {
             write!(f, " IN {}", line)?;
         }
         if self.backtrace.status() == BacktraceStatus::Captured {
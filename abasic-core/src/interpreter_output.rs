@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::graphics::GraphicsMode;
+
 #[derive(Debug)]
 pub enum InterpreterOutput {
     Print(String),
@@ -8,6 +10,17 @@ pub enum InterpreterOutput {
     Trace(u64),
     ExtraIgnored,
     Reenter,
+    /// The program was explicitly ended via `END`, as opposed to being
+    /// paused via `STOP` (which emits `Break`).
+    Ended,
+    /// The given graphics screen should be filled with the given color.
+    FillScreen(GraphicsMode, u8),
+    /// The cursor should be moved to the given zero-based row and column,
+    /// so that subsequent output appears there.
+    MoveCursor {
+        row: usize,
+        column: usize,
+    },
 }
 
 impl InterpreterOutput {
@@ -37,7 +50,14 @@ impl Display for InterpreterOutput {
             }
             InterpreterOutput::ExtraIgnored => write!(f, "EXTRA IGNORED"),
             InterpreterOutput::Reenter => write!(f, "REENTER"),
+            InterpreterOutput::Ended => write!(f, "END"),
             InterpreterOutput::Trace(line) => write!(f, "#{}", line),
+            InterpreterOutput::FillScreen(mode, color) => {
+                write!(f, "FILL {} SCREEN WITH COLOR {}", mode, color)
+            }
+            InterpreterOutput::MoveCursor { row, column } => {
+                write!(f, "MOVE CURSOR TO ROW {}, COLUMN {}", row, column)
+            }
         }
     }
 }
@@ -0,0 +1,40 @@
+use std::collections::VecDeque;
+
+/// Reading from this address via `PEEK` returns the most recently pressed
+/// key, with its high bit set if it hasn't been cleared via the strobe.
+pub const KEYBOARD_ADDRESS: f64 = 49152.0;
+
+/// Writing to this address via `POKE` clears the keyboard strobe, which
+/// tells the computer the most recently pressed key has been handled.
+pub const CLEAR_KEYBOARD_STROBE_ADDRESS: f64 = 49168.0;
+
+/// The bit that's set on the keyboard's value when a key is waiting.
+const STROBE_BIT: u8 = 0x80;
+
+/// Emulates just enough of the Apple II's memory-mapped keyboard for
+/// programs that poll it via `PEEK` to work. Keys are supplied by the
+/// client and are queued until the program clears the strobe.
+#[derive(Debug, Default)]
+pub struct Keyboard {
+    queue: VecDeque<u8>,
+    last_key: u8,
+}
+
+impl Keyboard {
+    pub fn push_key(&mut self, key: u8) {
+        self.queue.push_back(key & !STROBE_BIT);
+    }
+
+    pub fn peek(&self) -> u8 {
+        match self.queue.front() {
+            Some(key) => key | STROBE_BIT,
+            None => self.last_key,
+        }
+    }
+
+    pub fn clear_strobe(&mut self) {
+        if let Some(key) = self.queue.pop_front() {
+            self.last_key = key;
+        }
+    }
+}
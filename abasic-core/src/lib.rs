@@ -1,16 +1,20 @@
 mod analyzer;
 mod arrays;
+mod binary_program;
 mod builtins;
 mod data;
 mod expression;
+mod graphics;
 mod interpreter;
 mod interpreter_error;
 mod interpreter_output;
+mod keyboard;
 mod line_cruncher;
 mod line_number_parser;
 mod operators;
 mod program;
 mod program_lines;
+mod program_storage;
 mod random;
 mod statement;
 mod string_manager;
@@ -20,9 +24,15 @@ mod tokenizer;
 mod value;
 mod variables;
 
-pub use analyzer::{DiagnosticMessage, SourceFileAnalyzer, SourceFileMap, TokenType};
-pub use interpreter::{Interpreter, InterpreterState};
+pub use analyzer::{
+    AnalyzerOptions, DiagnosticMessage, OutlineSymbol, OutlineSymbolKind, ProgramMetrics,
+    SourceFileAnalyzer, SourceFileMap, TokenType, VariableSymbol,
+};
+pub use binary_program::BinaryProgramError;
+pub use graphics::GraphicsMode;
+pub use interpreter::{InputTarget, Interpreter, InterpreterState, RunOutcome};
 pub use interpreter_error::{InterpreterError, OutOfMemoryError, TracedInterpreterError};
 pub use interpreter_output::InterpreterOutput;
+pub use program_storage::ProgramStorage;
 pub use syntax_error::SyntaxError;
 pub use tokenizer::Token;
@@ -4,6 +4,7 @@
 ///
 /// This struct allows clients to iterate through the bytes
 /// of an array, skipping all such whitespace.
+#[derive(Clone)]
 pub struct LineCruncher<'a> {
     bytes: &'a [u8],
     index: usize,
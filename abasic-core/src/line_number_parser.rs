@@ -28,9 +28,7 @@ pub fn parse_line_number<T: AsRef<str>>(value: T) -> Option<(u64, usize)> {
         }
     }
 
-    let Some((start, end)) = number_endpoints else {
-        return None;
-    };
+    let (start, end) = number_endpoints?;
     let Ok(number) = value.as_ref()[start..end].parse::<u64>() else {
         return None;
     };
@@ -1,5 +1,6 @@
 use crate::{
     interpreter_error::{InterpreterError, TracedInterpreterError},
+    string_manager::StringManager,
     tokenizer::Token,
     value::Value,
 };
@@ -10,6 +11,9 @@ pub enum AddOrSubtractOp {
     Subtract,
 }
 
+/// The maximum length of a string created via concatenation.
+const MAX_STRING_LENGTH: usize = 255;
+
 impl AddOrSubtractOp {
     // I considered TryFrom here but it required an associated Error type
     // and I just wanted to use Option.
@@ -21,16 +25,27 @@ impl AddOrSubtractOp {
         }
     }
 
+    /// Evaluates the operation. Adding two strings concatenates them, using
+    /// the given string manager to de-dupe the result. Like Applesoft BASIC,
+    /// the result can't be longer than `MAX_STRING_LENGTH` characters.
     pub fn evaluate(
         &self,
         left_side: &Value,
         right_side: &Value,
+        string_manager: &mut StringManager,
     ) -> Result<Value, TracedInterpreterError> {
         let result = match (left_side, right_side) {
             (Value::Number(l), Value::Number(r)) => match self {
                 AddOrSubtractOp::Add => l + r,
                 AddOrSubtractOp::Subtract => l - r,
             },
+            (Value::String(l), Value::String(r)) if *self == AddOrSubtractOp::Add => {
+                if l.chars().count() + r.chars().count() > MAX_STRING_LENGTH {
+                    return Err(InterpreterError::StringTooLong.into());
+                }
+                let concatenated = format!("{l}{r}");
+                return Ok(string_manager.from_string(concatenated).into());
+            }
             _ => return Err(InterpreterError::TypeMismatch.into()),
         };
         Ok(result.into())
@@ -62,7 +77,12 @@ impl UnaryOp {
                 let number: f64 = -value.try_into()?;
                 Ok(number.into())
             }
-            UnaryOp::Not => Ok(Value::from_bool(!value.to_bool())),
+            // Applesoft BASIC raises a type mismatch when NOT is applied
+            // to a string, rather than checking whether it's empty.
+            UnaryOp::Not => {
+                let number: f64 = value.try_into()?;
+                Ok(Value::from_bool(number == 0.0))
+            }
         }
     }
 }
@@ -134,6 +154,17 @@ impl MultiplyOrDivideOp {
     }
 }
 
+/// Returns a type mismatch error for a binary operator whose right side
+/// didn't match the type of its left side.
+pub fn right_side_type_mismatch(left_side_is_string: bool) -> TracedInterpreterError {
+    let err: TracedInterpreterError = InterpreterError::TypeMismatch.into();
+    if left_side_is_string {
+        err.with_context("EXPECTED STRING ON RIGHT SIDE")
+    } else {
+        err.with_context("EXPECTED NUMBER ON RIGHT SIDE")
+    }
+}
+
 #[derive(Debug)]
 pub enum EqualityOp {
     EqualTo,
@@ -178,7 +209,8 @@ impl EqualityOp {
         let result = match (left_side, right_side) {
             (Value::String(l), Value::String(r)) => self.evaluate_partial_ord(l, r),
             (Value::Number(l), Value::Number(r)) => self.evaluate_partial_ord(l, r),
-            _ => return Err(InterpreterError::TypeMismatch.into()),
+            (Value::String(_), _) => return Err(right_side_type_mismatch(true)),
+            (Value::Number(_), _) => return Err(right_side_type_mismatch(false)),
         };
         // This is how Applesoft BASIC evaluates equality expressions.
         if result {
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::RangeInclusive};
 
 use crate::{
     data::{DataElement, DataIterator},
@@ -11,7 +11,9 @@ use crate::{
     variables::Variables,
 };
 
-const STACK_LIMIT: usize = 32;
+/// How deep the stack can get before we raise a stack overflow, unless
+/// configured otherwise.
+pub const DEFAULT_STACK_LIMIT: usize = 32;
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub enum ProgramLine {
@@ -98,21 +100,54 @@ pub struct Program {
     breakpoint: Option<NumberedProgramLocation>,
     stack: Vec<StackFrame>,
     loop_stack: Vec<LoopInfo>,
+    /// The location of the condition of each active `WHILE` loop.
+    while_loop_stack: Vec<ProgramLocation>,
     data_iterator: Option<DataIterator>,
     functions: HashMap<Symbol, FunctionDefinition>,
+    /// The line to go to when an error occurs, set via `ONERR GOTO`.
+    error_handler: Option<u64>,
+    /// Where the statement currently being evaluated started, and how
+    /// deep the stack was at the time, so we can retry it via `RESUME`.
+    statement_start: ProgramLocation,
+    statement_stack_len: usize,
+    /// Where `RESUME` will go, if an error has been trapped.
+    resume_location: Option<ProgramLocation>,
+    /// Overrides `DEFAULT_STACK_LIMIT`, if set.
+    stack_limit: Option<usize>,
+    /// How many tokens we've advanced past over our lifetime.
+    tokens_evaluated: usize,
 }
 
 impl Program {
+    pub fn set_stack_limit(&mut self, stack_limit: usize) {
+        self.stack_limit = Some(stack_limit);
+    }
+
+    /// Raises a stack overflow if a stack of the given depth can't grow
+    /// any further.
+    fn check_stack_depth(&self, depth: usize) -> Result<(), TracedInterpreterError> {
+        if depth >= self.stack_limit.unwrap_or(DEFAULT_STACK_LIMIT) {
+            return Err(OutOfMemoryError::StackOverflow.into());
+        }
+        Ok(())
+    }
+
     /// Set the content of the "immediate" line (i.e., the line that is being
     /// evaluated by the interpreter and has no line number) and go there.
     ///
     /// Resets the stack (unless we're in a breakpoint).
+    ///
+    /// This also marks the start of the immediate line as the current
+    /// statement, so that errors raised before it starts running (e.g. while
+    /// tokenizing it) aren't mistaken for errors in the last statement of
+    /// a program that set up an `ONERR GOTO` handler.
     pub fn set_and_goto_immediate_line(&mut self, tokens: Vec<Token>) {
         if self.breakpoint.is_none() {
             self.stack.clear();
         }
         self.immediate_line = tokens;
         self.location = Default::default();
+        self.mark_statement_start();
     }
 
     /// Removes any loop with the given symbol, and any loops in front of it in
@@ -156,7 +191,7 @@ impl Program {
     ///
     /// Finally, note that Dartmouth BASIC actually had a "FOR WITHOUT NEXT" error,
     /// but this doesn't seem to be present in Applesoft BASIC, nor is it present in
-    /// our implementation.
+    /// our implementation (although the analyzer can optionally warn about it).
     fn remove_loop_with_name(&mut self, symbol: &Symbol) -> Option<LoopInfo> {
         let mut found_index = None;
         for (i, loop_info) in self.loop_stack.iter().enumerate().rev() {
@@ -205,9 +240,7 @@ impl Program {
         step_value: f64,
     ) -> Result<(), TracedInterpreterError> {
         self.remove_loop_with_name(&symbol);
-        if self.loop_stack.len() == STACK_LIMIT {
-            return Err(OutOfMemoryError::StackOverflow.into());
-        }
+        self.check_stack_depth(self.loop_stack.len())?;
         self.loop_stack.push(LoopInfo {
             location: self.location,
             symbol: symbol.clone(),
@@ -218,11 +251,25 @@ impl Program {
         Ok(())
     }
 
+    /// Ends the current iteration of the loop with the given name, or the
+    /// innermost loop if no name is given. Returns the name of the loop's
+    /// variable, and whether the loop will continue, in which case we will
+    /// have jumped back to its beginning.
     pub fn end_loop(
         &mut self,
         variables: &mut Variables,
-        symbol: Symbol,
-    ) -> Result<(), TracedInterpreterError> {
+        symbol: Option<Symbol>,
+    ) -> Result<(Symbol, bool), TracedInterpreterError> {
+        let symbol = match symbol {
+            Some(symbol) => symbol,
+            None => match self.loop_stack.last() {
+                Some(loop_info) => loop_info.symbol.clone(),
+                None => return Err(InterpreterError::NextWithoutFor.into()),
+            },
+        };
+        // Note that we're reading the loop variable's current value rather
+        // than remembering it in the loop info, since the body of the loop
+        // may have assigned to it.
         let current_value = variables.get(&symbol);
         let current_number: f64 = current_value.clone().try_into()?;
 
@@ -250,10 +297,71 @@ impl Program {
             self.loop_stack.push(loop_info);
         }
 
-        variables.set(symbol, new_value.into())?;
+        variables.set(symbol.clone(), new_value.into())?;
+        Ok((symbol, continue_loop))
+    }
+
+    /// Starts a `WHILE` loop whose condition is at the given location. Like
+    /// `start_loop()`, this forgets about any existing frame for the same
+    /// loop, along with any loops nested inside it, so jumping out of the
+    /// loop via `GOTO` and then re-entering it doesn't leak memory.
+    pub fn start_while_loop(
+        &mut self,
+        condition_location: ProgramLocation,
+    ) -> Result<(), TracedInterpreterError> {
+        if let Some(i) = self
+            .while_loop_stack
+            .iter()
+            .rposition(|&location| location == condition_location)
+        {
+            self.while_loop_stack.truncate(i);
+        }
+        self.check_stack_depth(self.while_loop_stack.len())?;
+        self.while_loop_stack.push(condition_location);
         Ok(())
     }
 
+    /// Goes back to the condition of the innermost `WHILE` loop, so it can
+    /// be re-evaluated. Returns the location we were at, so we can go back
+    /// there via `end_while_loop()` if the condition is false.
+    pub fn goto_while_loop_condition(&mut self) -> Result<ProgramLocation, TracedInterpreterError> {
+        let Some(&condition_location) = self.while_loop_stack.last() else {
+            return Err(InterpreterError::WendWithoutWhile.into());
+        };
+        let wend_location = self.location;
+        self.location = condition_location;
+        Ok(wend_location)
+    }
+
+    /// Forgets about the innermost `WHILE` loop and goes to the given
+    /// location, which should be just after its `WEND`.
+    pub fn end_while_loop(&mut self, wend_location: ProgramLocation) {
+        self.while_loop_stack.pop();
+        self.location = wend_location;
+    }
+
+    /// Skips past the `WEND` that matches the `WHILE` loop we're in,
+    /// accounting for any nested loops along the way.
+    pub fn skip_past_matching_wend(&mut self) -> Result<(), TracedInterpreterError> {
+        let while_location = self.location;
+        let mut depth = 0;
+        loop {
+            while let Some(token) = self.next_token() {
+                match token {
+                    Token::While => depth += 1,
+                    Token::Wend if depth == 0 => return Ok(()),
+                    Token::Wend => depth -= 1,
+                    _ => {}
+                }
+            }
+            if !self.next_line() {
+                // Report the error at the WHILE rather than the end of the program.
+                self.location = while_location;
+                return Err(InterpreterError::WhileWithoutWend.into());
+            }
+        }
+    }
+
     pub fn has_line_number(&self, line_number: u64) -> bool {
         self.numbered_lines.has(line_number)
     }
@@ -261,12 +369,21 @@ impl Program {
     /// Resets virtually everything in the program
     /// except for the actual code.
     pub fn reset_runtime_state(&mut self) {
+        self.clear_runtime_state();
+        self.end();
+    }
+
+    /// Like `reset_runtime_state`, but leaves our location in the program
+    /// alone, so a running program can keep going.
+    pub fn clear_runtime_state(&mut self) {
         self.breakpoint = None;
         self.reset_data_cursor();
         self.functions.clear();
         self.stack.clear();
         self.loop_stack.clear();
-        self.end();
+        self.while_loop_stack.clear();
+        self.error_handler = None;
+        self.resume_location = None;
     }
 
     /// Go to the first numbered line. Resets virtually everything in the program
@@ -297,9 +414,7 @@ impl Program {
     }
 
     pub fn gosub_line_number(&mut self, line_number: u64) -> Result<(), TracedInterpreterError> {
-        if self.stack.len() == STACK_LIMIT {
-            return Err(OutOfMemoryError::StackOverflow.into());
-        }
+        self.check_stack_depth(self.stack.len())?;
         let return_location = self.location;
         self.goto_line_number(line_number)?;
         self.stack.push(StackFrame {
@@ -318,6 +433,54 @@ impl Program {
         Ok(())
     }
 
+    /// Discards the return address of the most recent `GOSUB` without
+    /// returning to it, so the next `RETURN` goes to the caller's caller.
+    pub fn pop_gosub_frame(&mut self) -> Result<(), TracedInterpreterError> {
+        if self.stack.pop().is_none() {
+            return Err(InterpreterError::ReturnWithoutGosub.into());
+        }
+        Ok(())
+    }
+
+    /// Remembers where the statement that's about to be evaluated starts,
+    /// in case it raises an error that needs to be retried via `RESUME`.
+    pub fn mark_statement_start(&mut self) {
+        self.statement_start = self.location;
+        self.statement_stack_len = self.stack.len();
+    }
+
+    pub fn set_error_handler(&mut self, line_number: u64) {
+        self.error_handler = Some(line_number);
+    }
+
+    /// If an error handler has been set via `ONERR GOTO`, goes to it and
+    /// remembers the statement that raised the error so it can be retried
+    /// via `RESUME`. Any function calls in the statement are abandoned.
+    ///
+    /// Returns whether the error was trapped. Errors raised by the
+    /// immediate line are never trapped.
+    pub fn trap_error(&mut self) -> bool {
+        let Some(error_handler) = self.error_handler else {
+            return false;
+        };
+        if self.statement_start.as_numbered().is_none() || !self.has_line_number(error_handler) {
+            return false;
+        }
+        self.stack.truncate(self.statement_stack_len);
+        self.resume_location = Some(self.statement_start);
+        self.goto_line_number(error_handler).is_ok()
+    }
+
+    /// Goes back to the start of the statement that raised the most
+    /// recently trapped error.
+    pub fn resume(&mut self) -> Result<(), TracedInterpreterError> {
+        let Some(location) = self.resume_location.take() else {
+            return Err(InterpreterError::CannotResume.into());
+        };
+        self.location = location;
+        Ok(())
+    }
+
     pub fn end(&mut self) {
         self.set_and_goto_immediate_line(vec![]);
     }
@@ -341,6 +504,20 @@ impl Program {
         self.functions.get(name).map(|f| &f.arguments)
     }
 
+    /// Returns the tokens that make up the body of the function with the
+    /// given name, i.e. everything after the `=` up to the end of the
+    /// statement, if the function has been defined.
+    pub fn get_function_body(&self, name: &Symbol) -> Option<&[Token]> {
+        let location = self.functions.get(name)?.location;
+        let tokens = self.numbered_lines.get(location.line)?;
+        let body = tokens.get(location.token_index..)?;
+        let end = body
+            .iter()
+            .position(|token| *token == Token::Colon)
+            .unwrap_or(body.len());
+        Some(&body[..end])
+    }
+
     /// Push the function with the given name onto the stack.
     /// Note that a function with this name MUST exist, or else the program will panic.
     /// You can use `get_function_argument_names` to validate this beforehand.
@@ -349,9 +526,12 @@ impl Program {
         name: &Symbol,
         bindings: Variables,
     ) -> Result<(), TracedInterpreterError> {
-        if self.stack.len() == STACK_LIMIT {
+        // Function calls recurse in Rust, so we never let them go deeper
+        // than the default limit, lest we overflow the native stack.
+        if self.stack.len() >= DEFAULT_STACK_LIMIT {
             return Err(OutOfMemoryError::StackOverflow.into());
         }
+        self.check_stack_depth(self.stack.len())?;
         self.stack.push(StackFrame {
             return_location: self.location,
             variables: bindings,
@@ -436,16 +616,21 @@ impl Program {
 
     pub fn get_data_location(&self) -> Option<ProgramLocation> {
         if let Some(data_iterator) = &self.data_iterator {
-            if let Some(location) = data_iterator.current_location() {
-                Some(location)
-            } else {
-                None
-            }
+            data_iterator.current_location()
         } else {
             None
         }
     }
 
+    /// Returns the number of `DATA` elements that haven't been read yet.
+    pub fn remaining_data_elements(&self) -> usize {
+        if let Some(data_iterator) = &self.data_iterator {
+            data_iterator.remaining()
+        } else {
+            self.numbered_lines.data_iterator().remaining()
+        }
+    }
+
     pub fn reset_data_cursor(&mut self) {
         self.data_iterator = None;
     }
@@ -480,6 +665,20 @@ impl Program {
         }
     }
 
+    /// Returns the numbers of all the program's lines, in ascending order.
+    pub fn line_numbers(&self) -> Vec<u64> {
+        self.numbered_lines.line_numbers()
+    }
+
+    pub fn get_numbered_line(&self, line_number: u64) -> Option<&Vec<Token>> {
+        self.numbered_lines.get(line_number)
+    }
+
+    pub fn list_tokens(&self) -> Vec<(u64, &Vec<Token>)> {
+        self.numbered_lines.list_tokens()
+    }
+
+    /// Returns the source of every line, each terminated by a newline.
     pub fn list(&self) -> Vec<String> {
         self.numbered_lines.list()
     }
@@ -491,11 +690,31 @@ impl Program {
     /// which has now been changed in unknown ways.
     pub fn set_numbered_line(&mut self, line_number: u64, tokens: Vec<Token>) {
         self.numbered_lines.set(line_number, tokens);
+        self.reset_for_changed_lines();
+    }
+
+    /// Deletes every numbered line in the given range. Like
+    /// `set_numbered_line()`, this resets a lot of the program's state.
+    pub fn delete_numbered_lines(&mut self, range: RangeInclusive<u64>) {
+        self.numbered_lines.remove_range(range);
+        self.reset_for_changed_lines();
+    }
+
+    /// Renumbers every numbered line, updating any references to them. See
+    /// `ProgramLines::renumber()` for details.
+    pub fn renumber_lines(&mut self, start: u64, increment: u64) -> Vec<(u64, u64)> {
+        let missing_references = self.numbered_lines.renumber(start, increment);
+        self.reset_for_changed_lines();
+        missing_references
+    }
+
+    fn reset_for_changed_lines(&mut self) {
         self.breakpoint = None;
         self.reset_data_cursor();
         self.functions.clear();
         self.stack.clear();
         self.loop_stack.clear();
+        self.while_loop_stack.clear();
         self.end();
     }
 
@@ -526,11 +745,22 @@ impl Program {
     pub fn next_token(&mut self) -> Option<Token> {
         let next = self.peek_next_token();
         if next.is_some() {
-            self.location.token_index += 1;
+            self.advance_token_index();
         }
         next
     }
 
+    fn advance_token_index(&mut self) {
+        self.location.token_index += 1;
+        self.tokens_evaluated = self.tokens_evaluated.wrapping_add(1);
+    }
+
+    /// Returns the total number of tokens we've advanced past, which is
+    /// a rough measure of how much work has been done.
+    pub fn tokens_evaluated(&self) -> usize {
+        self.tokens_evaluated
+    }
+
     /// Goes back in the stream just before the occurrence of the given token.
     ///
     /// If the token isn't found, panics.
@@ -538,6 +768,10 @@ impl Program {
     /// Generally this shouldn't be used unless absolutely necessary, e.g. because
     /// we might need to pause evaluation to do some asynchronous activity and then
     /// resume it again once the task is complete.
+    ///
+    /// Note that this only works if the token starts the statement being
+    /// evaluated and we haven't left its line, which is why `INPUT` and `GET`
+    /// can't be used inside expressions (e.g. in the body of a function).
     pub fn rewind_before_token(&mut self, token: Token) {
         let wrapped_token = Some(token.clone());
         while self.location.token_index > 0 {
@@ -569,6 +803,19 @@ impl Program {
         }
     }
 
+    /// Expect the current statement to be over, i.e. that the next token (if
+    /// any) is one that can follow a complete statement. If it isn't,
+    /// advance past it and return an error.
+    pub fn expect_end_of_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        match self.peek_next_token() {
+            None | Some(Token::Colon) | Some(Token::Else) => Ok(()),
+            token => {
+                self.advance_token_index();
+                Err(SyntaxError::UnexpectedToken(token).into())
+            }
+        }
+    }
+
     /// Checks to see if the next token in the stream is the given token.
     ///
     /// If it is, then our position in the stream advances and we return `true`.
@@ -576,7 +823,7 @@ impl Program {
     /// Otherwise, we return `false`.
     pub fn accept_next_token(&mut self, token: Token) -> bool {
         if self.peek_next_token() == Some(token) {
-            self.location.token_index += 1;
+            self.advance_token_index();
             true
         } else {
             false
@@ -595,7 +842,7 @@ impl Program {
     {
         if let Some(next_token) = self.peek_next_token() {
             if let Some(t) = f(next_token) {
-                self.location.token_index += 1;
+                self.advance_token_index();
                 Some(t)
             } else {
                 None
@@ -1,5 +1,8 @@
 use core::fmt::Debug;
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::RangeInclusive,
+};
 
 use crate::{
     data::{DataChunk, DataIterator},
@@ -7,6 +10,23 @@ use crate::{
     tokenizer::Token,
 };
 
+/// If the token at the given index is a line number referenced by
+/// `GOTO`, `GOSUB`, `THEN`, or `ELSE`, returns it.
+pub fn line_number_reference(tokens: &[Token], index: usize) -> Option<u64> {
+    let previous = tokens.get(index.checked_sub(1)?)?;
+    if !matches!(
+        previous,
+        Token::Goto | Token::Gosub | Token::Then | Token::Else
+    ) {
+        return None;
+    }
+    let Some(&Token::NumericLiteral(line_number)) = tokens.get(index) else {
+        return None;
+    };
+    // This mirrors how the interpreter converts these to line numbers.
+    Some(line_number as u64)
+}
+
 #[derive(Default)]
 pub struct ProgramLines {
     numbered_lines: HashMap<u64, Vec<Token>>,
@@ -72,6 +92,57 @@ impl ProgramLines {
         }
     }
 
+    /// Removes every line whose number is in the given range.
+    pub fn remove_range(&mut self, range: RangeInclusive<u64>) {
+        if range.is_empty() {
+            // BTreeSet::range() panics if the range is backwards.
+            return;
+        }
+        let line_numbers: Vec<u64> = self.sorted_line_numbers.range(range).copied().collect();
+        for line_number in line_numbers {
+            self.sorted_line_numbers.remove(&line_number);
+            self.numbered_lines.remove(&line_number);
+        }
+    }
+
+    /// Renumbers every line, starting at `start` and going up by `increment`,
+    /// and rewrites the line numbers after `GOTO`, `GOSUB`, `THEN`, and `ELSE`
+    /// to match.
+    ///
+    /// References to lines that don't exist are left alone, and returned as
+    /// pairs of the referring line's new number and the referenced number.
+    pub fn renumber(&mut self, start: u64, increment: u64) -> Vec<(u64, u64)> {
+        let new_line_numbers: HashMap<u64, u64> = self
+            .sorted_line_numbers
+            .iter()
+            .enumerate()
+            .map(|(i, &line_number)| (line_number, start + i as u64 * increment))
+            .collect();
+        let mut missing_references = vec![];
+        let mut numbered_lines = HashMap::with_capacity(self.numbered_lines.len());
+        for (line_number, mut tokens) in self.numbered_lines.drain() {
+            let new_line_number = new_line_numbers[&line_number];
+            for i in 0..tokens.len() {
+                let Some(target) = line_number_reference(&tokens, i) else {
+                    continue;
+                };
+                match new_line_numbers.get(&target) {
+                    Some(&new_target) => tokens[i] = Token::NumericLiteral(new_target as f64),
+                    None => missing_references.push((new_line_number, target)),
+                }
+            }
+            numbered_lines.insert(new_line_number, tokens);
+        }
+        self.sorted_line_numbers = numbered_lines.keys().copied().collect();
+        self.numbered_lines = numbered_lines;
+        missing_references.sort();
+        missing_references
+    }
+
+    pub fn line_numbers(&self) -> Vec<u64> {
+        self.sorted_line_numbers.iter().copied().collect()
+    }
+
     pub fn list_tokens(&self) -> Vec<(u64, &Vec<Token>)> {
         let mut lines: Vec<(u64, &Vec<Token>)> = Vec::with_capacity(self.numbered_lines.len());
 
@@ -83,6 +154,9 @@ impl ProgramLines {
         lines
     }
 
+    /// Returns the source of every line, in line number order. Each line
+    /// is terminated by exactly one newline, so clients can print them
+    /// as-is.
     pub fn list(&self) -> Vec<String> {
         let mut lines: Vec<String> = Vec::with_capacity(self.numbered_lines.len());
 
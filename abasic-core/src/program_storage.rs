@@ -0,0 +1,15 @@
+/// A place where `SAVE` and `LOAD` can persist programs, e.g. a
+/// filesystem or a browser's local storage. The core interpreter doesn't
+/// know about any of these, so clients need to provide their own.
+pub trait ProgramStorage {
+    /// Saves the given program listing under the given name, replacing
+    /// any program already saved under it. Each line of the listing is
+    /// terminated by a newline.
+    ///
+    /// If the program can't be saved, returns a description of why.
+    fn save(&mut self, name: &str, listing: &[String]) -> Result<(), String>;
+
+    /// Returns the listing of the program saved under the given name, if
+    /// there is one.
+    fn load(&self, name: &str) -> Option<Vec<String>>;
+}
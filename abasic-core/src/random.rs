@@ -16,7 +16,11 @@ pub struct Rng {
 /// by Knuth and H. W. Lewis.
 impl Rng {
     pub fn new(seed: u64) -> Self {
-        Rng { seed }
+        // Keeping the seed within the modulus ensures that multiplying it
+        // in `random()` can't overflow.
+        Rng {
+            seed: seed % MODULUS,
+        }
     }
 
     pub fn random(&mut self) -> f64 {
@@ -30,15 +34,16 @@ impl Rng {
 
     /// Simulates a BASIC-style RND call.
     pub fn rnd(&mut self, number: f64) -> Result<f64, InterpreterError> {
-        // Applesoft BASIC would always return the most recent random with the argument '0', and
-        // predefined items in the sequence with '-1', but in practice all the code I've seen
-        // just calls it with '1', and *any* positive number is supposed to return a random number
-        // in the interval [0, 1).
+        // Applesoft BASIC returns the most recent random with the argument '0', and *any*
+        // positive number is supposed to return a random number in the interval [0, 1).
         if number < 0.0 {
-            // None of the code I've seen actually uses this, and
-            // I don't fully understand what it means, so just don't
-            // support it for now.
-            Err(InterpreterError::Unimplemented)
+            // A negative number reseeds the generator based on the number, so that a given
+            // negative number always starts the same sequence. Most of the variation in a
+            // float's bits is in its upper half, so we fold that into the lower half, which
+            // is all the seed can hold.
+            let bits = number.to_bits();
+            *self = Rng::new(bits ^ (bits >> 32));
+            Ok(self.random())
         } else if number == 0.0 {
             Ok(self.latest_random())
         } else {
@@ -1,8 +1,22 @@
+use std::rc::Rc;
+
 use crate::{
-    expression::ExpressionEvaluator, program::Program, symbol::Symbol, value::Value, Interpreter,
-    InterpreterError, InterpreterOutput, SyntaxError, Token, TracedInterpreterError,
+    builtins::{is_unparenthesized_print_tab_or_spc, MID_ASSIGNMENT_TARGET, PRINT_SPC, PRINT_TAB},
+    expression::ExpressionEvaluator,
+    graphics::{GraphicsMode, BLACK, CLEAR_HIGH_RES_TO_HCOLOR_ADDRESS},
+    interpreter::{normalize_address, InputTarget, InterpreterState},
+    program::Program,
+    symbol::Symbol,
+    value::Value,
+    Interpreter, InterpreterError, InterpreterOutput, SyntaxError, Token, TracedInterpreterError,
 };
 
+/// The number of rows on the Apple II's text screen.
+const SCREEN_ROWS: f64 = 24.0;
+
+/// The number of columns on the Apple II's text screen.
+const SCREEN_COLUMNS: f64 = 40.0;
+
 struct LValue {
     symbol_name: Symbol,
     array_index: Option<Vec<usize>>,
@@ -24,31 +38,81 @@ impl<'a> StatementEvaluator<'a> {
                     .output(InterpreterOutput::Trace(line_number));
             }
         }
-        match self.program().next_token() {
-            Some(Token::Stop) => Ok(self.interpreter.break_at_current_location()),
-            Some(Token::Dim) => self.evaluate_dim_statement(),
-            Some(Token::Print) | Some(Token::QuestionMark) => self.evaluate_print_statement(),
-            Some(Token::Input) => self.evaluate_input_statement(),
-            Some(Token::If) => self.evaluate_if_statement(),
-            Some(Token::Goto) => self.evaluate_goto_statement(),
-            Some(Token::Gosub) => self.evaluate_gosub_statement(),
-            Some(Token::Return) => self.program().return_to_last_gosub(),
+        self.interpreter.maybe_record_line_execution();
+        let Some(token) = self.program().next_token() else {
+            return Ok(());
+        };
+        match token {
+            // These statements either change our location in the program or
+            // deal with the rest of the statement themselves, so we won't
+            // check what comes after them.
+            Token::Stop => {
+                self.interpreter.stop_at_current_location();
+                return Ok(());
+            }
+            Token::If => return self.evaluate_if_statement(),
+            Token::Goto => return self.evaluate_goto_statement(),
+            Token::Gosub => return self.evaluate_gosub_statement(),
+            Token::Return => return self.program().return_to_last_gosub(),
+            Token::Resume => return self.program().resume(),
+            Token::While => return self.evaluate_while_statement(),
+            Token::Wend => return self.evaluate_wend_statement(),
             // Dartmouth BASIC only allowed END at the very end of a program,
             // while Applesoft allowed it anywhere. We'll do the latter.
-            Some(Token::End) => Ok(self.program().end()),
-            Some(Token::For) => self.evaluate_for_statement(),
-            Some(Token::Next) => self.evaluate_next_statement(),
-            Some(Token::Restore) => Ok(self.program().reset_data_cursor()),
-            Some(Token::Def) => self.evaluate_def_statement(),
-            Some(Token::Read) => self.evaluate_read_statement(),
-            Some(Token::Remark(_)) => Ok(()),
-            Some(Token::Colon) => Ok(()),
-            Some(Token::Data(_)) => Ok(()),
-            Some(Token::Let) => self.evaluate_let_statement(),
-            Some(Token::Symbol(symbol)) => self.evaluate_assignment_statement(symbol),
-            Some(_) => Err(SyntaxError::UnexpectedToken.into()),
-            None => Ok(()),
+            Token::End => {
+                self.program().end();
+                self.interpreter.output(InterpreterOutput::Ended);
+                return Ok(());
+            }
+            Token::Def => return self.evaluate_def_statement(),
+            Token::Colon => return Ok(()),
+            Token::Input => {
+                self.evaluate_input_statement()?;
+                if self.interpreter.get_state() == InterpreterState::AwaitingInput {
+                    // We've rewound to the beginning of the statement.
+                    return Ok(());
+                }
+            }
+            Token::Get => {
+                self.evaluate_get_statement()?;
+                if self.interpreter.get_state() == InterpreterState::AwaitingInput {
+                    // We've rewound to the beginning of the statement.
+                    return Ok(());
+                }
+            }
+            Token::Dim => self.evaluate_dim_statement()?,
+            Token::Print | Token::QuestionMark => self.evaluate_print_statement()?,
+            Token::For => self.evaluate_for_statement()?,
+            Token::Next => self.evaluate_next_statement()?,
+            Token::Restore => self.program().reset_data_cursor(),
+            Token::Clear => self.interpreter.clear(),
+            Token::Gr => {
+                // Like Applesoft, GR clears the screen to black and also
+                // resets the current color to black.
+                self.interpreter.graphics.reset_low_res_color();
+                self.interpreter
+                    .output(InterpreterOutput::FillScreen(GraphicsMode::LowRes, BLACK));
+            }
+            Token::Hgr => {
+                self.interpreter
+                    .output(InterpreterOutput::FillScreen(GraphicsMode::HighRes, BLACK));
+            }
+            Token::Color => self.evaluate_color_statement(GraphicsMode::LowRes)?,
+            Token::Hcolor => self.evaluate_color_statement(GraphicsMode::HighRes)?,
+            Token::Call => self.evaluate_call_statement()?,
+            Token::Poke => self.evaluate_poke_statement()?,
+            Token::Pop => self.program().pop_gosub_frame()?,
+            Token::Onerr => self.evaluate_onerr_statement()?,
+            Token::Read => self.evaluate_read_statement()?,
+            Token::Remark(_) => {}
+            Token::Data(_) => {}
+            Token::Let => self.evaluate_let_statement()?,
+            Token::Symbol(symbol) => self.evaluate_assignment_statement(symbol)?,
+            token => return Err(SyntaxError::UnexpectedToken(Some(token)).into()),
         }
+        // Anything left over after a complete statement is probably a typo,
+        // e.g. a missing colon.
+        self.program().expect_end_of_statement()
     }
 
     fn program(&mut self) -> &mut Program {
@@ -65,12 +129,32 @@ impl<'a> StatementEvaluator<'a> {
         } else {
             ExpressionEvaluator::new(self.interpreter)
                 .evaluate_array_index()
-                .map(|index| Some(index))
+                .map(Some)
+        }
+    }
+
+    fn evaluate_while_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        let condition_location = self.program().get_location();
+        if ExpressionEvaluator::new(self.interpreter).evaluate_condition()? {
+            self.program().expect_end_of_statement()?;
+            self.program().start_while_loop(condition_location)
+        } else {
+            self.program().skip_past_matching_wend()
+        }
+    }
+
+    /// Rather than jumping back to the `WHILE` statement, we re-evaluate its
+    /// condition here, so that we don't start a new loop every iteration.
+    fn evaluate_wend_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        let wend_location = self.program().goto_while_loop_condition()?;
+        if !ExpressionEvaluator::new(self.interpreter).evaluate_condition()? {
+            self.program().end_while_loop(wend_location);
         }
+        Ok(())
     }
 
     fn evaluate_if_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let conditional_value = self.evaluate_expression()?;
+        let condition = ExpressionEvaluator::new(self.interpreter).evaluate_condition()?;
 
         // TODO: Dartmouth and Applesoft BASIC both support `IF X GOTO`,
         // whereas we are enforcing the use of `THEN` here.
@@ -79,7 +163,7 @@ impl<'a> StatementEvaluator<'a> {
         // Note that Applesoft BASIC doesn't seem to support ELSE,
         // but it's used in Tim Hartnell's book. We'll support very simple
         // cases; see the test suite for details.
-        if conditional_value.to_bool() {
+        if condition {
             // Evaluate the "then" clause.
             self.evaluate_statement_or_goto_line_number()?;
             if self.program().peek_next_token() == Some(Token::Else) {
@@ -115,18 +199,26 @@ impl<'a> StatementEvaluator<'a> {
         match lvalue.array_index {
             Some(index) => {
                 self.interpreter
-                    .maybe_log_warning_about_undeclared_array_use(&lvalue.symbol_name);
+                    .check_undeclared_array_use(&lvalue.symbol_name)?;
                 self.interpreter
                     .arrays
-                    .set_value_at_index(&lvalue.symbol_name, &index, rvalue)
+                    .set_value_at_index(&lvalue.symbol_name, &index, rvalue)?;
+                self.interpreter.log_array_change(lvalue.symbol_name);
+            }
+            None => {
+                self.interpreter
+                    .variables
+                    .set(lvalue.symbol_name.clone(), rvalue)?;
+                self.interpreter.log_variable_change(lvalue.symbol_name);
             }
-            None => self.interpreter.variables.set(lvalue.symbol_name, rvalue),
         }
+        Ok(())
     }
 
     fn evaluate_let_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(symbol_name)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(symbol_name)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         self.evaluate_assignment_statement(symbol_name)?;
         Ok(())
@@ -136,6 +228,10 @@ impl<'a> StatementEvaluator<'a> {
         &mut self,
         symbol_name: Symbol,
     ) -> Result<(), TracedInterpreterError> {
+        if symbol_name.as_str() == MID_ASSIGNMENT_TARGET {
+            return self.evaluate_mid_assignment_statement();
+        }
+
         let lvalue = LValue {
             symbol_name,
             array_index: self.parse_optional_array_index()?,
@@ -153,9 +249,62 @@ impl<'a> StatementEvaluator<'a> {
         Ok(())
     }
 
+    /// Applesoft BASIC doesn't support this, but Microsoft BASIC allows
+    /// `MID$(A$, start, length) = B$` to overwrite part of a string variable
+    /// with the beginning of another string. The length is optional, and the
+    /// string variable's length never changes.
+    fn evaluate_mid_assignment_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        self.program().expect_next_token(Token::LeftParen)?;
+        let lvalue = self.parse_lvalue()?;
+        self.program().expect_next_token(Token::Comma)?;
+        let start: f64 = self.evaluate_expression()?.try_into()?;
+        let max_length: Option<f64> = if self.program().accept_next_token(Token::Comma) {
+            Some(self.evaluate_expression()?.try_into()?)
+        } else {
+            None
+        };
+        self.program().expect_next_token(Token::RightParen)?;
+        self.program().expect_next_token(Token::Equals)?;
+        let replacement: Rc<String> = self.evaluate_expression()?.try_into()?;
+        let original: Rc<String> = self.get_lvalue_value(&lvalue)?.try_into()?;
+
+        let mut chars = original.chars().collect::<Vec<_>>();
+        if !start.is_finite()
+            || start < 1.0
+            || start as usize > chars.len()
+            || max_length.is_some_and(|len| !len.is_finite() || len < 0.0)
+        {
+            return Err(InterpreterError::IllegalQuantity.into());
+        }
+        let start = start as usize - 1;
+        let mut length = (chars.len() - start).min(replacement.chars().count());
+        if let Some(max_length) = max_length {
+            length = length.min(max_length as usize);
+        }
+        for (i, char) in replacement.chars().take(length).enumerate() {
+            chars[start + i] = char;
+        }
+        let string = chars.into_iter().collect::<String>();
+        self.assign_value(lvalue, string.into())
+    }
+
+    fn get_lvalue_value(&mut self, lvalue: &LValue) -> Result<Value, TracedInterpreterError> {
+        match &lvalue.array_index {
+            Some(index) => {
+                self.interpreter
+                    .check_undeclared_array_use(&lvalue.symbol_name)?;
+                self.interpreter
+                    .arrays
+                    .get_value_at_index(&lvalue.symbol_name, index)
+            }
+            None => Ok(self.interpreter.variables.get(&lvalue.symbol_name)),
+        }
+    }
+
     fn parse_lvalue(&mut self) -> Result<LValue, TracedInterpreterError> {
-        let Some(Token::Symbol(symbol_name)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(symbol_name)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         let array_index = self.parse_optional_array_index()?;
         Ok(LValue {
@@ -181,31 +330,92 @@ impl<'a> StatementEvaluator<'a> {
 
     fn evaluate_input_statement(&mut self) -> Result<(), TracedInterpreterError> {
         if let Some((data, has_leftover_input)) = self.interpreter.take_input() {
-            // TODO: Support multiple comma-separated items.
-            let lvalue = self.parse_lvalue()?;
-            // We're guaranteed to have at least one item in here, even if the input was an empty string.
-            let first_element = &data[0];
-            let has_excess_data = data.len() > 1 || has_leftover_input;
-            match Value::coerce_from_data_element(&lvalue.symbol_name, first_element) {
-                Ok(value) => {
-                    self.assign_value(lvalue, value)?;
-                    if has_excess_data {
-                        self.interpreter.output(InterpreterOutput::ExtraIgnored);
+            // Any items before this were filled by previous lines of input.
+            let first_item = self.interpreter.input_items_assigned;
+            let mut first_symbol_name = None;
+            // We're guaranteed to have at least one item in the data, even if the
+            // input was an empty string.
+            let mut elements = data.iter();
+            let mut item = 0;
+            loop {
+                let lvalue = self.parse_lvalue()?;
+                let symbol_name =
+                    first_symbol_name.get_or_insert_with(|| lvalue.symbol_name.clone());
+                if item >= first_item {
+                    let Some(element) = elements.next() else {
+                        // Like Applesoft BASIC, ask for the rest of the items with
+                        // a double question mark.
+                        self.interpreter.input_items_assigned = item;
+                        self.interpreter.print("?".to_string());
+                        self.interpreter
+                            .rewind_program_and_await_input(InputTarget::for_variable(
+                                &lvalue.symbol_name,
+                            ));
+                        return Ok(());
+                    };
+                    match Value::coerce_from_data_element(&lvalue.symbol_name, element) {
+                        Ok(value) => self.assign_value(lvalue, value)?,
+                        Err(TracedInterpreterError {
+                            error: InterpreterError::DataTypeMismatch,
+                            ..
+                        }) => {
+                            // The user needs to re-enter all the items, not just
+                            // the ones on this line.
+                            self.interpreter.input_items_assigned = 0;
+                            self.interpreter.output(InterpreterOutput::Reenter);
+                            self.interpreter.rewind_program_and_await_input(
+                                InputTarget::for_variable(symbol_name),
+                            );
+                            return Ok(());
+                        }
+                        Err(err) => return Err(err),
                     }
-                    Ok(())
                 }
-                Err(TracedInterpreterError {
-                    error: InterpreterError::DataTypeMismatch,
-                    ..
-                }) => {
-                    self.interpreter.output(InterpreterOutput::Reenter);
-                    self.interpreter.rewind_program_and_await_input();
-                    Ok(())
+                item += 1;
+                if !self.program().accept_next_token(Token::Comma) {
+                    break;
                 }
-                Err(err) => Err(err),
             }
+            self.interpreter.input_items_assigned = 0;
+            if elements.next().is_some() || has_leftover_input {
+                self.interpreter.output(InterpreterOutput::ExtraIgnored);
+            }
+            Ok(())
         } else {
-            self.interpreter.rewind_program_and_await_input();
+            let token = self.program().peek_next_token();
+            let Some(Token::Symbol(symbol_name)) = token else {
+                return Err(SyntaxError::UnexpectedToken(token).into());
+            };
+            self.interpreter.input_items_assigned = 0;
+            self.interpreter
+                .rewind_program_and_await_input(InputTarget::for_variable(&symbol_name));
+            Ok(())
+        }
+    }
+
+    /// Reads a single keystroke into a variable. Only the first character of
+    /// the provided input is used, and an empty input is treated as the
+    /// return key, like Applesoft BASIC. Numeric variables require a digit.
+    fn evaluate_get_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        if let Some(input) = self.interpreter.take_raw_input() {
+            let lvalue = self.parse_lvalue()?;
+            let char = input.chars().next().unwrap_or('\r');
+            let value: Value = if lvalue.symbol_name.as_str().ends_with('$') {
+                String::from(char).into()
+            } else {
+                let Some(digit) = char.to_digit(10) else {
+                    return Err(InterpreterError::TypeMismatch.into());
+                };
+                (digit as f64).into()
+            };
+            self.assign_value(lvalue, value)
+        } else {
+            let token = self.program().peek_next_token();
+            let Some(Token::Symbol(symbol_name)) = token else {
+                return Err(SyntaxError::UnexpectedToken(token).into());
+            };
+            self.interpreter
+                .rewind_program_and_await_input(InputTarget::keystroke_for_variable(&symbol_name));
             Ok(())
         }
     }
@@ -224,12 +434,74 @@ impl<'a> StatementEvaluator<'a> {
         };
         self.interpreter
             .arrays
-            .create(lvalue.symbol_name, max_indices)
+            .create(lvalue.symbol_name.clone(), max_indices)?;
+        self.interpreter.log_array_change(lvalue.symbol_name);
+        Ok(())
+    }
+
+    /// Evaluates the `AT row, column` part of `PRINT AT`. Coordinates start
+    /// at one and have the same limits as Applesoft's `VTAB` and `HTAB`.
+    fn evaluate_print_at(&mut self) -> Result<(), TracedInterpreterError> {
+        let row: f64 = self.evaluate_expression()?.try_into()?;
+        self.program().expect_next_token(Token::Comma)?;
+        let column: f64 = self.evaluate_expression()?.try_into()?;
+        if !(1.0..=SCREEN_ROWS).contains(&row) || !(1.0..=SCREEN_COLUMNS).contains(&column) {
+            return Err(InterpreterError::IllegalQuantity.into());
+        }
+        self.interpreter.output(InterpreterOutput::MoveCursor {
+            row: row as usize - 1,
+            column: column as usize - 1,
+        });
+        Ok(())
+    }
+
+    /// Evaluates the argument to `TAB` or `SPC`, which are only valid in
+    /// `PRINT` statements. Note that `TAB` to a column behind the cursor
+    /// does nothing. Like Applesoft, the argument must be between 0 and 255.
+    fn evaluate_print_tab_or_spc(&mut self, is_tab: bool) -> Result<(), TracedInterpreterError> {
+        if !self.program().accept_next_token(Token::LeftParen) {
+            return Err(SyntaxError::InvalidTabOrSpcArgument.into());
+        }
+        let Value::Number(value) = self.evaluate_expression()? else {
+            return Err(SyntaxError::InvalidTabOrSpcArgument.into());
+        };
+        if !self.program().accept_next_token(Token::RightParen) {
+            return Err(SyntaxError::InvalidTabOrSpcArgument.into());
+        }
+        if !(0.0..=255.0).contains(&value) {
+            return Err(InterpreterError::IllegalQuantity.into());
+        }
+        let amount = value as usize;
+        let spaces = if is_tab {
+            // TAB columns start at 1, while ours start at 0.
+            amount
+                .saturating_sub(1)
+                .saturating_sub(self.interpreter.get_column())
+        } else {
+            amount
+        };
+        if spaces > 0 {
+            self.interpreter.print_program_output(" ".repeat(spaces))?;
+        }
+        Ok(())
     }
 
     fn evaluate_print_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let mut ends_with_semicolon = false;
-        let mut strings: Vec<String> = vec![];
+        if self.program().accept_next_token(Token::At) {
+            self.evaluate_print_at()?;
+        }
+        // The items are buffered so the whole statement is emitted as a single
+        // piece of output, while still keeping track of the current column so
+        // that e.g. `POS` sees the effects of earlier items.
+        self.interpreter.start_print_buffer();
+        let result = self.evaluate_print_items();
+        // If an item raised an error, anything printed before it is still shown.
+        self.interpreter.flush_print_buffer();
+        result
+    }
+
+    fn evaluate_print_items(&mut self) -> Result<(), TracedInterpreterError> {
+        let mut suppress_newline = false;
         while let Some(token) = self.program().peek_next_token() {
             match token {
                 Token::Colon | Token::Else => break,
@@ -237,34 +509,85 @@ impl<'a> StatementEvaluator<'a> {
                     // Semicolons in Applesoft BASIC are very weird, they can be interspersed
                     // throughout a PRINT statement and appear to do nothing, unless they're at
                     // the end, in which case there won't be a newline at the end of the output.
-                    ends_with_semicolon = true;
+                    suppress_newline = true;
                     self.program().next_token().unwrap();
                 }
                 Token::Comma => {
-                    ends_with_semicolon = false;
-                    strings.push("\t".to_string());
+                    // Like semicolons, a comma at the end also means there won't be a
+                    // newline, so the next PRINT continues at the next tab stop.
+                    suppress_newline = true;
+                    self.interpreter.print_program_output("\t".to_string())?;
                     self.program().next_token().unwrap();
                 }
+                Token::Symbol(symbol)
+                    if symbol.as_str() == PRINT_TAB || symbol.as_str() == PRINT_SPC =>
+                {
+                    suppress_newline = false;
+                    self.program().next_token().unwrap();
+                    self.evaluate_print_tab_or_spc(symbol.as_str() == PRINT_TAB)?;
+                }
+                Token::Symbol(symbol) if is_unparenthesized_print_tab_or_spc(symbol.as_str()) => {
+                    return Err(SyntaxError::InvalidTabOrSpcArgument.into());
+                }
                 _ => {
-                    ends_with_semicolon = false;
+                    suppress_newline = false;
                     match self.evaluate_expression()? {
                         Value::String(string) => {
-                            strings.push(string.to_string());
+                            self.interpreter.print_program_output(string.to_string())?;
                         }
                         Value::Number(number) => {
-                            strings.push(format!("{}", number));
+                            // Negative numbers already start with a minus sign,
+                            // which takes the place of the space.
+                            let sign = if self.interpreter.enable_number_sign_spacing
+                                && !number.is_sign_negative()
+                            {
+                                " "
+                            } else {
+                                ""
+                            };
+                            self.interpreter
+                                .print_program_output(format!("{}{}", sign, number))?;
                         }
                     }
                 }
             }
         }
-        if !ends_with_semicolon {
-            strings.push(String::from("\n"));
+        if !suppress_newline {
+            self.interpreter.print_program_output(String::from("\n"))?;
         }
-        self.interpreter.print(strings.join(""));
         Ok(())
     }
 
+    fn evaluate_color_statement(
+        &mut self,
+        mode: GraphicsMode,
+    ) -> Result<(), TracedInterpreterError> {
+        self.program().expect_next_token(Token::Equals)?;
+        let color: f64 = self.evaluate_expression()?.try_into()?;
+        self.interpreter.graphics.set_color(mode, color)
+    }
+
+    /// We don't emulate any machine language routines, but we do support
+    /// the one that Applesoft programs use to clear the high-resolution
+    /// screen to the current color.
+    fn evaluate_call_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        let address: f64 = self.evaluate_expression()?.try_into()?;
+        if normalize_address(address)? != CLEAR_HIGH_RES_TO_HCOLOR_ADDRESS {
+            return Err(InterpreterError::Unimplemented.into());
+        }
+        let color = self.interpreter.graphics.color(GraphicsMode::HighRes);
+        self.interpreter
+            .output(InterpreterOutput::FillScreen(GraphicsMode::HighRes, color));
+        Ok(())
+    }
+
+    fn evaluate_poke_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        let address: f64 = self.evaluate_expression()?.try_into()?;
+        self.program().expect_next_token(Token::Comma)?;
+        let value: f64 = self.evaluate_expression()?.try_into()?;
+        self.interpreter.poke(address, value)
+    }
+
     fn evaluate_goto_statement(&mut self) -> Result<(), TracedInterpreterError> {
         let Some(Token::NumericLiteral(line_number)) = self.program().next_token() else {
             return Err(InterpreterError::UndefinedStatement.into());
@@ -273,6 +596,15 @@ impl<'a> StatementEvaluator<'a> {
         Ok(())
     }
 
+    fn evaluate_onerr_statement(&mut self) -> Result<(), TracedInterpreterError> {
+        self.program().expect_next_token(Token::Goto)?;
+        let Some(Token::NumericLiteral(line_number)) = self.program().next_token() else {
+            return Err(InterpreterError::UndefinedStatement.into());
+        };
+        self.program().set_error_handler(line_number as u64);
+        Ok(())
+    }
+
     fn evaluate_gosub_statement(&mut self) -> Result<(), TracedInterpreterError> {
         let Some(Token::NumericLiteral(line_number)) = self.program().next_token() else {
             return Err(InterpreterError::UndefinedStatement.into());
@@ -282,8 +614,9 @@ impl<'a> StatementEvaluator<'a> {
     }
 
     fn evaluate_for_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(symbol)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(symbol)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         self.program().expect_next_token(Token::Equals)?;
         let from_value = self.evaluate_expression()?;
@@ -305,28 +638,55 @@ impl<'a> StatementEvaluator<'a> {
             to_number,
             step_number,
         )?;
+        self.interpreter.log_variable_change(symbol);
         Ok(())
     }
 
+    /// Applesoft BASIC allows NEXT to be used without a variable name,
+    /// in which case it ends the innermost loop. It also allows multiple
+    /// comma-separated variable names, e.g. `NEXT J, I`, which ends each
+    /// of the loops in turn.
     fn evaluate_next_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(symbol)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let mut symbol = match self.program().peek_next_token() {
+            Some(Token::Symbol(symbol)) => {
+                self.program().next_token();
+                Some(symbol)
+            }
+            _ => None,
         };
-        self.interpreter
-            .program
-            .end_loop(&mut self.interpreter.variables, symbol)
+        loop {
+            let is_bare = symbol.is_none();
+            let (ended_symbol, continue_loop) = self
+                .interpreter
+                .program
+                .end_loop(&mut self.interpreter.variables, symbol)?;
+            self.interpreter.log_variable_change(ended_symbol);
+            // If the loop is continuing, we've jumped back to its beginning,
+            // so any remaining variables shouldn't be processed.
+            if continue_loop || is_bare || !self.program().accept_next_token(Token::Comma) {
+                return Ok(());
+            }
+            // A comma must be followed by another loop variable.
+            let token = self.program().next_token();
+            let Some(Token::Symbol(next_symbol)) = token else {
+                return Err(SyntaxError::UnexpectedToken(token).into());
+            };
+            symbol = Some(next_symbol);
+        }
     }
 
     fn evaluate_def_statement(&mut self) -> Result<(), TracedInterpreterError> {
-        let Some(Token::Symbol(function_name)) = self.program().next_token() else {
-            return Err(SyntaxError::UnexpectedToken.into());
+        let token = self.program().next_token();
+        let Some(Token::Symbol(function_name)) = token else {
+            return Err(SyntaxError::UnexpectedToken(token).into());
         };
         self.program().expect_next_token(Token::LeftParen)?;
         let mut arg_names: Vec<Symbol> = vec![];
         loop {
             // Note that in Applesoft BASIC, all functions must have at least one argument.
-            let Some(Token::Symbol(arg_name)) = self.program().next_token() else {
-                return Err(SyntaxError::UnexpectedToken.into());
+            let token = self.program().next_token();
+            let Some(Token::Symbol(arg_name)) = token else {
+                return Err(SyntaxError::UnexpectedToken(token).into());
             };
             arg_names.push(arg_name);
             match self.program().next_token() {
@@ -334,7 +694,7 @@ impl<'a> StatementEvaluator<'a> {
                     // Keep looping to parse additional arguments.
                 }
                 Some(Token::RightParen) => break,
-                _ => return Err(SyntaxError::UnexpectedToken.into()),
+                token => return Err(SyntaxError::UnexpectedToken(token).into()),
             }
         }
         self.program().expect_next_token(Token::Equals)?;
@@ -344,11 +704,8 @@ impl<'a> StatementEvaluator<'a> {
         // is actually called. but stop if we encounter a colon, since we'll want
         // to evaluate any additional statements immediately.
         while let Some(token) = self.program().next_token() {
-            match token {
-                Token::Colon => {
-                    break;
-                }
-                _ => {}
+            if token == Token::Colon {
+                break;
             }
         }
 
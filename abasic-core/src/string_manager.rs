@@ -18,6 +18,7 @@ pub struct StringManager {
 }
 
 impl StringManager {
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_str<T: AsRef<str>>(&mut self, value: T) -> Rc<String> {
         // It's annoying that we're doing this O(n) but it doesn't seem like
         // we can get the item with just a &str, which is too bad.
@@ -29,6 +30,7 @@ impl StringManager {
         self.add(value.as_ref().to_string())
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_string(&mut self, value: String) -> Rc<String> {
         if let Some(string) = self.strings.get(&value) {
             string.clone()
@@ -33,8 +33,8 @@ impl Debug for Symbol {
     }
 }
 
-impl Into<Symbol> for Rc<String> {
-    fn into(self) -> Symbol {
-        Symbol(self)
+impl From<Rc<String>> for Symbol {
+    fn from(val: Rc<String>) -> Self {
+        Symbol(val)
     }
 }
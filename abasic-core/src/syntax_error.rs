@@ -10,6 +10,9 @@ pub enum TokenizationError {
     UnterminatedStringLiteral(usize),
     /// The argument is the span (as string indices) that represents an invalid number.
     InvalidNumber(Range<usize>),
+    /// The argument is the span (as string indices) of a number that's too
+    /// large to represent.
+    NumberOverflow(Range<usize>),
 }
 
 impl TokenizationError {
@@ -18,6 +21,7 @@ impl TokenizationError {
             TokenizationError::IllegalCharacter(i) => *i..*i + 1,
             TokenizationError::UnterminatedStringLiteral(i) => *i..string_length,
             TokenizationError::InvalidNumber(range) => range.clone(),
+            TokenizationError::NumberOverflow(range) => range.clone(),
         }
     }
 }
@@ -28,6 +32,7 @@ impl Display for TokenizationError {
             TokenizationError::IllegalCharacter(_) => write!(f, "ILLEGAL CHARACTER"),
             TokenizationError::UnterminatedStringLiteral(_) => write!(f, "UNTERMINATED STRING"),
             TokenizationError::InvalidNumber(_) => write!(f, "INVALID NUMBER"),
+            TokenizationError::NumberOverflow(_) => write!(f, "OVERFLOW"),
         }
     }
 }
@@ -41,9 +46,26 @@ impl From<TokenizationError> for SyntaxError {
 #[derive(Debug, PartialEq)]
 pub enum SyntaxError {
     Tokenization(TokenizationError),
-    UnexpectedToken,
+    /// The argument is the token that wasn't expected, or `None` if it
+    /// wasn't a token at all (e.g. an invalid argument to a command).
+    UnexpectedToken(Option<Token>),
     ExpectedToken(Token),
     UnexpectedEndOfInput,
+    LineNumberTooLarge,
+    /// Programs can only contain numbered lines, so this is raised when
+    /// loading a program that has a line without a number.
+    MissingLineNumber,
+    LineTooLong,
+    /// `INPUT` and `GET` wait for input by rewinding to the beginning of
+    /// their statement and re-running it once the input has been provided,
+    /// so they can only be used as statements. Using them in an expression,
+    /// e.g. `A = INPUT`, raises this rather than corrupting our position in
+    /// the program.
+    InputInExpression,
+    /// `TAB` and `SPC` must be followed by a parenthesized number. Raising
+    /// this, rather than a generic error, makes it clear that the problem
+    /// is with their argument.
+    InvalidTabOrSpcArgument,
 }
 
 impl Error for SyntaxError {}
@@ -53,9 +75,17 @@ impl Display for SyntaxError {
         write!(f, "SYNTAX ERROR (")?;
         match self {
             SyntaxError::Tokenization(t) => write!(f, "{t})"),
-            SyntaxError::UnexpectedToken => write!(f, "UNEXPECTED TOKEN)"),
+            SyntaxError::UnexpectedToken(Some(tok)) => write!(f, "UNEXPECTED TOKEN '{tok}')"),
+            SyntaxError::UnexpectedToken(None) => write!(f, "UNEXPECTED TOKEN)"),
             SyntaxError::ExpectedToken(tok) => write!(f, "EXPECTED TOKEN '{tok}')"),
             SyntaxError::UnexpectedEndOfInput => write!(f, "UNEXPECTED END OF INPUT)"),
+            SyntaxError::LineNumberTooLarge => write!(f, "LINE NUMBER TOO LARGE)"),
+            SyntaxError::MissingLineNumber => write!(f, "MISSING LINE NUMBER)"),
+            SyntaxError::LineTooLong => write!(f, "LINE TOO LONG)"),
+            SyntaxError::InputInExpression => write!(f, "INPUT OR GET IN EXPRESSION)"),
+            SyntaxError::InvalidTabOrSpcArgument => {
+                write!(f, "TAB OR SPC NEEDS A NUMBER IN PARENTHESES)")
+            }
         }
     }
 }
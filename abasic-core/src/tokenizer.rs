@@ -51,6 +51,21 @@ pub enum Token {
     Read,
     Restore,
     Def,
+    Gr,
+    Hgr,
+    Color,
+    Hcolor,
+    Call,
+    Poke,
+    At,
+    Get,
+    Onerr,
+    Resume,
+    Iif,
+    While,
+    Wend,
+    Pop,
+    Clear,
     Remark(Rc<String>),
     Symbol(Symbol),
     StringLiteral(Rc<String>),
@@ -100,6 +115,21 @@ impl Display for Token {
             Token::Read => write!(f, "READ"),
             Token::Restore => write!(f, "RESTORE"),
             Token::Def => write!(f, "DEF"),
+            Token::Gr => write!(f, "GR"),
+            Token::Hgr => write!(f, "HGR"),
+            Token::Color => write!(f, "COLOR"),
+            Token::Hcolor => write!(f, "HCOLOR"),
+            Token::Call => write!(f, "CALL"),
+            Token::Poke => write!(f, "POKE"),
+            Token::At => write!(f, "AT"),
+            Token::Get => write!(f, "GET"),
+            Token::Onerr => write!(f, "ONERR"),
+            Token::Resume => write!(f, "RESUME"),
+            Token::Iif => write!(f, "IIF"),
+            Token::While => write!(f, "WHILE"),
+            Token::Wend => write!(f, "WEND"),
+            Token::Pop => write!(f, "POP"),
+            Token::Clear => write!(f, "CLEAR"),
             Token::Remark(comment) => write!(f, "REM{}", comment),
             Token::Symbol(name) => write!(f, "{}", name),
             Token::StringLiteral(string) => write!(f, "\"{}\"", string),
@@ -113,6 +143,12 @@ pub struct Tokenizer<'a, T: AsRef<str>> {
     string: T,
     index: usize,
     errored: bool,
+    lenient_operators: bool,
+    at_keyword: bool,
+    iif_keyword: bool,
+    while_keywords: bool,
+    graphics_keywords: bool,
+    applesoft_keywords: bool,
     string_manager: &'a mut StringManager,
 }
 
@@ -122,6 +158,12 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
             string,
             index: 0,
             errored: false,
+            lenient_operators: false,
+            at_keyword: false,
+            iif_keyword: false,
+            while_keywords: false,
+            graphics_keywords: false,
+            applesoft_keywords: false,
             string_manager,
         }
     }
@@ -134,7 +176,7 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
         &self.bytes()[self.index..]
     }
 
-    fn crunch_remaining_bytes(&self) -> LineCruncher {
+    fn crunch_remaining_bytes(&self) -> LineCruncher<'_> {
         LineCruncher::new(self.remaining_bytes())
     }
 
@@ -183,6 +225,19 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
                     if next_char == b'=' {
                         self.index += pos;
                         return Some(Ok(Token::GreaterThanOrEqualTo));
+                    } else if next_char == b'<' && self.lenient_operators {
+                        self.index += pos;
+                        return Some(Ok(Token::NotEquals));
+                    }
+                }
+            } else if token == Token::Equals && self.lenient_operators {
+                if let Some((next_char, pos)) = self.crunch_remaining_bytes().next() {
+                    if next_char == b'>' {
+                        self.index += pos;
+                        return Some(Ok(Token::GreaterThanOrEqualTo));
+                    } else if next_char == b'<' {
+                        self.index += pos;
+                        return Some(Ok(Token::LessThanOrEqualTo));
                     }
                 }
             }
@@ -249,23 +304,53 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
     fn chomp_number(&mut self) -> Option<Result<Token, TokenizationError>> {
         let mut digits = String::new();
         let mut latest_pos: Option<usize> = None;
+        let mut bytes = self.crunch_remaining_bytes().peekable();
 
-        for (byte, pos) in self.crunch_remaining_bytes() {
-            // TODO: We don't currently support scientific notation like ".1e10".
-
+        while let Some(&(byte, pos)) = bytes.peek() {
             // Note that we're not concerned with whether the decimal is in
             // the right place, we'll deal with that later when we parse the
             // final number.
             if byte.is_ascii_digit() || byte == b'.' {
                 latest_pos = Some(pos);
                 digits.push(byte as char);
+                bytes.next();
             } else {
                 break;
             }
         }
 
+        // Handle scientific notation like `6.022E23` or `1E-3`. If the `E`
+        // isn't followed by an exponent, we'll leave it alone so it can
+        // become a symbol.
+        if latest_pos.is_some() {
+            if let Some((b'E' | b'e', _)) = bytes.next() {
+                let mut exponent = String::from("e");
+                if let Some(&(sign @ (b'+' | b'-'), _)) = bytes.peek() {
+                    exponent.push(sign as char);
+                    bytes.next();
+                }
+                let mut exponent_pos: Option<usize> = None;
+                for (byte, pos) in bytes {
+                    if !byte.is_ascii_digit() {
+                        break;
+                    }
+                    exponent_pos = Some(pos);
+                    exponent.push(byte as char);
+                }
+                if exponent_pos.is_some() {
+                    latest_pos = exponent_pos;
+                    digits.push_str(&exponent);
+                }
+            }
+        }
+
         if let Some(pos) = latest_pos {
             if let Ok(number) = digits.parse::<f64>() {
+                if !number.is_finite() {
+                    return Some(Err(TokenizationError::NumberOverflow(
+                        self.index..self.index + pos,
+                    )));
+                }
                 self.index += pos;
                 Some(Ok(Token::NumericLiteral(number)))
             } else {
@@ -353,6 +438,12 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
             Some(Token::Gosub)
         } else if self.chomp_keyword("RETURN") {
             Some(Token::Return)
+        } else if self.iif_keyword && self.chomp_keyword("IIF") {
+            Some(Token::Iif)
+        } else if self.while_keywords && self.chomp_keyword("WHILE") {
+            Some(Token::While)
+        } else if self.while_keywords && self.chomp_keyword("WEND") {
+            Some(Token::Wend)
         } else if self.chomp_keyword("IF") {
             Some(Token::If)
         } else if self.chomp_keyword("THEN") {
@@ -383,6 +474,30 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
             Some(Token::Restore)
         } else if self.chomp_keyword("DEF") {
             Some(Token::Def)
+        } else if self.graphics_keywords && self.chomp_keyword("GR") {
+            Some(Token::Gr)
+        } else if self.graphics_keywords && self.chomp_keyword("HGR") {
+            Some(Token::Hgr)
+        } else if self.graphics_keywords && self.chomp_keyword("COLOR") {
+            Some(Token::Color)
+        } else if self.graphics_keywords && self.chomp_keyword("HCOLOR") {
+            Some(Token::Hcolor)
+        } else if self.graphics_keywords && self.chomp_keyword("CALL") {
+            Some(Token::Call)
+        } else if self.applesoft_keywords && self.chomp_keyword("POKE") {
+            Some(Token::Poke)
+        } else if self.applesoft_keywords && self.chomp_keyword("POP") {
+            Some(Token::Pop)
+        } else if self.applesoft_keywords && self.chomp_keyword("CLEAR") {
+            Some(Token::Clear)
+        } else if self.applesoft_keywords && self.chomp_keyword("GET") {
+            Some(Token::Get)
+        } else if self.applesoft_keywords && self.chomp_keyword("ONERR") {
+            Some(Token::Onerr)
+        } else if self.applesoft_keywords && self.chomp_keyword("RESUME") {
+            Some(Token::Resume)
+        } else if self.at_keyword && self.chomp_at_keyword() {
+            Some(Token::At)
         } else {
             None
         }
@@ -409,6 +524,20 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
         }
     }
 
+    /// Like Applesoft, we won't treat `AT` as a keyword if it's followed by
+    /// `N`, so that it doesn't break the `ATN` function.
+    fn chomp_at_keyword(&mut self) -> bool {
+        let prev_index = self.index;
+        if !self.chomp_keyword("AT") {
+            return false;
+        }
+        if let Some((b'N' | b'n', _)) = self.crunch_remaining_bytes().next() {
+            self.index = prev_index;
+            return false;
+        }
+        true
+    }
+
     fn chomp_keyword(&mut self, keyword: &str) -> bool {
         let keyword_bytes = keyword.as_bytes();
         let mut keyword_idx = 0;
@@ -480,6 +609,55 @@ impl<'a, T: AsRef<str>> Tokenizer<'a, T> {
         self.index += bytes;
         self
     }
+
+    /// When enabled, `><`, `=>`, and `=<` are recognized as aliases for
+    /// `<>`, `>=`, and `<=`, as they are in some BASIC dialects.
+    pub fn lenient_operators(mut self, enabled: bool) -> Self {
+        self.lenient_operators = enabled;
+        self
+    }
+
+    /// When enabled, `AT` is recognized as a keyword, for `PRINT AT`. Note
+    /// that this changes the meaning of any symbol containing `AT`, e.g.
+    /// `CAT` will be tokenized as `C` followed by `AT`.
+    pub fn at_keyword(mut self, enabled: bool) -> Self {
+        self.at_keyword = enabled;
+        self
+    }
+
+    /// When enabled, `IIF` is recognized as a keyword, for the
+    /// `IIF(condition, a, b)` function. Otherwise it's tokenized as the
+    /// symbol `I` followed by `IF`.
+    pub fn iif_keyword(mut self, enabled: bool) -> Self {
+        self.iif_keyword = enabled;
+        self
+    }
+
+    /// When enabled, `WHILE` and `WEND` are recognized as keywords, for
+    /// `WHILE` loops. Otherwise e.g. `WEND` is tokenized as the symbol `W`
+    /// followed by `END`.
+    pub fn while_keywords(mut self, enabled: bool) -> Self {
+        self.while_keywords = enabled;
+        self
+    }
+
+    /// When enabled, `GR`, `HGR`, `COLOR`, `HCOLOR`, and `CALL` are
+    /// recognized as keywords. Otherwise e.g. `GRADE` is tokenized as the
+    /// symbol `GRADE` rather than `GR` followed by `ADE`.
+    pub fn graphics_keywords(mut self, enabled: bool) -> Self {
+        self.graphics_keywords = enabled;
+        self
+    }
+
+    /// When enabled, the keywords of Applesoft BASIC statements that
+    /// aren't supported by default, like `GET`, `POP`, and `ONERR`, are
+    /// recognized.
+    /// Otherwise e.g. `TARGET` is tokenized as the symbol `TARGET` rather
+    /// than `TAR` followed by `GET`.
+    pub fn applesoft_keywords(mut self, enabled: bool) -> Self {
+        self.applesoft_keywords = enabled;
+        self
+    }
 }
 
 impl<'a, T: AsRef<str>> Iterator for Tokenizer<'a, T> {
@@ -641,7 +819,7 @@ mod tests {
     #[test]
     fn roundtrip_of_misc_tokens_works() {
         assert_roundtrip_works(
-            r#"dim let print input goto gosub return :;,?()+-*/^=<><<=>>= and or not if then else end stop for to step next read restore def"#,
+            r#"dim let print input goto gosub return :;,?()+-*/^=<><<=>>= and or not if then else end stop for to step next read restore def get onerr resume"#,
         );
     }
 
@@ -653,6 +831,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parsing_scientific_notation_works() {
+        assert_values_parse_to_tokens(
+            &["6.022E23", "6.022e23", "6.022e+23", " 6.022 E 23 "],
+            &[Token::NumericLiteral(6.022e23)],
+        );
+        assert_values_parse_to_tokens(&["1E-3", "1e-3", ".001"], &[Token::NumericLiteral(0.001)]);
+        assert_values_parse_to_tokens(&["1.5e-3"], &[Token::NumericLiteral(0.0015)]);
+    }
+
+    #[test]
+    fn parsing_incomplete_scientific_notation_leaves_symbol() {
+        assert_values_parse_to_tokens(&["5E"], &[Token::NumericLiteral(5.0), symbol("E")]);
+        assert_values_parse_to_tokens(
+            &["5E-"],
+            &[Token::NumericLiteral(5.0), symbol("E"), Token::Minus],
+        );
+        assert_values_parse_to_tokens(&["5EX"], &[Token::NumericLiteral(5.0), symbol("EX")]);
+    }
+
     #[test]
     fn parsing_invalid_decimal_number_returns_error() {
         assert_value_parses_to_tokens_wrapped(
@@ -669,6 +867,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parsing_overflowing_number_returns_error() {
+        assert_value_parses_to_tokens_wrapped(
+            "1E999",
+            &[Err(TokenizationError::NumberOverflow(0..5))],
+        );
+    }
+
     #[test]
     fn parsing_empty_string_works() {
         assert_values_parse_to_tokens(&["", " ", "    "], &[]);
@@ -765,6 +971,168 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parsing_lenient_equality_operators_works() {
+        let get_lenient_tokens = |value: &str| {
+            let mut manager = StringManager::default();
+            Tokenizer::new(value, &mut manager)
+                .lenient_operators(true)
+                .remaining_tokens()
+                .unwrap()
+        };
+        for (value, token) in [
+            ("><", Token::NotEquals),
+            ("> <", Token::NotEquals),
+            ("=>", Token::GreaterThanOrEqualTo),
+            ("= >", Token::GreaterThanOrEqualTo),
+            ("=<", Token::LessThanOrEqualTo),
+            ("= <", Token::LessThanOrEqualTo),
+            ("<>", Token::NotEquals),
+            (">=", Token::GreaterThanOrEqualTo),
+            ("<=", Token::LessThanOrEqualTo),
+        ] {
+            assert_eq!(get_lenient_tokens(value), vec![token], "parsing '{value}'");
+        }
+    }
+
+    #[test]
+    fn parsing_iif_keyword_works() {
+        let mut manager = StringManager::default();
+        assert_eq!(
+            Tokenizer::new("iif(1,2,3)", &mut manager)
+                .iif_keyword(true)
+                .remaining_tokens()
+                .unwrap(),
+            vec![
+                Token::Iif,
+                Token::LeftParen,
+                Token::NumericLiteral(1.0),
+                Token::Comma,
+                Token::NumericLiteral(2.0),
+                Token::Comma,
+                Token::NumericLiteral(3.0),
+                Token::RightParen
+            ]
+        );
+        assert_eq!(get_tokens("iif"), vec![symbol("I"), Token::If]);
+    }
+
+    #[test]
+    fn parsing_while_keywords_works() {
+        let mut manager = StringManager::default();
+        assert_eq!(
+            Tokenizer::new("while x:wend", &mut manager)
+                .while_keywords(true)
+                .remaining_tokens()
+                .unwrap(),
+            vec![Token::While, symbol("X"), Token::Colon, Token::Wend]
+        );
+        assert_eq!(get_tokens("wend"), vec![symbol("W"), Token::End]);
+    }
+
+    #[test]
+    fn parsing_graphics_keywords_works() {
+        let mut manager = StringManager::default();
+        assert_eq!(
+            Tokenizer::new("gr:hgr:color=1:hcolor=2:call 62454", &mut manager)
+                .graphics_keywords(true)
+                .remaining_tokens()
+                .unwrap(),
+            vec![
+                Token::Gr,
+                Token::Colon,
+                Token::Hgr,
+                Token::Colon,
+                Token::Color,
+                Token::Equals,
+                Token::NumericLiteral(1.0),
+                Token::Colon,
+                Token::Hcolor,
+                Token::Equals,
+                Token::NumericLiteral(2.0),
+                Token::Colon,
+                Token::Call,
+                Token::NumericLiteral(62454.0)
+            ]
+        );
+        assert_eq!(get_tokens("grade"), vec![symbol("GRADE")]);
+        assert_eq!(get_tokens("recall"), vec![symbol("RECALL")]);
+    }
+
+    #[test]
+    fn parsing_applesoft_keywords_works() {
+        let mut manager = StringManager::default();
+        assert_eq!(
+            Tokenizer::new(
+                "get a$:pop:clear:poke 1,2:onerr goto 5:resume",
+                &mut manager
+            )
+            .applesoft_keywords(true)
+            .remaining_tokens()
+            .unwrap(),
+            vec![
+                Token::Get,
+                symbol("A$"),
+                Token::Colon,
+                Token::Pop,
+                Token::Colon,
+                Token::Clear,
+                Token::Colon,
+                Token::Poke,
+                Token::NumericLiteral(1.0),
+                Token::Comma,
+                Token::NumericLiteral(2.0),
+                Token::Colon,
+                Token::Onerr,
+                Token::Goto,
+                Token::NumericLiteral(5.0),
+                Token::Colon,
+                Token::Resume
+            ]
+        );
+        assert_eq!(get_tokens("target"), vec![symbol("TARGET")]);
+        assert_eq!(get_tokens("pops"), vec![symbol("POPS")]);
+        assert_eq!(get_tokens("cleared"), vec![symbol("CLEARED")]);
+    }
+
+    #[test]
+    fn parsing_at_keyword_works() {
+        let get_tokens_with_at_keyword = |value: &str| {
+            let mut manager = StringManager::default();
+            Tokenizer::new(value, &mut manager)
+                .at_keyword(true)
+                .remaining_tokens()
+                .unwrap()
+        };
+        assert_eq!(
+            get_tokens_with_at_keyword("print at 1,2"),
+            vec![
+                Token::Print,
+                Token::At,
+                Token::NumericLiteral(1.0),
+                Token::Comma,
+                Token::NumericLiteral(2.0)
+            ]
+        );
+        assert_eq!(
+            get_tokens_with_at_keyword("atn(1)"),
+            vec![
+                symbol("ATN"),
+                Token::LeftParen,
+                Token::NumericLiteral(1.0),
+                Token::RightParen
+            ]
+        );
+        assert_eq!(get_tokens("print at 1"), vec![Token::Print, symbol("AT1")]);
+    }
+
+    #[test]
+    fn parsing_lenient_equality_operators_is_disabled_by_default() {
+        assert_values_parse_to_tokens(&["><"], &[Token::GreaterThan, Token::LessThan]);
+        assert_values_parse_to_tokens(&["=>"], &[Token::Equals, Token::GreaterThan]);
+        assert_values_parse_to_tokens(&["=<"], &[Token::Equals, Token::LessThan]);
+    }
+
     #[test]
     fn parsing_symbol_works() {
         assert_values_parse_to_tokens(&["x", " x", "  x  "], &[symbol("X")]);
@@ -781,6 +1149,28 @@ mod tests {
         assert_values_parse_to_tokens(&["x$u", " x $u", "  x$u  "], &[symbol("X$"), symbol("U")]);
     }
 
+    #[test]
+    fn parsing_crunched_builtin_names_works() {
+        assert_values_parse_to_tokens(
+            &["sqr(9)", "s q r(9)", "S Q R ( 9 )"],
+            &[
+                symbol("SQR"),
+                Token::LeftParen,
+                Token::NumericLiteral(9.0),
+                Token::RightParen,
+            ],
+        );
+        assert_values_parse_to_tokens(
+            &["mid$(a$)", "m i d $ (a $)"],
+            &[
+                symbol("MID$"),
+                Token::LeftParen,
+                symbol("A$"),
+                Token::RightParen,
+            ],
+        );
+    }
+
     #[test]
     fn parsing_data_works() {
         use crate::data::test_util::{number, string};
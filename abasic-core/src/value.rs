@@ -37,6 +37,8 @@ impl TryFrom<Value> for Rc<String> {
 
 impl Value {
     // TODO: Should we use the `From` trait instead?  Or is this more explicit?
+    /// Numbers are true if they're non-zero, and strings are true if they're
+    /// non-empty.
     pub fn to_bool(&self) -> bool {
         match self {
             Value::String(string) => !string.is_empty(),
@@ -1,20 +1,37 @@
 use std::ops::Range;
 
 use abasic_core::{
-    DiagnosticMessage, InterpreterError, SourceFileAnalyzer, SourceFileMap, SyntaxError, TokenType,
+    AnalyzerOptions, DiagnosticMessage, Interpreter, InterpreterError, OutlineSymbol,
+    OutlineSymbolKind, ProgramMetrics, SourceFileAnalyzer, SourceFileMap, SyntaxError, Token,
+    TokenType, VariableSymbol,
 };
 
 fn analyze(program: &'static str) -> SourceFileAnalyzer {
+    analyze_with_options(program, AnalyzerOptions::default())
+}
+
+fn analyze_with_options(program: &'static str, options: AnalyzerOptions) -> SourceFileAnalyzer {
     let lines = program
         .split("\n")
         .map(|line| line.trim_start())
         .map(|s| s.to_owned());
-    SourceFileAnalyzer::analyze(lines.clone().collect::<Vec<_>>().join("\n"))
+    SourceFileAnalyzer::analyze_lines_with_options(lines.collect(), options)
+}
+
+fn applesoft_options() -> AnalyzerOptions {
+    AnalyzerOptions {
+        enable_applesoft_statements: true,
+        ..Default::default()
+    }
 }
 
 fn assert_program_is_fine(program: &'static str) {
-    let analyzer_messages = analyze(program).take_messages();
-    if analyzer_messages.len() != 0 {
+    assert_program_is_fine_with_options(program, AnalyzerOptions::default());
+}
+
+fn assert_program_is_fine_with_options(program: &'static str, options: AnalyzerOptions) {
+    let analyzer_messages = analyze_with_options(program, options).take_messages();
+    if !analyzer_messages.is_empty() {
         panic!("Expected analyzer for program {program} to be empty but got {analyzer_messages:?}");
     }
 }
@@ -22,6 +39,7 @@ fn assert_program_is_fine(program: &'static str) {
 #[derive(PartialEq, Debug)]
 enum MessageType {
     Warning,
+    Hint,
     Error,
 }
 
@@ -37,7 +55,7 @@ impl SourceMappedMessage {
     fn from_diagnostic(
         diagnostic: &DiagnosticMessage,
         map: &SourceFileMap,
-        lines: &Vec<String>,
+        lines: &[String],
     ) -> Self {
         let Some((line, range)) = map.map_to_source(diagnostic) else {
             panic!("Unable to source-map {diagnostic:?}");
@@ -50,9 +68,20 @@ impl SourceMappedMessage {
                 line: *line,
                 source_snippet,
             },
+            DiagnosticMessage::Hint(line, _, message) => SourceMappedMessage {
+                _type: MessageType::Hint,
+                message: message.clone(),
+                line: *line,
+                source_snippet,
+            },
             DiagnosticMessage::Error(line, err) => SourceMappedMessage {
                 _type: MessageType::Error,
-                message: err.to_string(),
+                // We don't use the error's Display implementation, since it
+                // includes a backtrace if RUST_BACKTRACE is set.
+                message: match err.line_number() {
+                    Some(line_number) => format!("{} IN {}", err.message(), line_number),
+                    None => err.message(),
+                },
                 line: *line,
                 source_snippet,
             },
@@ -78,11 +107,24 @@ fn assert_program_has_source_mapped_diagnostics(
     program: &'static str,
     expected_messages: Vec<SourceMappedMessage>,
 ) {
-    let mut analyzer = SourceFileAnalyzer::analyze_lines(
+    assert_program_has_source_mapped_diagnostics_with_options(
+        program,
+        AnalyzerOptions::default(),
+        expected_messages,
+    );
+}
+
+fn assert_program_has_source_mapped_diagnostics_with_options(
+    program: &'static str,
+    options: AnalyzerOptions,
+    expected_messages: Vec<SourceMappedMessage>,
+) {
+    let mut analyzer = SourceFileAnalyzer::analyze_lines_with_options(
         program
             .split('\n')
             .map(|s| s.to_owned())
             .collect::<Vec<_>>(),
+        options,
     );
     let messages = analyzer
         .take_messages()
@@ -99,11 +141,19 @@ fn assert_program_has_source_mapped_diagnostics(
 }
 
 fn assert_program_has_error(program: &'static str, error: InterpreterError) {
-    let analyzer_messages = analyze(program).take_messages();
+    assert_program_has_error_with_options(program, AnalyzerOptions::default(), error);
+}
+
+fn assert_program_has_error_with_options(
+    program: &'static str,
+    options: AnalyzerOptions,
+    error: InterpreterError,
+) {
+    let analyzer_messages = analyze_with_options(program, options).take_messages();
     if analyzer_messages.len() != 1 {
         panic!("Expected analyzer for program {program} to have one element but got {analyzer_messages:?}");
     }
-    let message = analyzer_messages.get(0).unwrap();
+    let message = analyzer_messages.first().unwrap();
     match message {
         abasic_core::DiagnosticMessage::Error(_line, err) => {
             assert_eq!(err.error, error);
@@ -125,16 +175,138 @@ fn assert_program_token_types(
 fn print_works() {
     assert_program_is_fine("10 print \"hi\"");
     assert_program_is_fine("10 a$ = \"hello\":print a$");
+    assert_program_is_fine("10 print tab(5);\"x\";spc(2);\"y\"");
+    assert_program_has_error(
+        "10 print tab(\"x\")",
+        InterpreterError::Syntax(SyntaxError::InvalidTabOrSpcArgument),
+    );
+    assert_program_has_error(
+        "10 print tab 5",
+        InterpreterError::Syntax(SyntaxError::InvalidTabOrSpcArgument),
+    );
+}
+
+#[test]
+fn input_works() {
+    assert_program_is_fine("10 input a$: print a$");
+    assert_program_is_fine("10 input a, b$, c(1): print a; b$; c(1)");
+    assert_program_has_error("10 a = input", SyntaxError::InputInExpression.into());
 }
 
 #[test]
 fn builtins_work() {
     assert_program_is_fine("10 print rnd(1)");
+    assert_program_is_fine("10 print peek(49152)");
+    assert_program_is_fine("10 print sqr(9) + sin(0) + cos(0) + tan(0) + atn(0) + log(1) + exp(0)");
+}
+
+#[test]
+fn string_concatenation_works() {
+    assert_program_is_fine("10 a$ = \"foo\" + \"bar\": print a$ + \"!\"");
+    assert_program_has_error("10 print \"x\" + 1", InterpreterError::TypeMismatch);
+    assert_program_has_error("10 print 1 + \"x\"", InterpreterError::TypeMismatch);
+    assert_program_has_error("10 print \"x\" - \"y\"", InterpreterError::TypeMismatch);
+}
+
+#[test]
+fn string_builtins_work() {
+    assert_program_is_fine(
+        "10 a$ = \"hello\": print len(a$);left$(a$, 1);right$(a$, 1);mid$(a$, 2);mid$(a$, 2, 3)",
+    );
+    assert_program_has_error("10 print len(5)", InterpreterError::TypeMismatch);
+    assert_program_has_error("10 print asc(5)", InterpreterError::TypeMismatch);
+    assert_program_has_error(
+        "10 print left$(\"hi\", \"x\")",
+        InterpreterError::TypeMismatch,
+    );
+    assert_program_has_error(
+        "10 print mid$(\"hi\", 1) * 2",
+        InterpreterError::TypeMismatch,
+    );
+}
+
+#[test]
+fn mid_assignment_works() {
+    assert_program_is_fine("10 a$ = \"hello\": mid$(a$, 2, 2) = \"EL\": print a$");
+    assert_program_has_error(
+        "10 a$ = \"hello\": mid$(a$, 2) = 5: print a$",
+        InterpreterError::TypeMismatch,
+    );
+    assert_program_has_error(
+        "10 a = 1: mid$(a, 2) = \"x\": print a",
+        InterpreterError::TypeMismatch,
+    );
+}
+
+#[test]
+fn function_definitions_must_match_function_type() {
+    assert_program_has_error(
+        "10 def fna$(x) = 5\n20 print fna$(1)",
+        InterpreterError::TypeMismatch,
+    );
+    assert_program_has_error(
+        "10 def fna(x) = \"hi\"\n20 print fna(65)",
+        InterpreterError::TypeMismatch,
+    );
+}
+
+#[test]
+fn get_works() {
+    assert_program_is_fine_with_options("10 get a$: print a$", applesoft_options());
+    assert_program_is_fine_with_options("10 get a: print a", applesoft_options());
+    assert_program_is_fine("10 target = 2: print target");
+}
+
+#[test]
+fn onerr_goto_and_resume_work() {
+    assert_program_is_fine_with_options(
+        "10 onerr goto 20\n20 print \"error\": resume",
+        applesoft_options(),
+    );
+    assert_program_has_error_with_options(
+        "10 onerr goto 20",
+        applesoft_options(),
+        InterpreterError::UndefinedStatement,
+    );
+    assert_program_has_error_with_options(
+        "10 onerr 20\n20 print",
+        applesoft_options(),
+        InterpreterError::Syntax(SyntaxError::ExpectedToken(Token::Goto)),
+    );
+}
+
+#[test]
+fn graphics_statements_require_option() {
+    assert_program_is_fine("10 grade = 5: print grade");
+    let options = AnalyzerOptions {
+        enable_graphics: true,
+        ..Default::default()
+    };
+    assert_program_has_source_mapped_diagnostics_with_options(
+        "10 gr: hcolor=3: hgr: call 62454",
+        options,
+        vec![],
+    );
+    let analyzer =
+        SourceFileAnalyzer::analyze_lines_with_options(vec!["10 gr".to_string()], options);
+    assert!(analyzer.into_interpreter().enable_graphics);
+}
+
+#[test]
+fn poke_works() {
+    assert_program_is_fine_with_options("10 poke 49168, 0", applesoft_options());
+    assert_program_has_error_with_options(
+        "10 poke 49168, \"hi\"",
+        applesoft_options(),
+        InterpreterError::TypeMismatch,
+    );
 }
 
 #[test]
 fn for_loops_work() {
     assert_program_is_fine("10 for i = 1 to 3: next i");
+    assert_program_is_fine("10 for i = 1 to 3: next\n20 print i");
+    assert_program_is_fine("10 for i = 1 to 3: for j = 1 to 3: next j, i");
     assert_program_has_error(
         "5 i = 0\n10 for i$ = 1 to 3: next i\n20 print i$;i",
         InterpreterError::TypeMismatch,
@@ -143,6 +315,22 @@ fn for_loops_work() {
         "5 i$ = \"hi\"\n10 for i = 1 to 3: next i$\n20 print i",
         InterpreterError::TypeMismatch,
     );
+    assert_program_has_error(
+        "10 for i = 1 to 3: next i,",
+        SyntaxError::UnexpectedToken(None).into(),
+    );
+}
+
+#[test]
+fn for_loops_with_string_variables_fail() {
+    assert_program_has_error(
+        "10 for i$ = 1 to 3\n20 print i$",
+        InterpreterError::TypeMismatch,
+    );
+    assert_program_has_error(
+        "10 for i$ = \"a\" to \"z\"\n20 print i$",
+        InterpreterError::TypeMismatch,
+    );
 }
 
 #[test]
@@ -167,6 +355,12 @@ fn conditionals_work() {
     );
 }
 
+#[test]
+fn not_requires_a_number() {
+    assert_program_is_fine("10 print not 5");
+    assert_program_has_error("10 print not \"hi\"", InterpreterError::TypeMismatch);
+}
+
 #[test]
 fn unexpected_end_of_input_works() {
     assert_program_has_error(
@@ -204,6 +398,20 @@ fn line_without_statements_warning_works() {
     );
 }
 
+#[test]
+fn crlf_line_endings_work() {
+    assert_program_is_fine("10 x$ = \"hi\"\r\n20 print x$\r\n30 rem sup\r\n");
+    let analyzer = SourceFileAnalyzer::analyze("10 rem hi\r\n20 print \"x\"\r\n".to_string());
+    assert_eq!(
+        analyzer.source_file_lines(),
+        &vec![
+            "10 rem hi".to_string(),
+            "20 print \"x\"".to_string(),
+            "".to_string()
+        ]
+    );
+}
+
 #[test]
 fn line_without_number_warning_works() {
     assert_program_has_source_mapped_diagnostics(
@@ -243,6 +451,170 @@ fn undefined_symbol_works() {
     );
 }
 
+#[test]
+fn symbols_assigned_before_use_are_fine() {
+    assert_program_is_fine("10 x = 1\n20 print x");
+    assert_program_is_fine("10 x = 1: x = x + 1: print x");
+    assert_program_is_fine("10 dim a(3): a(1) = 1\n20 print a(1)");
+}
+
+#[test]
+fn symbol_used_before_defined_works() {
+    assert_program_has_source_mapped_diagnostics(
+        "10 print x\n20 x = 1",
+        vec![SourceMappedMessage::new(
+            Warning,
+            "'X' is used before it's defined.",
+            0,
+            "x",
+        )],
+    );
+}
+
+#[test]
+fn symbol_used_before_defined_on_same_line_works() {
+    assert_program_has_source_mapped_diagnostics(
+        "10 x = x + 1: print x",
+        vec![SourceMappedMessage::new(
+            Warning,
+            "'X' is used before it's defined.",
+            0,
+            "x",
+        )],
+    );
+}
+
+#[test]
+fn arrays_and_scalars_are_tracked_separately() {
+    assert_program_has_source_mapped_diagnostics(
+        "10 a(1) = 1\n20 print a",
+        vec![
+            SourceMappedMessage::new(Warning, "'A' is never used.", 0, "a"),
+            SourceMappedMessage::new(Warning, "'A' is never defined.", 1, "a"),
+        ],
+    );
+}
+
+#[test]
+fn function_parameters_are_not_variable_reads() {
+    assert_program_is_fine("10 def fn f(x) = x * 2\n20 print fn f(1)");
+}
+
+#[test]
+fn diagnostic_columns_include_line_number_prefix() {
+    for (program, expected_range) in [("1000 print a", 11..12), ("  12345print a", 13..14)] {
+        let mut analyzer = SourceFileAnalyzer::analyze(program.to_string());
+        let messages = analyzer.take_messages();
+        assert_eq!(messages.len(), 1, "analyzing {program}");
+        assert_eq!(
+            analyzer.source_file_map().map_to_source(&messages[0]),
+            Some((0, expected_range)),
+            "analyzing {program}"
+        );
+    }
+}
+
+#[test]
+fn unclosed_loop_warning_works() {
+    let options = AnalyzerOptions {
+        enable_unclosed_loop_warnings: true,
+        ..Default::default()
+    };
+    let program = "10 for i = 1 to 3\n20 print i";
+    assert_program_has_source_mapped_diagnostics_with_options(
+        program,
+        options,
+        vec![SourceMappedMessage::new(
+            Warning,
+            "FOR loop over 'I' has no matching NEXT.",
+            0,
+            "i",
+        )],
+    );
+    assert_program_has_source_mapped_diagnostics(program, vec![]);
+
+    for program in [
+        "10 for i = 1 to 3: print i: next",
+        "10 for i = 1 to 3: for j = 1 to 3: print i;j: next j, i",
+        // A NEXT for an outer loop forgets about any loops inside it.
+        "10 for i = 1 to 3: for j = 1 to 3: print i;j: next i",
+        // Exiting a loop early via GOTO is fine.
+        "10 for i = 1 to 3\n20 if i = 2 then goto 40\n30 next i\n40 print i",
+    ] {
+        assert_program_has_source_mapped_diagnostics_with_options(program, options, vec![]);
+    }
+}
+
+#[test]
+fn no_op_jump_hint_works() {
+    let options = AnalyzerOptions {
+        enable_no_op_jump_hints: true,
+        ..Default::default()
+    };
+    let program = "10 goto 30\n20 print \"unreachable\"\n30 data 1, 2\n40 print \"hi\"";
+    assert_program_has_source_mapped_diagnostics_with_options(
+        program,
+        options,
+        vec![SourceMappedMessage::new(
+            Hint,
+            "Line 30 starts with a DATA statement, which does nothing when run.",
+            0,
+            "30",
+        )],
+    );
+    assert_program_has_source_mapped_diagnostics(program, vec![]);
+
+    assert_program_has_source_mapped_diagnostics_with_options(
+        "10 gosub 30: end\n20 rem subroutine\n30 rem starts here\n40 return",
+        options,
+        vec![SourceMappedMessage::new(
+            Hint,
+            "Line 30 starts with a REM statement, which does nothing when run.",
+            0,
+            "30",
+        )],
+    );
+
+    assert_program_has_source_mapped_diagnostics_with_options(
+        "10 if 1 then 30\n20 rem skipped\n30 print \"hi\"",
+        options,
+        vec![],
+    );
+}
+
+#[test]
+fn shadowing_hint_works() {
+    let options = AnalyzerOptions {
+        enable_shadowing_hints: true,
+        ..Default::default()
+    };
+    let program = "10 x = 1\n20 def fn f(x) = x + 1\n30 print fn f(x)";
+    assert_program_has_source_mapped_diagnostics_with_options(
+        program,
+        options,
+        vec![SourceMappedMessage::new(
+            Hint,
+            "Parameter 'X' shadows a variable of the same name. Any function called while \
+            this one is being evaluated will also see the parameter instead of the variable.",
+            1,
+            "x",
+        )],
+    );
+    assert_program_has_source_mapped_diagnostics(program, vec![]);
+
+    let mut analyzer = SourceFileAnalyzer::analyze_lines_with_options(
+        vec!["10 y = 1".to_string(), "20 def fn f(x) = x + y".to_string()],
+        options,
+    );
+    let messages = analyzer.take_messages();
+    assert!(
+        !messages
+            .iter()
+            .any(|message| matches!(message, DiagnosticMessage::Hint(..))),
+        "expected no hints but got {messages:?}"
+    );
+}
+
 #[test]
 fn redefined_line_warning_works() {
     assert_program_has_source_mapped_diagnostics(
@@ -256,6 +628,55 @@ fn redefined_line_warning_works() {
     );
 }
 
+#[test]
+fn redefined_line_warning_works_for_lines_that_are_not_defined() {
+    assert_program_has_source_mapped_diagnostics(
+        "10 print \"boop\n20 print 2\n10 print 10",
+        vec![
+            SourceMappedMessage::new(Error, "SYNTAX ERROR (UNTERMINATED STRING)", 0, "\"boop"),
+            SourceMappedMessage::new(Warning, "Redefinition of pre-existing BASIC line.", 2, "10"),
+        ],
+    );
+}
+
+#[test]
+fn undefined_jump_target_error_points_at_line_number() {
+    for (program, message, line) in [
+        ("10 goto 999", "UNDEF'D STATEMENT ERROR IN 10", 0),
+        (
+            "10 print 1\n20 gosub 999",
+            "UNDEF'D STATEMENT ERROR IN 20",
+            1,
+        ),
+        ("10 if 1 then 999", "UNDEF'D STATEMENT ERROR IN 10", 0),
+        (
+            "10 if 1 then 10 else 999",
+            "UNDEF'D STATEMENT ERROR IN 10",
+            0,
+        ),
+        ("10 onerr goto 999", "UNDEF'D STATEMENT ERROR IN 10", 0),
+    ] {
+        assert_program_has_source_mapped_diagnostics_with_options(
+            program,
+            applesoft_options(),
+            vec![SourceMappedMessage::new(Error, message, line, "999")],
+        );
+    }
+}
+
+#[test]
+fn jumps_to_defined_lines_are_fine() {
+    assert_program_is_fine_with_options(
+        r#"10 gosub 40
+        20 if 1 then 50 else 10
+        30 onerr goto 50
+        40 return
+        50 goto 60
+        60 end"#,
+        applesoft_options(),
+    );
+}
+
 #[test]
 fn unterminated_string_literal_works() {
     assert_program_has_source_mapped_diagnostics(
@@ -282,6 +703,56 @@ fn type_mismatch_works() {
     );
 }
 
+#[test]
+fn equality_type_mismatch_works() {
+    assert_program_has_source_mapped_diagnostics(
+        "10 x = 1: x$ = \"hi\": print x = x$",
+        vec![SourceMappedMessage::new(
+            Error,
+            "TYPE MISMATCH (EXPECTED NUMBER ON RIGHT SIDE) IN 10",
+            0,
+            "x$",
+        )],
+    );
+}
+
+#[test]
+fn trailing_tokens_after_statement_works() {
+    assert_program_has_source_mapped_diagnostics(
+        "10 x = 1 print x\n20 print x",
+        vec![SourceMappedMessage::new(
+            Error,
+            "SYNTAX ERROR (UNEXPECTED TOKEN 'PRINT') IN 10",
+            0,
+            "print",
+        )],
+    );
+}
+
+#[test]
+fn metrics_works() {
+    assert_eq!(
+        analyze(
+            r#"10 def fn sq(x) = x * x
+            20 for i = 1 to 3
+            30 gosub 100
+            40 next i
+            50 if i > 3 then 70 else 60
+            60 goto 10
+            70 end
+            100 print fn sq(i)
+            110 return"#
+        )
+        .metrics(),
+        ProgramMetrics {
+            lines: 9,
+            jumps: 4,
+            loops: 1,
+            function_definitions: 1,
+        }
+    );
+}
+
 #[test]
 fn token_types_works() {
     use TokenType::*;
@@ -291,3 +762,279 @@ fn token_types_works() {
         vec![vec![(Number, 0..2), (Keyword, 3..9), (String, 11..15)]],
     );
 }
+
+#[test]
+fn remark_is_a_single_comment_token() {
+    use TokenType::*;
+
+    assert_program_token_types(
+        "10 rem print \"x\":goto 10",
+        vec![vec![(Number, 0..2), (Comment, 3..24)]],
+    );
+    // Nothing inside the remark is analyzed, either.
+    assert_program_is_fine("10 rem print x:goto 20");
+}
+
+#[test]
+fn tokenize_line_works() {
+    use TokenType::*;
+
+    let tokens =
+        SourceFileAnalyzer::tokenize_line("10 x$ = \"hi\": rem sup", &Interpreter::default())
+            .unwrap()
+            .into_iter()
+            .map(|(token, token_type, range)| (token.to_string(), token_type, range))
+            .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        vec![
+            ("10".to_string(), Number, 0..2),
+            ("X$".to_string(), Symbol, 3..5),
+            ("=".to_string(), Operator, 6..7),
+            ("\"hi\"".to_string(), String, 8..12),
+            (":".to_string(), Delimiter, 12..13),
+            ("REM sup".to_string(), Comment, 14..21),
+        ]
+    );
+
+    assert_eq!(
+        SourceFileAnalyzer::tokenize_line("print 1", &Interpreter::default()).unwrap(),
+        vec![
+            (Token::Print, Keyword, 0..5),
+            (Token::NumericLiteral(1.0), Number, 6..7)
+        ]
+    );
+
+    assert_eq!(
+        SourceFileAnalyzer::tokenize_line("10 print \"boop", &Interpreter::default())
+            .unwrap_err()
+            .error
+            .to_string(),
+        "SYNTAX ERROR (UNTERMINATED STRING)"
+    );
+}
+
+#[test]
+fn tokenize_line_uses_interpreter_settings() {
+    let tokenize = |interpreter: &Interpreter| {
+        SourceFileAnalyzer::tokenize_line("while x: wend", interpreter)
+            .unwrap()
+            .into_iter()
+            .map(|(token, _, _)| token.to_string())
+            .collect::<Vec<_>>()
+    };
+    let mut interpreter = Interpreter::default();
+    assert_eq!(tokenize(&interpreter), vec!["WHILEX", ":", "W", "END"]);
+
+    interpreter.enable_while_loops = true;
+    assert_eq!(tokenize(&interpreter), vec!["WHILE", "X", ":", "WEND"]);
+}
+
+#[test]
+fn print_commas_are_classified_as_print_delimiters() {
+    use TokenType::*;
+
+    assert_program_token_types(
+        "10 print a,b",
+        vec![vec![
+            (Number, 0..2),
+            (Keyword, 3..8),
+            (Symbol, 9..10),
+            (PrintDelimiter, 10..11),
+            (Symbol, 11..12),
+        ]],
+    );
+    assert_program_token_types(
+        "10 read a,b",
+        vec![vec![
+            (Number, 0..2),
+            (Keyword, 3..7),
+            (Symbol, 8..9),
+            (Delimiter, 9..10),
+            (Symbol, 10..11),
+        ]],
+    );
+
+    // Commas in function calls aren't print delimiters, and neither are
+    // commas in statements after the PRINT.
+    let types =
+        SourceFileAnalyzer::tokenize_line("? mid$(a$,1),1: read a,b", &Interpreter::default())
+            .unwrap()
+            .into_iter()
+            .filter(|(token, _, _)| token == &Token::Comma)
+            .map(|(_, token_type, _)| token_type)
+            .collect::<Vec<_>>();
+    assert_eq!(types, vec![Delimiter, PrintDelimiter, Delimiter]);
+}
+
+#[test]
+fn question_mark_is_classified_as_keyword() {
+    use TokenType::*;
+
+    assert_program_token_types(
+        "10 ? \"hi\"",
+        vec![vec![(Number, 0..2), (Keyword, 3..4), (String, 5..9)]],
+    );
+}
+
+#[test]
+fn find_definition_works() {
+    let analyzer = analyze(
+        r#"10 x = 5
+        20 def fna(n) = n * x
+        30 gosub 50
+        40 print fna(x): end
+        50 x = x + 1: return"#,
+    );
+
+    // Line numbers jump to the line they refer to.
+    assert_eq!(analyzer.find_definition(2, 9), Some((4, 0..2)));
+    assert_eq!(analyzer.find_definition(2, 11), Some((4, 0..2)));
+
+    // Functions jump to their definition.
+    assert_eq!(analyzer.find_definition(3, 10), Some((1, 7..10)));
+
+    // Variables jump to their first assignment.
+    assert_eq!(analyzer.find_definition(3, 13), Some((0, 3..4)));
+    assert_eq!(analyzer.find_definition(4, 7), Some((0, 3..4)));
+    assert_eq!(analyzer.find_definition(4, 3), Some((0, 3..4)));
+
+    // Anything else has no definition.
+    assert_eq!(analyzer.find_definition(3, 3), None);
+    assert_eq!(analyzer.find_definition(0, 0), None);
+    assert_eq!(analyzer.find_definition(5, 0), None);
+}
+
+#[test]
+fn find_definition_ignores_references_to_nonexistent_lines() {
+    let analyzer = analyze("10 goto 500");
+    assert_eq!(analyzer.find_definition(0, 9), None);
+}
+
+#[test]
+fn hover_works() {
+    let analyzer = analyze(
+        r#"10 a$ = "hi": dim b(3)
+        20 def fna(n) = n * 2
+        30 print mid$(a$, 1, 1); b(1); fna(5); c"#,
+    );
+
+    assert_eq!(
+        analyzer.hover(0, 3),
+        Some("`A$`: string variable".to_string())
+    );
+    assert_eq!(
+        analyzer.hover(0, 18),
+        Some("`B`: numeric array".to_string())
+    );
+    assert_eq!(
+        analyzer.hover(1, 7),
+        Some("`FNA`: numeric function".to_string())
+    );
+    assert_eq!(
+        analyzer.hover(2, 9),
+        Some(
+            "`MID$(A$, START[, LENGTH])`\n\nReturns part of a string, starting at the given \
+            1-based position."
+                .to_string()
+        )
+    );
+    assert_eq!(
+        analyzer.hover(2, 40),
+        Some("`C`: numeric variable".to_string())
+    );
+
+    // Keywords, literals, and positions outside the program have no hover.
+    assert_eq!(analyzer.hover(2, 3), None);
+    assert_eq!(analyzer.hover(2, 23), None);
+    assert_eq!(analyzer.hover(5, 0), None);
+}
+
+#[test]
+fn variables_works() {
+    let analyzer = analyze(
+        r#"10 a$ = "hi": b(1) = 5: b = 2
+        20 def fna(n) = n * 2
+        30 print a$; b(1); fna(b)"#,
+    );
+
+    let variable = |name: &str, is_string, is_array| VariableSymbol {
+        name: name.to_string(),
+        is_string,
+        is_array,
+    };
+    let variables = analyzer.variables();
+    assert_eq!(
+        variables,
+        vec![
+            variable("A$", true, false),
+            variable("B", false, false),
+            variable("B", false, true),
+        ]
+    );
+    assert_eq!(variables[0].detail(), "string variable");
+    assert_eq!(variables[2].detail(), "numeric array");
+}
+
+#[test]
+fn outline_works() {
+    let analyzer = analyze(
+        r#"10 def fna(x) = x * 2
+        20 def fnb$(a$, n) = left$(a$, n): print "hi"
+
+        30 rem functions
+        40 print fna(1); fnb$("hello", 2)
+        50 x = 1: end"#,
+    );
+    let outline = analyzer.outline();
+
+    assert_eq!(
+        outline
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.file_line_number))
+            .collect::<Vec<_>>(),
+        vec![
+            ("10 DEF", 0),
+            ("20 DEF", 1),
+            ("30 REM", 3),
+            ("40 PRINT", 4),
+            ("50 LET", 5)
+        ]
+    );
+    assert_eq!(
+        outline[0],
+        OutlineSymbol {
+            name: "10 DEF".to_string(),
+            detail: None,
+            kind: OutlineSymbolKind::Line,
+            file_line_number: 0,
+            range: 0..21,
+            selection_range: 0..2,
+            children: vec![OutlineSymbol {
+                name: "FNA".to_string(),
+                detail: Some("(X)".to_string()),
+                kind: OutlineSymbolKind::Function,
+                file_line_number: 0,
+                range: 3..21,
+                selection_range: 7..10,
+                children: vec![],
+            }],
+        }
+    );
+    assert_eq!(outline[1].range, 0..45);
+    assert_eq!(
+        outline[1].children,
+        vec![OutlineSymbol {
+            name: "FNB$".to_string(),
+            detail: Some("(A$, N)".to_string()),
+            kind: OutlineSymbolKind::Function,
+            file_line_number: 1,
+            range: 3..33,
+            selection_range: 7..11,
+            children: vec![],
+        }]
+    );
+    for symbol in &outline[2..] {
+        assert_eq!(symbol.children, vec![], "{}", symbol.name);
+    }
+}
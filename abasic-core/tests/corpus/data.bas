@@ -0,0 +1,12 @@
+10 DIM N$(3)
+20 FOR I = 1 TO 3
+30 READ N$(I), A(I)
+40 NEXT I
+50 FOR I = 3 TO 1 STEP -1
+60 PRINT N$(I),A(I)
+70 NEXT I
+80 RESTORE
+90 READ N$
+100 PRINT "FIRST IS " N$
+1000 DATA "ALICE", 31, BOB, 27
+1010 DATA CAROL, 45
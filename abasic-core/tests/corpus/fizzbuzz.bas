@@ -0,0 +1,6 @@
+10 FOR I = 1 TO 15
+20 IF I - INT(I / 15) * 15 = 0 THEN PRINT "FIZZBUZZ":GOTO 60
+30 IF I - INT(I / 3) * 3 = 0 THEN PRINT "FIZZ":GOTO 60
+40 IF I - INT(I / 5) * 5 = 0 THEN PRINT "BUZZ":GOTO 60
+50 PRINT I
+60 NEXT I
@@ -0,0 +1,8 @@
+10 DEF FN SQ(X) = X * X
+20 FOR I = 1 TO 3
+30 GOSUB 100
+40 NEXT I
+50 PRINT "DONE"
+60 END
+100 PRINT I; " SQUARED IS "; FN SQ(I)
+110 RETURN
@@ -0,0 +1,71 @@
+//! This runs every program in the `corpus` directory through each of our
+//! interpreter implementations, and ensures that their output matches the
+//! program's expected output, which is stored alongside it in a file with
+//! the same name and an `.out` extension.
+//!
+//! Currently `abasic-core` is the only implementation, but keeping the
+//! corpus separate from any particular implementation's test suite makes
+//! it easy to catch behavioral drift if another one is added.
+
+use std::path::{Path, PathBuf};
+
+use abasic_core::{Interpreter, InterpreterOutput, InterpreterState};
+
+type Implementation = fn(&str) -> String;
+
+const IMPLEMENTATIONS: &[(&str, Implementation)] = &[("abasic-core", run_with_abasic_core)];
+
+fn run_with_abasic_core(source: &str) -> String {
+    let mut interpreter = Interpreter::default();
+    if let Err(err) = interpreter.load_program(source) {
+        panic!("loading program failed: {}", err);
+    }
+    let mut result = interpreter.start_evaluating("RUN");
+    while result.is_ok() && interpreter.get_state() == InterpreterState::Running {
+        result = interpreter.continue_evaluating();
+    }
+    let mut output = String::new();
+    for item in interpreter.take_output() {
+        match item {
+            InterpreterOutput::Print(string) => output.push_str(&string),
+            InterpreterOutput::Ended => {}
+            _ => output.push_str(&format!("{}\n", item)),
+        }
+    }
+    if let Err(err) = result {
+        panic!("running program failed: {}", err);
+    }
+    output
+}
+
+fn corpus_programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("corpus");
+    let mut programs = std::fs::read_dir(dir)
+        .expect("corpus directory should exist")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bas"))
+        .collect::<Vec<_>>();
+    programs.sort();
+    programs
+}
+
+#[test]
+fn corpus_output_matches_across_implementations() {
+    let programs = corpus_programs();
+    assert!(!programs.is_empty(), "corpus should not be empty");
+    for program in programs {
+        let source = std::fs::read_to_string(&program).unwrap();
+        let expected = std::fs::read_to_string(program.with_extension("out")).unwrap();
+        for (name, run) in IMPLEMENTATIONS {
+            assert_eq!(
+                run(&source),
+                expected,
+                "output of {} under {} should match expected output",
+                program.display(),
+                name
+            );
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
 use abasic_core::{
-    DiagnosticMessage, Interpreter, InterpreterError, InterpreterOutput, InterpreterState,
-    OutOfMemoryError, SourceFileAnalyzer, SyntaxError, Token, TracedInterpreterError,
+    AnalyzerOptions, BinaryProgramError, DiagnosticMessage, GraphicsMode, InputTarget, Interpreter,
+    InterpreterError, InterpreterOutput, InterpreterState, OutOfMemoryError, ProgramStorage,
+    RunOutcome, SourceFileAnalyzer, SyntaxError, Token, TracedInterpreterError,
 };
 
 struct Action {
@@ -26,6 +29,20 @@ fn create_interpreter() -> Interpreter {
     Interpreter::default()
 }
 
+fn create_applesoft_interpreter() -> Interpreter {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_applesoft_statements = true;
+    interpreter
+}
+
+fn analyzer_options_for(interpreter: &Interpreter) -> AnalyzerOptions {
+    AnalyzerOptions {
+        enable_graphics: interpreter.enable_graphics,
+        enable_applesoft_statements: interpreter.enable_applesoft_statements,
+        ..Default::default()
+    }
+}
+
 fn evaluate_while_running(interpreter: &mut Interpreter) -> Result<(), TracedInterpreterError> {
     while interpreter.get_state() == InterpreterState::Running {
         interpreter.continue_evaluating()?;
@@ -42,12 +59,16 @@ fn evaluate_line_while_running(
 }
 
 fn ensure_no_analyzer_errors<T: AsRef<str>>(program: T) {
+    ensure_no_analyzer_errors_with_options(program, AnalyzerOptions::default());
+}
+
+fn ensure_no_analyzer_errors_with_options<T: AsRef<str>>(program: T, options: AnalyzerOptions) {
     let lines = program
         .as_ref()
         .split("\n")
         .map(|line| line.trim_start())
         .map(|s| s.to_owned());
-    let mut analyzer = SourceFileAnalyzer::analyze(lines.clone().collect::<Vec<_>>().join("\n"));
+    let mut analyzer = SourceFileAnalyzer::analyze_lines_with_options(lines.collect(), options);
     for message in analyzer.take_messages() {
         if let DiagnosticMessage::Error(_line, e) = message {
             panic!(
@@ -79,8 +100,17 @@ fn assert_eval_output(line: &'static str, expected: &'static str) {
 }
 
 fn assert_program_actions(program: &'static str, actions: &[Action]) {
-    ensure_no_analyzer_errors(program);
-    let mut interpreter = create_interpreter();
+    assert_program_actions_with_interpreter(create_interpreter(), program, actions);
+}
+
+/// Like `assert_program_actions`, but with an interpreter whose settings
+/// (e.g. which keywords are enabled) the analyzer will also use.
+fn assert_program_actions_with_interpreter(
+    mut interpreter: Interpreter,
+    program: &'static str,
+    actions: &[Action],
+) {
+    ensure_no_analyzer_errors_with_options(program, analyzer_options_for(&interpreter));
     let lines = program.split("\n").map(|line| line.trim_start());
     for line in lines {
         eval_line_and_expect_success(&mut interpreter, line);
@@ -113,8 +143,27 @@ fn assert_program_output(program: &'static str, expected: &'static str) {
     assert_program_actions(program, &[Action::expect_output(expected)]);
 }
 
+fn assert_applesoft_program_output(program: &'static str, expected: &'static str) {
+    assert_program_actions_with_interpreter(
+        create_applesoft_interpreter(),
+        program,
+        &[Action::expect_output(expected)],
+    );
+}
+
 fn assert_program_error(program: &'static str, expected: InterpreterError) {
-    let mut interpreter = create_interpreter();
+    assert_program_error_with_interpreter(create_interpreter(), program, expected);
+}
+
+fn assert_applesoft_program_error(program: &'static str, expected: InterpreterError) {
+    assert_program_error_with_interpreter(create_applesoft_interpreter(), program, expected);
+}
+
+fn assert_program_error_with_interpreter(
+    mut interpreter: Interpreter,
+    program: &'static str,
+    expected: InterpreterError,
+) {
     let lines = program.split("\n").map(|line| line.trim_start());
     for line in lines {
         eval_line_and_expect_success(&mut interpreter, line);
@@ -135,7 +184,10 @@ fn take_output_as_string(interpreter: &mut Interpreter) -> String {
         .into_iter()
         .map(|output| match output {
             InterpreterOutput::Print(message) => message.to_string(),
-            _ => format!("{}\n", output.to_string()),
+            // Most tests don't care about this, and there's a dedicated test
+            // for it, so just skip it.
+            InterpreterOutput::Ended => String::new(),
+            _ => format!("{}\n", output),
         })
         .collect::<Vec<_>>()
         .join("")
@@ -185,6 +237,245 @@ fn print_works_with_comma() {
     assert_eval_output("print ,1", "\t1\n");
 }
 
+#[test]
+fn print_works_with_trailing_comma() {
+    assert_eval_output("print \"a\",", "a\t");
+    assert_eval_output("print \"a\",:print \"b\"", "a\tb\n");
+    assert_eval_output("print \"a\",:print pos(0)", "a\t8\n");
+}
+
+#[test]
+fn number_sign_spacing_works() {
+    let line = "print 5;-5;0;\"x\"";
+    assert_eval_output(line, "5-50x\n");
+
+    let mut interpreter = create_interpreter();
+    interpreter.enable_number_sign_spacing = true;
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, line),
+        " 5-5 0x\n"
+    );
+    // Negative zero starts with a minus sign, so it doesn't need a space.
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print -0"),
+        "-0\n"
+    );
+}
+
+#[test]
+fn print_at_works() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_print_at = true;
+    interpreter.start_evaluating("print at 2,3;\"x\"").unwrap();
+    evaluate_while_running(&mut interpreter).unwrap();
+    let output = interpreter.take_output();
+    assert!(matches!(
+        output[0],
+        InterpreterOutput::MoveCursor { row: 1, column: 2 }
+    ));
+    assert_eq!(
+        output[1..]
+            .iter()
+            .map(|output| output.to_string())
+            .collect::<Vec<_>>(),
+        vec!["x\n"]
+    );
+
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print at 1,5;pos(0)"),
+        "MOVE CURSOR TO ROW 0, COLUMN 4\n4\n"
+    );
+}
+
+#[test]
+fn print_at_validates_coordinates() {
+    for line in [
+        "print at 0,1",
+        "print at 25,1",
+        "print at 1,0",
+        "print at 1,41",
+    ] {
+        let mut interpreter = create_interpreter();
+        interpreter.enable_print_at = true;
+        assert_eq!(
+            evaluate_line_while_running(&mut interpreter, line)
+                .unwrap_err()
+                .error,
+            InterpreterError::IllegalQuantity,
+            "evaluating '{line}'"
+        );
+    }
+}
+
+#[test]
+fn iif_selects_numbers() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_iif = true;
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print iif(1, 5, 6); iif(0, 5, 6)"),
+        "56\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "x = 3: print iif(x > 2, x * 2, x) + 1"),
+        "7\n"
+    );
+}
+
+#[test]
+fn iif_selects_strings() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_iif = true;
+    assert_eq!(
+        eval_line_and_expect_success(
+            &mut interpreter,
+            "for i = 1 to 2: print iif(i = 1, \"one\", \"two\"): next"
+        ),
+        "one\ntwo\n"
+    );
+}
+
+#[test]
+fn iif_evaluates_both_arguments() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_iif = true;
+    let err = evaluate_line_while_running(&mut interpreter, "print iif(1, 5, 1 / 0)").unwrap_err();
+    assert_eq!(err.error, InterpreterError::DivisionByZero);
+}
+
+#[test]
+fn iif_requires_matching_types() {
+    for line in ["print iif(1, 5, \"x\")", "print iif(1, \"x\", 5)"] {
+        let mut interpreter = create_interpreter();
+        interpreter.enable_iif = true;
+        assert_eq!(
+            evaluate_line_while_running(&mut interpreter, line)
+                .unwrap_err()
+                .error,
+            InterpreterError::TypeMismatch,
+            "evaluating '{line}'"
+        );
+    }
+}
+
+#[test]
+fn iif_conditions_work_like_if_conditions() {
+    for condition in ["0", "1", "-0.5", "\"\"", "\"0\"", "\"hi\""] {
+        for strict in [false, true] {
+            let mut interpreter = create_interpreter();
+            interpreter.enable_iif = true;
+            interpreter.enable_strict_conditions = strict;
+            let if_result = evaluate_line_while_running(
+                &mut interpreter,
+                &format!("if {condition} then print \"yes\""),
+            )
+            .map(|_| take_output_as_string(&mut interpreter) == "yes\n")
+            .map_err(|err| err.error);
+            let iif_result = evaluate_line_while_running(
+                &mut interpreter,
+                &format!("print iif({condition}, \"yes\", \"no\")"),
+            )
+            .map(|_| take_output_as_string(&mut interpreter) == "yes\n")
+            .map_err(|err| err.error);
+            assert_eq!(
+                if_result, iif_result,
+                "condition {condition}, strict conditions {strict}"
+            );
+        }
+    }
+}
+
+#[test]
+fn iif_is_disabled_by_default() {
+    assert_eval_error(
+        "print iif(1, 5, 6)",
+        InterpreterError::Syntax(SyntaxError::UnexpectedToken(Some(Token::If))),
+    );
+}
+
+fn run_program_with_while_loops(program: &str) -> Result<String, TracedInterpreterError> {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_while_loops = true;
+    interpreter.load_program(program)?;
+    evaluate_line_while_running(&mut interpreter, "run")?;
+    Ok(take_output_as_string(&mut interpreter))
+}
+
+fn while_loop_program_output(program: &str) -> String {
+    match run_program_with_while_loops(program) {
+        Ok(output) => output,
+        Err(err) => panic!("expected {program} to run successfully but got {err}"),
+    }
+}
+
+#[test]
+fn while_loops_work() {
+    assert_eq!(
+        while_loop_program_output("10 while i < 3\n20 print i;\n30 i = i + 1\n40 wend\n50 print"),
+        "012\n"
+    );
+    assert_eq!(
+        while_loop_program_output("10 while i < 3: print i;: i = i + 1: wend: print \"!\""),
+        "012!\n"
+    );
+}
+
+#[test]
+fn while_loops_with_false_conditions_are_skipped() {
+    assert_eq!(
+        while_loop_program_output(
+            "10 while 0\n20 print \"nope\"\n30 while 1: print \"nope\": wend\n40 wend\n50 print \"done\""
+        ),
+        "done\n"
+    );
+}
+
+#[test]
+fn nested_while_loops_work() {
+    assert_eq!(
+        while_loop_program_output(
+            r#"
+            10 while i < 2
+            20 j = 0
+            30 while j < 3: print i; j; " ";: j = j + 1: wend
+            40 i = i + 1
+            50 wend
+            "#
+        ),
+        "00 01 02 10 11 12 "
+    );
+}
+
+#[test]
+fn jumping_out_of_while_loops_does_not_leak_memory() {
+    assert_eq!(
+        while_loop_program_output(
+            "10 while 1\n20 i = i + 1: if i < 100 then goto 10\n30 print i: end\n40 wend"
+        ),
+        "100\n"
+    );
+}
+
+#[test]
+fn while_without_wend_errors() {
+    let err = run_program_with_while_loops("10 print 1\n20 while 0\n30 print 2").unwrap_err();
+    assert_eq!(err.error, InterpreterError::WhileWithoutWend);
+    assert_eq!(err.line_number(), Some(20));
+}
+
+#[test]
+fn wend_without_while_errors() {
+    let err = run_program_with_while_loops("10 wend").unwrap_err();
+    assert_eq!(err.error, InterpreterError::WendWithoutWhile);
+}
+
+#[test]
+fn while_loops_are_disabled_by_default() {
+    assert_eval_error(
+        "while 1: wend",
+        InterpreterError::Syntax(SyntaxError::ExpectedToken(Token::Equals)),
+    );
+}
+
 #[test]
 fn print_works_with_semicolon() {
     assert_eval_output("print ;", "");
@@ -193,6 +484,68 @@ fn print_works_with_semicolon() {
     assert_eval_output("print \"hello\";:print \"there\"", "hellothere\n");
 }
 
+#[test]
+fn print_works_with_mixed_semicolons_and_commas() {
+    assert_eval_output("a = 1:b = 2:c = 3:d = 4:print a;b,c;d", "12\t34\n");
+    assert_eval_output("print \"a\";\"b\",\"c\"", "ab\tc\n");
+    assert_eval_output("print 1,;2", "1\t2\n");
+    assert_eval_output("print 1;,2", "1\t2\n");
+    assert_eval_output("print 1,,2", "1\t\t2\n");
+    assert_eval_output("print 1,2;", "1\t2");
+}
+
+#[test]
+fn print_tab_works() {
+    assert_eval_output("print tab(5);\"x\"", "    x\n");
+    assert_eval_output("print \"ab\";tab(5);\"x\"", "ab  x\n");
+    assert_eval_output("print \"hello\";tab(3);\"x\"", "hellox\n");
+    assert_eval_output("print tab(1);\"x\"", "x\n");
+    assert_eval_output("print \"ab\";:print tab(4);\"x\"", "ab x\n");
+    assert_eval_error("print tab(-1)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print tab(256)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print tab(1e10)", InterpreterError::IllegalQuantity);
+    assert_eval_error(
+        "print tab(\"x\")",
+        InterpreterError::Syntax(SyntaxError::InvalidTabOrSpcArgument),
+    );
+    assert_eval_error(
+        "print tab 5",
+        InterpreterError::Syntax(SyntaxError::InvalidTabOrSpcArgument),
+    );
+    assert_eval_error(
+        "print spc(5",
+        InterpreterError::Syntax(SyntaxError::InvalidTabOrSpcArgument),
+    );
+    // Other variables that happen to start with TAB or SPC are fine.
+    assert_eval_output("tabs = 1: spc1x = 2: print tabs;spc1x", "12\n");
+}
+
+#[test]
+fn print_spc_works() {
+    assert_eval_output("print \"a\";spc(3);\"b\"", "a   b\n");
+    assert_eval_output("print \"a\";spc(0);\"b\"", "ab\n");
+    assert_eval_output("print spc(2)", "  \n");
+    assert_eval_error("print spc(-1)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print spc(256)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print spc(1e10)", InterpreterError::IllegalQuantity);
+}
+
+#[test]
+fn column_tracking_works() {
+    let mut interpreter = create_interpreter();
+    assert_eq!(interpreter.get_column(), 0);
+    eval_line_and_expect_success(&mut interpreter, "print \"hi\";");
+    assert_eq!(interpreter.get_column(), 2);
+    eval_line_and_expect_success(&mut interpreter, "print \"😊\";");
+    assert_eq!(interpreter.get_column(), 3);
+    eval_line_and_expect_success(&mut interpreter, "print ,;");
+    assert_eq!(interpreter.get_column(), 8);
+    eval_line_and_expect_success(&mut interpreter, "print \"there\"");
+    assert_eq!(interpreter.get_column(), 0);
+    eval_line_and_expect_success(&mut interpreter, "print \"one\" \"\n\" \"two\";");
+    assert_eq!(interpreter.get_column(), 3);
+}
+
 #[test]
 fn print_works_with_math() {
     assert_eval_output("print +4", "4\n");
@@ -248,6 +601,26 @@ fn print_works_with_chained_numeric_equality_expressions() {
     assert_eval_output("print 5 > 4 = 1", "1\n");
 }
 
+#[test]
+fn lenient_equality_operators_work() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_lenient_operators = true;
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print 1 >< 2; 2 => 2; 3 =< 2"),
+        "110\n"
+    );
+    eval_line_and_expect_success(&mut interpreter, "10 print 1 >< 1");
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "0\n");
+}
+
+#[test]
+fn scientific_notation_works() {
+    assert_eval_output("print 1.5e3", "1500\n");
+    assert_eval_output("print 1e-3 * 1000", "1\n");
+    assert_eval_output("print 5e + 1", "50\n");
+    assert_eval_output("e = 2: print 5e", "52\n");
+}
+
 #[test]
 fn exponentiation_works() {
     assert_eval_output("print 5 ^ 2", "25\n");
@@ -256,6 +629,18 @@ fn exponentiation_works() {
     assert_eval_output("print -5 ^ 2", "25\n");
 }
 
+#[test]
+fn exponentiation_precedence_matches_applesoft() {
+    // Like Applesoft BASIC, unary operators bind more tightly than `^`...
+    assert_eval_output("print -2^2", "4\n");
+    assert_eval_output("print 2^-2", "0.25\n");
+    assert_eval_output("print -2^-2", "0.25\n");
+    assert_eval_output("print 0 - 2^2", "-4\n");
+    // ...and `^` is left-associative.
+    assert_eval_output("print 2^3^2", "64\n");
+    assert_eval_output("print 2^-1^2", "0.25\n");
+}
+
 #[test]
 fn unary_logical_operator_works() {
     assert_eval_output("print not 5", "0\n");
@@ -264,6 +649,9 @@ fn unary_logical_operator_works() {
     assert_eval_output("print not 0 * 300", "300\n");
     assert_eval_output("print not 0 + 10", "11\n");
     assert_eval_output("print not 530 + 10", "10\n");
+    assert_eval_error("print not \"hi\"", InterpreterError::TypeMismatch);
+    assert_eval_error("print not \"\"", InterpreterError::TypeMismatch);
+    assert_eval_error("print not a$", InterpreterError::TypeMismatch);
 }
 
 #[test]
@@ -292,6 +680,94 @@ fn int_works() {
     assert_eval_output("print int(5.9)", "5\n");
 }
 
+#[test]
+fn sqr_works() {
+    assert_eval_output("print sqr(9)", "3\n");
+    assert_eval_output("print sqr(0)", "0\n");
+    assert_eval_error("print sqr(-1)", InterpreterError::IllegalQuantity);
+}
+
+#[test]
+fn crunched_builtin_names_work() {
+    assert_eval_output("print s q r(9)", "3\n");
+    assert_eval_output("print S Q R ( 9 )", "3\n");
+    assert_eval_output("print int (s i n(0))", "0\n");
+    assert_eval_output("print m i d $(\"hello\",2,3)", "ell\n");
+}
+
+#[test]
+fn trigonometric_functions_work() {
+    assert_eval_output("print int(sin(0))", "0\n");
+    assert_eval_output("print cos(0)", "1\n");
+    assert_eval_output("print tan(0)", "0\n");
+    assert_eval_output("print atn(0)", "0\n");
+    assert_eval_output("print int(atn(1) * 4 * 1000)", "3141\n");
+}
+
+#[test]
+fn log_and_exp_work() {
+    assert_eval_output("print log(1)", "0\n");
+    assert_eval_output("print exp(0)", "1\n");
+    assert_eval_output("print int(log(exp(5)) + .5)", "5\n");
+    assert_eval_error("print log(0)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print log(-1)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print exp(1000)", InterpreterError::Overflow);
+}
+
+#[test]
+fn len_works() {
+    assert_eval_output("print len(\"\")", "0\n");
+    assert_eval_output("print len(\"hello\")", "5\n");
+    assert_eval_error("print len(5)", InterpreterError::TypeMismatch);
+}
+
+#[test]
+fn left_and_right_work() {
+    assert_eval_output("print left$(\"hello\",2)", "he\n");
+    assert_eval_output("print left$(\"hello\",99)", "hello\n");
+    assert_eval_output("print right$(\"hello\",2)", "lo\n");
+    assert_eval_output("print right$(\"hello\",99)", "hello\n");
+    assert_eval_error(
+        "print left$(\"hello\",0)",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "print right$(\"hello\",-1)",
+        InterpreterError::IllegalQuantity,
+    );
+}
+
+#[test]
+fn mid_works() {
+    assert_eval_output("print mid$(\"hello\",2,3)", "ell\n");
+    assert_eval_output("print mid$(\"hello\",2)", "ello\n");
+    assert_eval_output("print mid$(\"hello\",4,99)", "lo\n");
+    assert_eval_output("print mid$(\"hello\",6)", "\n");
+    assert_eval_output("print mid$(\"hello\",99,1)", "\n");
+    assert_eval_error("print mid$(\"hello\",0)", InterpreterError::IllegalQuantity);
+    // Overflowing and then subtracting gives us NaN.
+    assert_eval_error(
+        "n = 1e300 * 1e300: n = n - n: print mid$(\"hello\",n)",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "n = 1e300 * 1e300: n = n - n: print left$(\"hello\",n)",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "print mid$(\"hello\",1,0)",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "print mid$(\"hello\")",
+        SyntaxError::ExpectedToken(Token::Comma).into(),
+    );
+    assert_eval_error(
+        "print mid$(\"hello\",1,2,3)",
+        SyntaxError::ExpectedToken(Token::RightParen).into(),
+    );
+}
+
 #[test]
 fn rnd_with_positive_number_works() {
     assert_eval_output(
@@ -314,8 +790,150 @@ fn rnd_with_zero_works() {
 }
 
 #[test]
-fn rnd_with_negative_number_is_unimplemented() {
-    assert_eval_error("print rnd(-1)", InterpreterError::Unimplemented);
+fn rnd_with_negative_number_reseeds() {
+    let mut interpreter = create_interpreter();
+    let output = eval_line_and_expect_success(&mut interpreter, "print rnd(-1):print rnd(-1)");
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], lines[1]);
+    assert_eval_output("print rnd(-1) = rnd(-1)", "1\n");
+    assert_eval_output("print rnd(-1) = rnd(-2)", "0\n");
+
+    let run_after_reseed = |seed: u64| {
+        let mut interpreter = create_interpreter();
+        interpreter.randomize(seed);
+        eval_line_and_expect_success(
+            &mut interpreter,
+            "print rnd(1):print rnd(-5):print rnd(1):print rnd(0):print rnd(1)",
+        )
+        .lines()
+        .skip(1)
+        .collect::<Vec<_>>()
+        .join("\n")
+    };
+    assert_eq!(run_after_reseed(1), run_after_reseed(1234));
+}
+
+#[test]
+fn mid_assignment_works() {
+    assert_eval_output(
+        "a$ = \"hello\": mid$(a$, 2, 2) = \"EL\": print a$",
+        "hELlo\n",
+    );
+    assert_eval_output(
+        "a$ = \"hello\": mid$(a$, 2, 2) = \"ELLO\": print a$",
+        "hELlo\n",
+    );
+    assert_eval_output(
+        "a$ = \"hello\": mid$(a$, 2) = \"ELLOOOO\": print a$",
+        "hELLO\n",
+    );
+    assert_eval_output(
+        "a$ = \"hello\": mid$(a$, 5, 0) = \"X\": print a$",
+        "hello\n",
+    );
+    assert_eval_output(
+        "dim a$(1): a$(1) = \"hi\": mid$(a$(1), 1) = \"y\": print a$(1)",
+        "yi\n",
+    );
+}
+
+#[test]
+fn mid_assignment_validates_arguments() {
+    assert_eval_error(
+        "a$ = \"hello\": mid$(a$, 0) = \"x\"",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "a$ = \"hello\": mid$(a$, 6) = \"x\"",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "a$ = \"hello\": mid$(a$, 1, -1) = \"x\"",
+        InterpreterError::IllegalQuantity,
+    );
+    // Overflowing and then subtracting gives us NaN.
+    assert_eval_error(
+        "a$ = \"hello\": n = 1e300 * 1e300: n = n - n: mid$(a$, n) = \"x\"",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "a$ = \"hello\": n = 1e300 * 1e300: n = n - n: mid$(a$, 1, n) = \"x\"",
+        InterpreterError::IllegalQuantity,
+    );
+    assert_eval_error(
+        "a$ = \"hello\": mid$(a$, 1) = 5",
+        InterpreterError::TypeMismatch,
+    );
+    assert_eval_error("a = 5: mid$(a, 1) = \"x\"", InterpreterError::TypeMismatch);
+    assert_eval_error(
+        "a$ = \"hello\": mid$(a$, \"x\") = \"x\"",
+        InterpreterError::TypeMismatch,
+    );
+}
+
+#[test]
+fn chr_works() {
+    assert_eval_output("print chr$(65)", "A\n");
+    assert_eval_output("print chr$(65.9)", "A\n");
+    assert_eval_error("print chr$(-1)", InterpreterError::IllegalQuantity);
+    assert_eval_error("print chr$(\"A\")", InterpreterError::TypeMismatch);
+    assert_eval_output("print chr$(128512)", "\u{1f600}\n");
+}
+
+#[test]
+fn asc_works() {
+    assert_eval_output("print asc(\"A\")", "65\n");
+    assert_eval_output("print asc(\"AB\")", "65\n");
+    assert_eval_output("print asc(\"\u{1f600}\")", "128512\n");
+    assert_eval_error("print asc(\"\")", InterpreterError::IllegalQuantity);
+    assert_eval_error("print asc(65)", InterpreterError::TypeMismatch);
+}
+
+#[test]
+fn asc_and_chr_roundtrip() {
+    assert_eval_output("print asc(chr$(65))", "65\n");
+    assert_eval_output("print asc(chr$(128512))", "128512\n");
+    assert_eval_output("print chr$(asc(\"z\"))", "z\n");
+}
+
+#[test]
+fn pos_works() {
+    assert_eval_output("print pos(0)", "0\n");
+    assert_eval_output("print \"hi\";pos(0)", "hi2\n");
+    assert_eval_output("print \"hi\",pos(0)", "hi\t8\n");
+    assert_eval_output("print \"hi\":print pos(0)", "hi\n0\n");
+    assert_eval_error("print pos(\"hi\")", InterpreterError::TypeMismatch);
+}
+
+#[test]
+fn print_statement_is_output_all_at_once() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .start_evaluating("print \"hi\";pos(0),1")
+        .unwrap();
+    evaluate_while_running(&mut interpreter).unwrap();
+    let output = interpreter.take_output();
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].to_string(), "hi2\t1\n");
+
+    // Anything printed before an error is still output.
+    let err = interpreter
+        .start_evaluating("print \"hi\";1/0")
+        .unwrap_err();
+    assert_eq!(err.error, InterpreterError::DivisionByZero);
+    let output = interpreter.take_output();
+    assert_eq!(output.len(), 1);
+    assert_eq!(output[0].to_string(), "hi");
+}
+
+#[test]
+fn pos_works_with_control_characters() {
+    assert_eval_output("print \"hi\";chr$(13);pos(0)", "hi\r0\n");
+    assert_eval_output("print chr$(13);pos(0)", "\r0\n");
+    assert_eval_output("print \"hi\";chr$(10);pos(0)", "hi\n0\n");
+    assert_eval_output("print \"hi\";chr$(8);pos(0)", "hi\x081\n");
+    assert_eval_output("print chr$(8);pos(0)", "\x080\n");
 }
 
 #[ignore]
@@ -348,6 +966,27 @@ fn if_statement_works_with_strings() {
     assert_eval_output("if \"hi\" then print \"YO\"", "YO\n");
 }
 
+#[test]
+fn if_statement_with_strings_fails_with_strict_conditions() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_strict_conditions = true;
+    for line in [
+        "if \"\" then print \"nope\"",
+        "if \"hi\" then print \"nope\"",
+    ] {
+        assert_eq!(
+            evaluate_line_while_running(&mut interpreter, line)
+                .unwrap_err()
+                .error,
+            InterpreterError::TypeMismatch
+        );
+    }
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "if \"hi\" = \"hi\" then print \"YO\""),
+        "YO\n"
+    );
+}
+
 #[test]
 fn if_statement_works_with_numbers() {
     assert_eval_output("if 0 then print \"THIS SHOULD NOT APPEAR\"", "");
@@ -384,27 +1023,103 @@ fn if_statement_processes_multiple_statements_in_else_clause() {
 fn if_statement_does_not_support_else_when_then_clause_has_multiple_statements() {
     assert_eval_error(
         "if 1 then print:print else print",
-        SyntaxError::UnexpectedToken.into(),
+        SyntaxError::UnexpectedToken(Some(Token::Else)).into(),
     );
 
     assert_eval_error(
         "if 1 then x = 3:y = 4 else z = 3",
-        SyntaxError::UnexpectedToken.into(),
+        SyntaxError::UnexpectedToken(Some(Token::Else)).into(),
     );
 }
 
 #[test]
-fn default_array_values_work() {
-    assert_eval_output("print a(1)", "0\n");
-    assert_eval_output("print a$(1)", "\n");
-    assert_eval_output("print a(1,2,3)", "0\n");
-    assert_eval_output("print a$(1,2,3)", "\n");
+fn unexpected_token_is_reported() {
+    for (line, token) in [
+        ("for 5 = 1 to 2", Some(Token::NumericLiteral(5.0))),
+        ("let = 1", Some(Token::Equals)),
+        ("def fna(x, for) = 1", Some(Token::For)),
+        ("input", None),
+    ] {
+        assert_eval_error(line, SyntaxError::UnexpectedToken(token).into());
+    }
+    assert_eq!(
+        InterpreterError::from(SyntaxError::UnexpectedToken(Some(Token::For))).to_string(),
+        "SYNTAX ERROR (UNEXPECTED TOKEN 'FOR')"
+    );
 }
 
 #[test]
-fn array_assignment_works() {
-    assert_eval_output("a(0) = 5:print a(0)", "5\n");
-    assert_eval_output("a(1,1) = 5:print a(1,1)", "5\n");
+fn trailing_tokens_after_statements_raise_errors() {
+    for (line, token) in [
+        ("x = 5 \"hi\"", "\"hi\""),
+        ("a = 1 print a", "PRINT"),
+        ("dim a(3) print 1", "PRINT"),
+        ("restore print 1", "PRINT"),
+        ("for i = 1 to 2 print i", "PRINT"),
+        ("hcolor=1 print 2", "PRINT"),
+    ] {
+        let mut interpreter = create_graphics_interpreter();
+        let err = evaluate_line_while_running(&mut interpreter, line).unwrap_err();
+        assert_eq!(
+            err.error.to_string(),
+            format!("SYNTAX ERROR (UNEXPECTED TOKEN '{token}')"),
+            "evaluating '{line}'"
+        );
+        assert_eq!(
+            take_output_as_string(&mut interpreter),
+            "",
+            "evaluating '{line}'"
+        );
+    }
+}
+
+#[test]
+fn trailing_tokens_after_goto_are_ignored() {
+    // Applesoft BASIC behaves this way too, although our static analyzer
+    // will complain about it, so we're not using `assert_program_output()`.
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 goto 20blarg\n20 print \"hi\"")
+        .unwrap();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "hi\n"
+    );
+}
+
+#[test]
+fn default_array_values_work() {
+    assert_eval_output("print a(1)", "0\n");
+    assert_eval_output("print a$(1)", "\n");
+    assert_eval_output("print a(1,2,3)", "0\n");
+    assert_eval_output("print a$(1,2,3)", "\n");
+}
+
+#[test]
+fn undeclared_arrays_raise_errors_in_strict_mode() {
+    for line in ["a(1) = 5", "print a(1)"] {
+        let mut interpreter = create_interpreter();
+        interpreter.enable_strict_arrays = true;
+        let err = evaluate_line_while_running(&mut interpreter, line).unwrap_err();
+        assert_eq!(
+            err.error,
+            InterpreterError::UndimensionedArray,
+            "evaluating '{line}'"
+        );
+    }
+
+    let mut interpreter = create_interpreter();
+    interpreter.enable_strict_arrays = true;
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "dim a(5):a(1) = 5:print a(1) a(2)"),
+        "50\n"
+    );
+}
+
+#[test]
+fn array_assignment_works() {
+    assert_eval_output("a(0) = 5:print a(0)", "5\n");
+    assert_eval_output("a(1,1) = 5:print a(1,1)", "5\n");
 
     assert_eval_output("a$(0) = \"blarg\":print a$(0)", "blarg\n");
     assert_eval_output("a$(1,1) = \"blarg\":print a$(1,1)", "blarg\n");
@@ -489,9 +1204,80 @@ fn weird_looping_works() {
     );
 }
 
+#[test]
+fn looping_honors_changes_to_loop_variable() {
+    assert_eval_output(
+        "for i = 1 to 10: i = i + 1: print i:next i",
+        "2\n4\n6\n8\n10\n",
+    );
+
+    assert_eval_output("for i = 1 to 10: print i: i = 10:next i", "1\n");
+}
+
+#[test]
+fn looping_always_executes_body_at_least_once() {
+    // This is how Applesoft BASIC behaves, even though the loop's range is
+    // empty: the body runs, and then NEXT terminates the loop.
+    assert_eval_output("for i = 5 to 1: print i: next i: print i", "5\n6\n");
+    assert_eval_output("for i = 1 to 5 step -1: print i: next i: print i", "1\n0\n");
+}
+
+#[test]
+fn looping_works_with_bare_next() {
+    assert_eval_output(
+        "for i = 1 to 3: print i:next:print \"DONE\" i",
+        "1\n2\n3\nDONE4\n",
+    );
+    assert_eval_output(
+        "for i = 1 to 2:for j = 1 to 2:print i;j:next:next",
+        "11\n12\n21\n22\n",
+    );
+    assert_program_output(
+        r#"
+        10 for i = 1 to 2
+        20 for j = 1 to 2
+        30 print i;j
+        40 next
+        50 next
+        "#,
+        "11\n12\n21\n22\n",
+    );
+}
+
+#[test]
+fn looping_works_with_multiple_variables_in_next() {
+    assert_eval_output(
+        "for i = 1 to 2:for j = 1 to 2:print i;j:next j, i:print \"DONE\"",
+        "11\n12\n21\n22\nDONE\n",
+    );
+    assert_program_output(
+        r#"
+        10 for i = 1 to 2
+        20 for j = 1 to 2
+        30 for k = 1 to 2
+        40 print i;j;k
+        50 next k,j,i
+        "#,
+        "111\n112\n121\n122\n211\n212\n221\n222\n",
+    );
+}
+
+#[test]
+fn next_rejects_trailing_comma() {
+    for line in ["for j = 1 to 1:next j,", "for j = 1 to 3:next j,"] {
+        assert_eval_error(line, SyntaxError::UnexpectedToken(None).into());
+    }
+    assert_eval_error(
+        "for j = 1 to 1:next j,:print \"nope\"",
+        SyntaxError::UnexpectedToken(Some(Token::Colon)).into(),
+    );
+}
+
 #[test]
 fn next_without_for_error_works() {
     assert_eval_error("next i", InterpreterError::NextWithoutFor);
+    assert_eval_error("next", InterpreterError::NextWithoutFor);
+    assert_eval_error("for i = 1 to 1:next i, j", InterpreterError::NextWithoutFor);
     assert_eval_error("for i = 1 to 3:next j", InterpreterError::NextWithoutFor);
     assert_eval_error(
         "for j = 1 to 3:for i = 1 to 3:next j:next i",
@@ -504,6 +1290,31 @@ fn type_mismatch_error_works_with_arithmetic_expressions() {
     assert_eval_error("print -\"hi\"", InterpreterError::TypeMismatch);
     assert_eval_error("print \"hi\" - 4", InterpreterError::TypeMismatch);
     assert_eval_error("print 4 + \"hi\"", InterpreterError::TypeMismatch);
+    assert_eval_error("print \"x\" + 1", InterpreterError::TypeMismatch);
+    assert_eval_error("print \"x\" - \"y\"", InterpreterError::TypeMismatch);
+}
+
+#[test]
+fn string_concatenation_works() {
+    assert_eval_output("print \"foo\" + \"bar\"", "foobar\n");
+    assert_eval_output(
+        "a$ = \"foo\": a$ = a$ + \"bar\" + a$: print a$",
+        "foobarfoo\n",
+    );
+    assert_eval_output("print \"\" + \"\" = \"\"", "1\n");
+}
+
+#[test]
+fn string_concatenation_is_limited_to_255_characters() {
+    // 2^8 = 256 characters is too long, but 255 is fine.
+    assert_eval_output(
+        "a$ = \"x\": for i = 1 to 7: a$ = a$ + a$: next i: a$ = a$ + left$(a$, 127): print len(a$)",
+        "255\n",
+    );
+    assert_eval_error(
+        "a$ = \"x\": for i = 1 to 8: a$ = a$ + a$: next i",
+        InterpreterError::StringTooLong,
+    );
 }
 
 #[test]
@@ -516,6 +1327,35 @@ fn type_mismatch_error_works_with_equality_expressions() {
     assert_eval_error("print x$ > x", InterpreterError::TypeMismatch);
 }
 
+#[test]
+fn type_mismatch_error_in_equality_expressions_describes_right_side() {
+    for (line, expected_context) in [
+        ("print x = x$", "EXPECTED NUMBER ON RIGHT SIDE"),
+        ("print x$ = x", "EXPECTED STRING ON RIGHT SIDE"),
+    ] {
+        let mut interpreter = create_interpreter();
+        let err = evaluate_line_while_running(&mut interpreter, line).unwrap_err();
+        assert_eq!(err.error, InterpreterError::TypeMismatch);
+        assert_eq!(err.context, Some(expected_context), "evaluating '{line}'");
+    }
+}
+
+#[test]
+fn errors_expose_message_and_line_number_separately() {
+    let mut interpreter = create_interpreter();
+    eval_line_and_expect_success(&mut interpreter, "10 print x = x$");
+    let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(
+        err.message(),
+        "TYPE MISMATCH (EXPECTED NUMBER ON RIGHT SIDE)"
+    );
+    assert_eq!(err.line_number(), Some(10));
+
+    let err = evaluate_line_while_running(&mut interpreter, "print 1/0").unwrap_err();
+    assert_eq!(err.message(), "DIVISION BY ZERO ERROR");
+    assert_eq!(err.line_number(), None);
+}
+
 #[test]
 fn type_mismatch_error_works_with_variable_assignment() {
     assert_eval_error("x = x$", InterpreterError::TypeMismatch);
@@ -544,7 +1384,10 @@ fn out_of_data_error_works() {
 
 #[test]
 fn syntax_error_raised_when_no_array_index_is_given() {
-    assert_eval_error("print a()", SyntaxError::UnexpectedToken.into());
+    assert_eval_error(
+        "print a()",
+        SyntaxError::UnexpectedToken(Some(Token::RightParen)).into(),
+    );
 }
 
 #[test]
@@ -578,6 +1421,9 @@ fn undefined_statement_error_works() {
 #[test]
 fn return_without_gosub_error_works() {
     assert_eval_error("return", InterpreterError::ReturnWithoutGosub);
+    let mut interpreter = create_applesoft_interpreter();
+    let err = evaluate_line_while_running(&mut interpreter, "pop").unwrap_err();
+    assert_eq!(err.error, InterpreterError::ReturnWithoutGosub);
 }
 
 #[test]
@@ -614,6 +1460,73 @@ fn line_numbers_work() {
     );
 }
 
+#[test]
+fn line_numbers_above_maximum_raise_errors() {
+    let mut interpreter = create_interpreter();
+    eval_line_and_expect_success(&mut interpreter, "63999 print \"x\"");
+    assert_eq!(
+        evaluate_line_while_running(&mut interpreter, "64000 print \"x\"")
+            .unwrap_err()
+            .error,
+        SyntaxError::LineNumberTooLarge.into()
+    );
+    assert_eq!(
+        interpreter
+            .load_program("64000 print \"x\"")
+            .unwrap_err()
+            .error,
+        SyntaxError::LineNumberTooLarge.into()
+    );
+
+    interpreter.max_line_number = 100;
+    assert_eq!(
+        evaluate_line_while_running(&mut interpreter, "101 print \"x\"")
+            .unwrap_err()
+            .error,
+        SyntaxError::LineNumberTooLarge.into()
+    );
+    eval_line_and_expect_success(&mut interpreter, "100 print \"x\"");
+}
+
+#[test]
+fn lines_above_maximum_length_raise_errors() {
+    let mut interpreter = create_interpreter();
+    interpreter.max_line_length = 20;
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print \"hello there\""),
+        "hello there\n"
+    );
+    let err =
+        evaluate_line_while_running(&mut interpreter, "print \"hello there, pal\"").unwrap_err();
+    assert_eq!(err.error, SyntaxError::LineTooLong.into());
+    assert_eq!(err.message(), "SYNTAX ERROR (LINE TOO LONG)");
+    assert_eq!(
+        interpreter
+            .load_program("10 print \"hello there, pal\"")
+            .unwrap_err()
+            .error,
+        SyntaxError::LineTooLong.into()
+    );
+}
+
+#[test]
+fn line_numbers_are_exposed_in_order() {
+    let mut interpreter = create_interpreter();
+    assert_eq!(interpreter.line_numbers(), Vec::<u64>::new());
+    for line in [
+        "30 print 3",
+        "10 print 1",
+        "20 print 2",
+        "5 print 0",
+        "10 print 1",
+    ] {
+        eval_line_and_expect_success(&mut interpreter, line);
+    }
+    assert_eq!(interpreter.line_numbers(), vec![5, 10, 20, 30]);
+    eval_line_and_expect_success(&mut interpreter, "20");
+    assert_eq!(interpreter.line_numbers(), vec![5, 10, 30]);
+}
+
 #[test]
 fn line_numbers_can_be_redefined() {
     assert_program_output(
@@ -638,6 +1551,267 @@ fn empty_line_numbers_are_deleted() {
     );
 }
 
+#[test]
+fn delete_removes_line_ranges() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print 1\n20 print 2\n25 print 2.5\n30 print 3\n40 print 4")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "delete 20,30");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "10 PRINT 1\n40 PRINT 4\n"
+    );
+    let err = evaluate_line_while_running(&mut interpreter, "goto 20").unwrap_err();
+    assert_eq!(err.error, InterpreterError::UndefinedStatement);
+
+    eval_line_and_expect_success(&mut interpreter, "delete 40");
+    assert_eq!(interpreter.line_numbers(), vec![10]);
+
+    // Deleting lines that don't exist is fine.
+    eval_line_and_expect_success(&mut interpreter, "delete 100, 200");
+    eval_line_and_expect_success(&mut interpreter, "delete 30,20");
+    assert_eq!(interpreter.line_numbers(), vec![10]);
+}
+
+#[test]
+fn clear_forgets_variables_but_keeps_program() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print x").unwrap();
+    eval_line_and_expect_success(&mut interpreter, "x = 5: dim a(3): a(1) = 2");
+    eval_line_and_expect_success(&mut interpreter, "clear");
+    // Arrays were forgotten, so re-dimensioning is fine.
+    eval_line_and_expect_success(&mut interpreter, "dim a(3)");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print x;a(1)"),
+        "00\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "10 PRINT X\n"
+    );
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "0\n");
+}
+
+#[test]
+fn clear_works_as_a_program_statement() {
+    assert_applesoft_program_output(
+        r#"
+        10 x = 5: dim a(3): a(1) = 2
+        20 clear: print x
+        30 dim a(3): print "done"
+        "#,
+        "0\ndone\n",
+    );
+    assert_applesoft_program_error(
+        r#"
+        10 gosub 100
+        20 end
+        100 clear
+        110 return
+        "#,
+        InterpreterError::ReturnWithoutGosub,
+    );
+}
+
+#[test]
+fn clear_is_not_reserved_by_default() {
+    assert_program_output("10 cleared = 2: print cleared", "2\n");
+}
+
+#[derive(Default, Clone)]
+struct InMemoryProgramStorage(Rc<RefCell<HashMap<String, Vec<String>>>>);
+
+impl ProgramStorage for InMemoryProgramStorage {
+    fn save(&mut self, name: &str, listing: &[String]) -> Result<(), String> {
+        if name == "READONLY" {
+            return Err("read-only".to_string());
+        }
+        self.0
+            .borrow_mut()
+            .insert(name.to_string(), listing.to_vec());
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Option<Vec<String>> {
+        self.0.borrow().get(name).cloned()
+    }
+}
+
+fn create_interpreter_with_storage(storage: &InMemoryProgramStorage) -> Interpreter {
+    let mut interpreter = create_interpreter();
+    interpreter.program_storage = Some(Box::new(storage.clone()));
+    interpreter
+}
+
+#[test]
+fn save_and_load_work() {
+    let storage = InMemoryProgramStorage::default();
+    let mut interpreter = create_interpreter_with_storage(&storage);
+    interpreter
+        .load_program("10 print \"hi\"\n20 goto 10")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "save \"MyProgram\"");
+    assert_eq!(
+        storage.0.borrow().get("MyProgram"),
+        Some(&vec![
+            "10 PRINT \"hi\"\n".to_string(),
+            "20 GOTO 10\n".to_string()
+        ])
+    );
+
+    let mut interpreter = create_interpreter_with_storage(&storage);
+    interpreter.load_program("5 print 5\n30 end").unwrap();
+    eval_line_and_expect_success(&mut interpreter, "x = 1");
+    eval_line_and_expect_success(&mut interpreter, "load MyProgram");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "10 PRINT \"hi\"\n20 GOTO 10\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print x"),
+        "0\n"
+    );
+}
+
+#[test]
+fn load_nonexistent_program_fails() {
+    let storage = InMemoryProgramStorage::default();
+    let mut interpreter = create_interpreter_with_storage(&storage);
+    interpreter.load_program("10 print 1").unwrap();
+    let err = interpreter.start_evaluating("load \"nope\"").unwrap_err();
+    assert_eq!(err.error, InterpreterError::FileNotFound);
+    assert_eq!(interpreter.line_numbers(), vec![10]);
+}
+
+#[test]
+fn save_failure_is_reported() {
+    let storage = InMemoryProgramStorage::default();
+    let mut interpreter = create_interpreter_with_storage(&storage);
+    interpreter.load_program("10 print 1").unwrap();
+    let err = interpreter.start_evaluating("save READONLY").unwrap_err();
+    assert_eq!(
+        err.error,
+        InterpreterError::IoError("read-only".to_string())
+    );
+}
+
+#[test]
+fn load_invalid_program_leaves_current_program_untouched() {
+    let storage = InMemoryProgramStorage::default();
+    storage.0.borrow_mut().insert(
+        "BAD".to_string(),
+        vec!["10 print 1\n".to_string(), "20 print \"\n".to_string()],
+    );
+    let mut interpreter = create_interpreter_with_storage(&storage);
+    interpreter.load_program("5 print x").unwrap();
+    eval_line_and_expect_success(&mut interpreter, "x = 1");
+    assert!(interpreter.start_evaluating("load BAD").is_err());
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "5 PRINT X\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print x"),
+        "1\n"
+    );
+}
+
+#[test]
+fn save_and_load_require_storage_and_name() {
+    assert_eval_error("save \"x\"", InterpreterError::Unimplemented);
+    assert_eval_error("load \"x\"", InterpreterError::Unimplemented);
+    assert_eval_error(
+        "save",
+        InterpreterError::Syntax(SyntaxError::UnexpectedToken(None)),
+    );
+    assert_eval_error(
+        "load \"\"",
+        InterpreterError::Syntax(SyntaxError::UnexpectedToken(None)),
+    );
+}
+
+#[test]
+fn delete_requires_line_numbers() {
+    for line in ["delete", "delete x", "delete 10,", "delete 10,20,30"] {
+        assert_eval_error(
+            line,
+            InterpreterError::Syntax(SyntaxError::UnexpectedToken(None)),
+        );
+    }
+}
+
+#[test]
+fn renumber_rewrites_line_numbers_and_references() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("5 goto 17\n12 print \"skipped\"\n17 print \"landed\"")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "renumber");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "10 GOTO 30\n20 PRINT \"skipped\"\n30 PRINT \"landed\"\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "landed\n"
+    );
+
+    eval_line_and_expect_success(&mut interpreter, "renumber 100,5");
+    assert_eq!(interpreter.line_numbers(), vec![100, 105, 110]);
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "landed\n"
+    );
+}
+
+#[test]
+fn renumber_rewrites_gosub_then_and_else_references() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("1 gosub 3: if x then 2 else 3\n2 end\n3 return")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "renumber ,5");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "10 GOSUB 20 : IF X THEN 15 ELSE 20\n15 END\n20 RETURN\n"
+    );
+}
+
+#[test]
+fn renumber_warns_about_references_to_nonexistent_lines() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("1 goto 500\n2 end").unwrap();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "renumber"),
+        "WARNING IN 10: Reference to nonexistent line 500.\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        "10 GOTO 500\n20 END\n"
+    );
+}
+
+#[test]
+fn renumber_rejects_bad_arguments() {
+    for line in ["renumber x", "renumber 10,x", "renumber 10,10,10"] {
+        assert_eval_error(
+            line,
+            InterpreterError::Syntax(SyntaxError::UnexpectedToken(None)),
+        );
+    }
+    assert_eval_error("renumber 10,0", InterpreterError::IllegalQuantity);
+
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("1 end\n2 end").unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "renumber 63999").unwrap_err();
+    assert_eq!(
+        err.error,
+        InterpreterError::Syntax(SyntaxError::LineNumberTooLarge)
+    );
+    assert_eq!(interpreter.line_numbers(), vec![1, 2]);
+}
+
 #[test]
 fn out_of_order_line_numbers_work() {
     assert_program_output(
@@ -690,6 +1864,32 @@ fn gosub_works() {
     );
 }
 
+#[test]
+fn pop_discards_return_address() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter
+        .load_program(
+            r#"10 gosub 100
+            20 print "back in main"
+            30 end
+            100 gosub 200
+            110 print "back in first subroutine"
+            120 return
+            200 pop
+            210 return"#,
+        )
+        .unwrap();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "back in main\n"
+    );
+}
+
+#[test]
+fn pop_is_not_reserved_by_default() {
+    assert_program_output("10 pops = 2: print pops", "2\n");
+}
+
 #[test]
 fn loop_with_goto_after_next_works() {
     assert_program_output(
@@ -746,6 +1946,64 @@ fn stack_overflow_works() {
     );
 }
 
+#[test]
+fn maxstack_changes_stack_limit() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 d = d + 1: gosub 10").unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(err.error, OutOfMemoryError::StackOverflow.into());
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print d"),
+        "33\n"
+    );
+
+    eval_line_and_expect_success(&mut interpreter, "maxstack 5");
+    evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print d"),
+        "6\n"
+    );
+
+    eval_line_and_expect_success(&mut interpreter, "maxstack 10000");
+    evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print d"),
+        "10001\n"
+    );
+}
+
+#[test]
+fn maxstack_does_not_deepen_function_recursion() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 def fna(x) = fna(x) + 1\n20 print fna(1)")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "maxstack 10000");
+    let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(err.error, OutOfMemoryError::StackOverflow.into());
+}
+
+#[test]
+fn maxstack_validates_its_argument() {
+    for line in ["maxstack", "maxstack x", "maxstack -1", "maxstack 1.5"] {
+        assert_eval_error(
+            line,
+            InterpreterError::Syntax(SyntaxError::UnexpectedToken(None)),
+        );
+    }
+    for line in ["maxstack 0", "maxstack 10001"] {
+        assert_eval_error(line, InterpreterError::IllegalQuantity);
+    }
+}
+
+#[test]
+fn maxstack_is_not_a_program_statement() {
+    assert_program_error(
+        "10 maxstack 5",
+        InterpreterError::Syntax(SyntaxError::UnexpectedEndOfInput),
+    );
+}
+
 #[test]
 fn conditional_goto_works() {
     assert_program_output(
@@ -869,6 +2127,33 @@ fn data_in_middle_works() {
     );
 }
 
+#[test]
+fn data_is_read_in_line_number_order() {
+    assert_program_output(
+        r#"
+        50 data 5,6
+        10 data 1,2
+        30 data 3,4
+        20 for i = 1 to 6: read a: print a;: next i
+        "#,
+        "123456",
+    );
+}
+
+#[test]
+fn remaining_data_elements_works() {
+    let mut interpreter = create_interpreter();
+    eval_line_and_expect_success(&mut interpreter, "10 data 1, 2");
+    eval_line_and_expect_success(&mut interpreter, "20 data 3");
+    assert_eq!(interpreter.remaining_data_elements(), 3);
+    eval_line_and_expect_success(&mut interpreter, "read a, b");
+    assert_eq!(interpreter.remaining_data_elements(), 1);
+    eval_line_and_expect_success(&mut interpreter, "read c");
+    assert_eq!(interpreter.remaining_data_elements(), 0);
+    eval_line_and_expect_success(&mut interpreter, "restore");
+    assert_eq!(interpreter.remaining_data_elements(), 3);
+}
+
 #[test]
 fn data_type_mismatch_works() {
     assert_program_error(
@@ -880,6 +2165,25 @@ fn data_type_mismatch_works() {
     );
 }
 
+#[test]
+fn data_type_mismatch_reports_data_line() {
+    for (program, expected_line) in [
+        ("10 data sup\n20 read a", 10),
+        (
+            "10 data 1, 2\n20 data 3, sup\n30 data 5\n40 read a, b, c, d",
+            20,
+        ),
+        ("10 data 1\n20 data sup\n30 read a: read b", 20),
+        ("10 read a, b\n20 data 1\n30 print \"hi\": data \"sup\"", 30),
+    ] {
+        let mut interpreter = create_interpreter();
+        interpreter.load_program(program).unwrap();
+        let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+        assert_eq!(err.error, InterpreterError::DataTypeMismatch);
+        assert_eq!(err.line_number(), Some(expected_line), "running {program}");
+    }
+}
+
 #[test]
 fn statements_are_processed_after_function_definitions() {
     assert_program_output(
@@ -952,7 +2256,7 @@ fn function_calls_with_badly_typed_arguments_fail() {
         10 def fna(x) = x
         20 print fna("boop")
         "#,
-        InterpreterError::TypeMismatch.into(),
+        InterpreterError::TypeMismatch,
     );
 }
 
@@ -979,7 +2283,60 @@ fn function_calls_with_too_many_arguments_fail() {
 }
 
 #[test]
-fn infinite_recursion_causes_stack_overflow() {
+fn printing_function_results_works() {
+    assert_program_output(
+        r#"
+        10 def fna$(x) = chr$(x)
+        20 def fnb(x) = x * 2
+        30 print "a";fna$(66);"c";fnb(2);"d"
+        40 print fnb(.25);fna$(fnb(33)) "!"
+        "#,
+        "aBc4d\n0.5B!\n",
+    );
+}
+
+#[test]
+fn function_results_must_match_function_type() {
+    assert_program_error(
+        r#"
+        10 def fna$(x) = x
+        20 print fna$(1)
+        "#,
+        InterpreterError::TypeMismatch,
+    );
+}
+
+#[test]
+fn function_bodies_can_be_retrieved() {
+    let mut interpreter = create_interpreter();
+    assert_eq!(interpreter.get_function_body("fna"), None);
+    interpreter
+        .load_program("10 def fna(x) = x + 1: print \"hi\"\n20 def fnb(x) = fna(x)")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "run");
+
+    let body = interpreter.get_function_body("fna").unwrap();
+    assert_eq!(body.len(), 3);
+    assert_eq!(body[1], Token::Plus);
+    assert_eq!(body[2], Token::NumericLiteral(1.0));
+    let body_source = body
+        .iter()
+        .map(|token| token.to_string())
+        .collect::<String>();
+    assert_eq!(body_source, "X+1");
+
+    let body = interpreter.get_function_body("FNB").unwrap();
+    let body_source = body
+        .iter()
+        .map(|token| token.to_string())
+        .collect::<String>();
+    assert_eq!(body_source, "FNA(X)");
+
+    assert_eq!(interpreter.get_function_body("fnc"), None);
+}
+
+#[test]
+fn infinite_recursion_causes_stack_overflow() {
     assert_program_error(
         r#"
         10 def fna(x) = fna(x) + 1
@@ -1032,6 +2389,73 @@ fn input_reentry_works() {
     )
 }
 
+#[test]
+fn input_works_with_multiple_variables() {
+    assert_program_actions(
+        r#"
+        10 input a, b, c
+        20 print a + b + c
+    "#,
+        &[
+            Action::expect_output("").then_input("1,2,3"),
+            Action::expect_output("6\n"),
+        ],
+    )
+}
+
+#[test]
+fn input_asks_for_remaining_variables() {
+    assert_program_actions(
+        r#"
+        10 input a$, b(1), c
+        20 print a$; b(1); c
+    "#,
+        &[
+            Action::expect_output("").then_input("hi"),
+            Action::expect_output("?").then_input("2"),
+            Action::expect_output("?").then_input("3, 4"),
+            Action::expect_output("EXTRA IGNORED\nhi23\n"),
+        ],
+    )
+}
+
+#[test]
+fn input_reentry_works_with_multiple_variables() {
+    assert_program_actions(
+        r#"
+        10 input a, b
+        20 print a; b
+    "#,
+        &[
+            Action::expect_output("").then_input("1"),
+            Action::expect_output("?").then_input("oops"),
+            Action::expect_output("REENTER\n").then_input("5, 6"),
+            Action::expect_output("56\n"),
+        ],
+    )
+}
+
+#[test]
+fn input_in_expression_errors_cleanly() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter
+        .load_program("10 def fn f(x) = input\n20 print fn f(1)")
+        .unwrap();
+    for line in ["a = input", "print 1 + get", "run"] {
+        let err = evaluate_line_while_running(&mut interpreter, line).unwrap_err();
+        assert_eq!(
+            err.error,
+            InterpreterError::Syntax(SyntaxError::InputInExpression),
+            "evaluating '{line}'"
+        );
+        assert_eq!(interpreter.pending_input_target(), None);
+    }
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print 5"),
+        "5\n"
+    );
+}
+
 #[test]
 fn input_ignoring_extra_works_with_commas() {
     assert_program_actions(
@@ -1046,6 +2470,36 @@ fn input_ignoring_extra_works_with_commas() {
     )
 }
 
+#[test]
+fn input_treats_quoted_commas_as_part_of_value() {
+    assert_program_actions(
+        r#"
+        10 input a$
+        20 print "hello " a$
+    "#,
+        &[
+            Action::expect_output("").then_input("\"sup, dog\""),
+            Action::expect_output("hello sup, dog\n"),
+        ],
+    )
+}
+
+#[test]
+fn raw_input_treats_quoted_commas_as_separators() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_raw_input = true;
+    interpreter
+        .load_program("10 input a$\n20 print \"hello \" a$")
+        .unwrap();
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    interpreter.provide_input("\"sup, dog\"".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(
+        take_output_as_string(&mut interpreter),
+        "EXTRA IGNORED\nhello \"sup\n"
+    );
+}
+
 #[test]
 fn input_ignoring_extra_works_with_colons() {
     // This is weird, but it's how Applesoft BASIC works, and it's how
@@ -1061,3 +2515,1062 @@ fn input_ignoring_extra_works_with_colons() {
         ],
     )
 }
+
+#[test]
+fn input_echo_works() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_input_echo = true;
+    eval_line_and_expect_success(&mut interpreter, "10 input a$");
+    eval_line_and_expect_success(&mut interpreter, "20 print \"hello \" a$");
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "");
+    interpreter.provide_input("budy\x08\x08ddy".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(
+        take_output_as_string(&mut interpreter),
+        "buddy\nhello budy\x08\x08ddy\n"
+    );
+}
+
+#[test]
+fn pending_input_target_works() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 input a\n20 input b$(1)")
+        .unwrap();
+    assert_eq!(interpreter.pending_input_target(), None);
+    eval_line_and_expect_success(&mut interpreter, "run");
+    assert_eq!(
+        interpreter.pending_input_target(),
+        Some(InputTarget::Line { is_string: false })
+    );
+    interpreter.provide_input("this is not a number".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(
+        interpreter.pending_input_target(),
+        Some(InputTarget::Line { is_string: false })
+    );
+    interpreter.provide_input("1".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(
+        interpreter.pending_input_target(),
+        Some(InputTarget::Line { is_string: true })
+    );
+    interpreter.provide_input("hi".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(interpreter.pending_input_target(), None);
+}
+
+#[test]
+fn get_works() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter.enable_input_echo = true;
+    interpreter
+        .load_program("10 print \"key\";: get a$: print \"!\" a$ \"!\"\n20 get n: print n * 2")
+        .unwrap();
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "key");
+    assert_eq!(interpreter.get_state(), InterpreterState::AwaitingInput);
+    assert_eq!(
+        interpreter.pending_input_target(),
+        Some(InputTarget::Keystroke { is_string: true })
+    );
+    interpreter.provide_input("xyz".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(take_output_as_string(&mut interpreter), "!x!\n");
+    assert_eq!(
+        interpreter.pending_input_target(),
+        Some(InputTarget::Keystroke { is_string: false })
+    );
+    interpreter.provide_input("7".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(take_output_as_string(&mut interpreter), "14\n");
+    assert_eq!(interpreter.get_state(), InterpreterState::Idle);
+}
+
+#[test]
+fn queued_input_satisfies_consecutive_input_statements() {
+    let mut interpreter = create_interpreter();
+    interpreter.queue_input(["5".to_string(), "hi".to_string()]);
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "input a: input b$: print a * 2;b$"),
+        "10hi\n"
+    );
+    assert_eq!(interpreter.get_state(), InterpreterState::Idle);
+}
+
+#[test]
+fn queued_input_is_discarded_when_evaluation_errors() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 input a\n20 print 1 / 0")
+        .unwrap();
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "");
+    interpreter.queue_input(["1".to_string(), "2".to_string(), "3".to_string()]);
+    let err = evaluate_while_running(&mut interpreter).unwrap_err();
+    assert_eq!(err.error, InterpreterError::DivisionByZero);
+
+    // The leftover input shouldn't be consumed by the next line we evaluate.
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "input b: print \"b=\";b"),
+        ""
+    );
+    assert_eq!(interpreter.get_state(), InterpreterState::AwaitingInput);
+}
+
+#[test]
+fn queued_input_is_discarded_by_run_and_stop() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 input a: print a").unwrap();
+    interpreter.queue_input(["1".to_string()]);
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "");
+    assert_eq!(interpreter.get_state(), InterpreterState::AwaitingInput);
+
+    interpreter.queue_input(["2".to_string()]);
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(take_output_as_string(&mut interpreter), "2\n");
+    interpreter.queue_input(["3".to_string()]);
+    interpreter.stop_evaluating();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "input b"),
+        ""
+    );
+    assert_eq!(interpreter.get_state(), InterpreterState::AwaitingInput);
+}
+
+#[test]
+fn queued_input_is_provided_if_already_awaiting_input() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 input a\n20 input b\n30 print a + b")
+        .unwrap();
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "");
+    interpreter.queue_input(["1".to_string(), "2".to_string()]);
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(take_output_as_string(&mut interpreter), "3\n");
+}
+
+#[test]
+fn get_treats_empty_input_as_return_key() {
+    let mut interpreter = create_applesoft_interpreter();
+    eval_line_and_expect_success(&mut interpreter, "get a$");
+    interpreter.provide_input("".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print asc(a$)"),
+        "13\n"
+    );
+}
+
+#[test]
+fn get_with_numeric_variable_requires_digit() {
+    let mut interpreter = create_applesoft_interpreter();
+    eval_line_and_expect_success(&mut interpreter, "get a");
+    interpreter.provide_input("x".to_string());
+    assert_eq!(
+        evaluate_while_running(&mut interpreter).unwrap_err().error,
+        InterpreterError::TypeMismatch
+    );
+}
+
+#[test]
+fn get_is_not_reserved_by_default() {
+    assert_program_output("10 target = 2: print target", "2\n");
+}
+
+#[test]
+fn applesoft_error_codes_are_correct() {
+    for (error, code) in [
+        (InterpreterError::NextWithoutFor, 0),
+        (InterpreterError::TypeMismatch, 163),
+        (InterpreterError::StringTooLong, 176),
+        (InterpreterError::CannotContinue, 210),
+    ] {
+        assert_eq!(error.applesoft_error_code(), Some(code), "{error}");
+    }
+}
+
+#[test]
+fn onerr_goto_works() {
+    assert_applesoft_program_output(
+        r#"
+        10 onerr goto 100
+        20 print 1 / 0
+        30 print "unreachable"
+        100 print "recovered from error" peek(222)
+        "#,
+        "recovered from error133\n",
+    );
+}
+
+#[test]
+fn onerr_goto_records_error_line() {
+    assert_applesoft_program_output(
+        r#"
+        10 onerr goto 1000
+        300 x = 1 / 0
+        1000 print peek(218) + 256 * peek(219)
+        "#,
+        "300\n",
+    );
+}
+
+#[test]
+fn resume_retries_statement() {
+    assert_applesoft_program_output(
+        r#"
+        10 onerr goto 100
+        20 d = 0
+        30 print 10 / d
+        40 end
+        100 print "error" peek(222)
+        110 d = 2: resume
+        "#,
+        "error133\n5\n",
+    );
+}
+
+#[test]
+fn resume_without_error_fails() {
+    assert_applesoft_program_error("10 resume", InterpreterError::CannotResume);
+    assert_applesoft_program_error(
+        "10 onerr goto 20\n20 resume",
+        InterpreterError::CannotResume,
+    );
+}
+
+#[test]
+fn onerr_goto_does_not_trap_immediate_mode_errors() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter
+        .load_program("10 onerr goto 100\n100 print \"error\"")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "run");
+    let err = evaluate_line_while_running(&mut interpreter, "print 1 / 0").unwrap_err();
+    assert_eq!(err.error, InterpreterError::DivisionByZero);
+}
+
+#[test]
+fn onerr_goto_does_not_trap_errors_in_lines_typed_after_program_ends() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter
+        .load_program("10 onerr goto 100\n20 end\n100 print \"TRAPPED\"")
+        .unwrap();
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "");
+    for line in ["print \"unterminated", "99999 print 1"] {
+        assert!(
+            evaluate_line_while_running(&mut interpreter, line).is_err(),
+            "evaluating '{}'",
+            line
+        );
+        assert_eq!(
+            take_output_as_string(&mut interpreter),
+            "",
+            "evaluating '{}'",
+            line
+        );
+    }
+}
+
+#[test]
+fn load_program_works_with_crlf_line_endings() {
+    let program = "10 rem hi\n20 print \"hello\"\n\n30 print \"there\"\n";
+    let run = |source: &str| {
+        let mut interpreter = create_interpreter();
+        interpreter.load_program(source).unwrap();
+        (
+            interpreter.line_numbers(),
+            eval_line_and_expect_success(&mut interpreter, "list"),
+            eval_line_and_expect_success(&mut interpreter, "run"),
+        )
+    };
+    assert_eq!(run(&program.replace('\n', "\r\n")), run(program));
+}
+
+#[test]
+fn load_program_works() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print \"hello\"\n\n  20 goto 40\n30 print \"nope\"\n40 print \"there\"\n")
+        .unwrap();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "hello\nthere\n"
+    );
+}
+
+#[test]
+fn load_program_rejects_lines_without_numbers() {
+    let mut interpreter = create_interpreter();
+    let err = interpreter
+        .load_program("10 print \"hi\"\nprint \"nope\"")
+        .unwrap_err();
+    assert_eq!(
+        err.error,
+        InterpreterError::Syntax(SyntaxError::MissingLineNumber)
+    );
+    assert_eq!(
+        err.get_line_with_pointer_caret(&interpreter, None::<&str>),
+        vec!["print \"nope\"".to_string(), "^^^^^^^^^^^^".to_string()]
+    );
+    assert_eq!(interpreter.line_numbers(), vec![10]);
+}
+
+#[test]
+fn load_program_tokenization_errors_show_offending_line() {
+    let mut interpreter = create_interpreter();
+    let err = interpreter
+        .load_program("10 print 1\n20 print .1.")
+        .unwrap_err();
+    assert_eq!(
+        err.get_line_with_pointer_caret(&interpreter, None::<&str>),
+        vec!["20 print .1.".to_string(), "         ^^^".to_string()]
+    );
+}
+
+#[test]
+fn load_program_fails_when_not_idle() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 input a").unwrap();
+    interpreter.start_evaluating("run").unwrap();
+    assert_eq!(interpreter.get_state(), InterpreterState::AwaitingInput);
+    let err = interpreter.load_program("20 print 2").unwrap_err();
+    assert_eq!(err.error, InterpreterError::Busy);
+}
+
+#[test]
+fn runtime_errors_point_at_operators() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print 1 + 5/0 + 2").unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(err.error, InterpreterError::DivisionByZero);
+    assert_eq!(
+        err.get_line_with_pointer_caret(&interpreter, None::<&str>),
+        vec![
+            "PRINT 1 + 5 / 0 + 2".to_string(),
+            "            ^".to_string()
+        ]
+    );
+
+    // Errors raised while evaluating an operand still point at the operand.
+    interpreter.load_program("10 print 1 + sqr(-1)").unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(err.error, InterpreterError::IllegalQuantity);
+    assert_eq!(
+        err.get_line_with_pointer_caret(&interpreter, None::<&str>),
+        vec![
+            "PRINT 1 + SQR ( - 1 )".to_string(),
+            "                    ^".to_string()
+        ]
+    );
+}
+
+#[test]
+fn load_program_reports_tokenization_errors_with_line_context() {
+    let mut interpreter = create_interpreter();
+    let err = interpreter
+        .load_program("10 print \"hi\"\n20 print \"unterminated\n30 print 5")
+        .unwrap_err();
+    assert!(matches!(
+        err.error,
+        InterpreterError::Syntax(SyntaxError::Tokenization(_))
+    ));
+    assert!(err.to_string().contains(" IN 20"));
+    assert_eq!(
+        err.get_line_with_pointer_caret(&interpreter, Some("20 print \"unterminated")),
+        vec![
+            "20 print \"unterminated".to_string(),
+            "         ^^^^^^^^^^^^^".to_string()
+        ]
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "hi\n"
+    );
+}
+
+fn create_graphics_interpreter() -> Interpreter {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_graphics = true;
+    interpreter
+}
+
+#[test]
+fn gr_and_hgr_clear_to_black() {
+    let mut interpreter = create_graphics_interpreter();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "gr"),
+        "FILL LOW-RES SCREEN WITH COLOR 0\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "hcolor=3:hgr"),
+        "FILL HIGH-RES SCREEN WITH COLOR 0\n"
+    );
+}
+
+#[test]
+fn clearing_to_current_color_via_negative_address_works() {
+    let mut interpreter = create_graphics_interpreter();
+    evaluate_line_while_running(&mut interpreter, "hcolor=5:call -3082").unwrap();
+    let output = interpreter.take_output();
+    assert!(matches!(
+        output[..],
+        [InterpreterOutput::FillScreen(GraphicsMode::HighRes, 5)]
+    ));
+}
+
+#[test]
+fn clearing_to_current_color_works() {
+    let mut interpreter = create_graphics_interpreter();
+    evaluate_line_while_running(&mut interpreter, "hgr:hcolor=5:call 62454").unwrap();
+    let output = interpreter.take_output();
+    assert_eq!(output.len(), 2);
+    assert!(matches!(
+        output[0],
+        InterpreterOutput::FillScreen(GraphicsMode::HighRes, 0)
+    ));
+    assert!(matches!(
+        output[1],
+        InterpreterOutput::FillScreen(GraphicsMode::HighRes, 5)
+    ));
+}
+
+#[test]
+fn invalid_colors_raise_errors() {
+    for (line, expected) in [
+        ("color=16", InterpreterError::IllegalQuantity),
+        ("color=-1", InterpreterError::IllegalQuantity),
+        ("hcolor=8", InterpreterError::IllegalQuantity),
+        ("hcolor=\"hi\"", InterpreterError::TypeMismatch),
+    ] {
+        let mut interpreter = create_graphics_interpreter();
+        let err = evaluate_line_while_running(&mut interpreter, line).unwrap_err();
+        assert_eq!(err.error, expected, "evaluating '{line}'");
+    }
+}
+
+#[test]
+fn unsupported_call_addresses_raise_errors() {
+    let mut interpreter = create_graphics_interpreter();
+    let err = evaluate_line_while_running(&mut interpreter, "call 768").unwrap_err();
+    assert_eq!(err.error, InterpreterError::Unimplemented);
+}
+
+#[test]
+fn graphics_keywords_are_not_reserved_by_default() {
+    assert_program_output("10 grade = 5: recall = 3\n20 print grade; recall", "53\n");
+}
+
+#[test]
+fn peeking_keyboard_works() {
+    let mut interpreter = create_applesoft_interpreter();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print peek(49152)"),
+        "0\n"
+    );
+    interpreter.push_key(b'A');
+    interpreter.push_key(b'B');
+    assert_eq!(
+        eval_line_and_expect_success(
+            &mut interpreter,
+            "print peek(49152): print peek(49152): poke 49168,0: print peek(49152)"
+        ),
+        "193\n193\n194\n"
+    );
+    // Once the queue is empty, the last key is returned without its high bit.
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "poke 49168,0: print peek(49152)"),
+        "66\n"
+    );
+}
+
+#[test]
+fn negative_and_fractional_addresses_work() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter.push_key(b'A');
+    assert_eq!(
+        eval_line_and_expect_success(
+            &mut interpreter,
+            "print peek(-16384): print peek(49152.7): poke -16368,0: print peek(-16384)"
+        ),
+        "193\n193\n65\n"
+    );
+}
+
+#[test]
+fn out_of_range_addresses_raise_errors() {
+    let mut interpreter = create_applesoft_interpreter();
+    let err = evaluate_line_while_running(&mut interpreter, "print peek(65536)").unwrap_err();
+    assert_eq!(err.error, InterpreterError::IllegalQuantity);
+    let err = evaluate_line_while_running(&mut interpreter, "poke -65536,0").unwrap_err();
+    assert_eq!(err.error, InterpreterError::IllegalQuantity);
+}
+
+#[test]
+fn polling_keyboard_in_a_loop_works() {
+    let mut interpreter = create_applesoft_interpreter();
+    interpreter.push_key(b'Y');
+    interpreter
+        .load_program(
+            r#"
+            10 k = peek(49152): if k < 128 then 10
+            20 poke 49168,0
+            30 print chr$(k - 128)
+            "#,
+        )
+        .unwrap();
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "Y\n");
+}
+
+#[test]
+fn unsupported_peek_and_poke_addresses_raise_errors() {
+    assert_eval_error("print peek(768)", InterpreterError::Unimplemented);
+    for (line, expected) in [
+        ("poke 768,1", InterpreterError::Unimplemented),
+        ("poke 49168,256", InterpreterError::IllegalQuantity),
+    ] {
+        let mut interpreter = create_applesoft_interpreter();
+        let err = evaluate_line_while_running(&mut interpreter, line).unwrap_err();
+        assert_eq!(err.error, expected, "evaluating '{line}'");
+    }
+}
+
+#[test]
+fn output_limit_works() {
+    let mut interpreter = create_interpreter();
+    interpreter.max_output_bytes = Some(10);
+    interpreter
+        .load_program("10 print \"abc\";: goto 10")
+        .unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "run").unwrap_err();
+    assert_eq!(err.error, InterpreterError::OutputLimitExceeded);
+    assert_eq!(take_output_as_string(&mut interpreter), "abcabcabc");
+
+    // The limit applies each time evaluation starts.
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print \"123456789\""),
+        "123456789\n"
+    );
+}
+
+#[test]
+fn seeding_rng_is_deterministic() {
+    let program = "10 for i = 1 to 5: print rnd(1): next i";
+    let run_with_seed = |seed: u64| {
+        let mut interpreter = create_interpreter();
+        interpreter.randomize(seed);
+        interpreter.load_program(program).unwrap();
+        eval_line_and_expect_success(&mut interpreter, "run")
+    };
+    assert_eq!(run_with_seed(1234), run_with_seed(1234));
+    assert_ne!(run_with_seed(1234), run_with_seed(5678));
+}
+
+#[test]
+fn rng_state_is_isolated_per_interpreter() {
+    let mut a = create_interpreter();
+    let mut b = create_interpreter();
+    a.randomize(1234);
+    b.randomize(1234);
+
+    // Interleave the interpreters' calls to RND, to make sure neither one
+    // advances the other's sequence.
+    let mut a_output = String::new();
+    let mut b_output = String::new();
+    for _ in 0..3 {
+        a_output.push_str(&eval_line_and_expect_success(&mut a, "print rnd(1)"));
+        a_output.push_str(&eval_line_and_expect_success(&mut a, "print rnd(0)"));
+        b_output.push_str(&eval_line_and_expect_success(&mut b, "print rnd(1)"));
+        b_output.push_str(&eval_line_and_expect_success(&mut b, "print rnd(0)"));
+    }
+    assert_eq!(a_output, b_output);
+}
+
+#[test]
+fn resetting_rng_reproduces_sequence() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print rnd(1);rnd(1)").unwrap();
+    interpreter.reset_rng(Some(1234));
+    let first_output = eval_line_and_expect_success(&mut interpreter, "run");
+    assert_ne!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        first_output
+    );
+    interpreter.reset_rng(Some(1234));
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        first_output
+    );
+
+    // Variables and the program itself shouldn't be affected.
+    eval_line_and_expect_success(&mut interpreter, "x = 5");
+    interpreter.reset_rng(None);
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "print x"),
+        "5\n"
+    );
+    assert_eq!(interpreter.line_numbers(), vec![10]);
+    interpreter.reset_rng(Some(1234));
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        first_output
+    );
+}
+
+#[test]
+fn running_empty_program_does_nothing() {
+    let mut interpreter = create_interpreter();
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    assert!(interpreter.take_output().is_empty());
+    assert_eq!(interpreter.get_state(), InterpreterState::Idle);
+
+    // It should be fine to do it again, too.
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    assert!(interpreter.take_output().is_empty());
+    assert_eq!(interpreter.get_state(), InterpreterState::Idle);
+}
+
+#[test]
+fn cont_without_running_fails() {
+    assert_eval_error("cont", InterpreterError::CannotContinue);
+
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print 1").unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "cont").unwrap_err();
+    assert_eq!(err.error, InterpreterError::CannotContinue);
+    assert_eq!(interpreter.get_state(), InterpreterState::Idle);
+
+    // The same goes for an empty program that has been run.
+    let mut interpreter = create_interpreter();
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    let err = evaluate_line_while_running(&mut interpreter, "cont").unwrap_err();
+    assert_eq!(err.error, InterpreterError::CannotContinue);
+}
+
+#[test]
+fn rng_sequence_is_preserved_across_cont() {
+    let run_with_line_20 = |line_20: &str, then_cont: bool| {
+        let mut interpreter = create_interpreter();
+        interpreter.randomize(1234);
+        interpreter
+            .load_program(format!("10 a = rnd(1)\n{line_20}\n30 print a: print rnd(1)").as_str())
+            .unwrap();
+        let output = eval_line_and_expect_success(&mut interpreter, "run");
+        if then_cont {
+            eval_line_and_expect_success(&mut interpreter, "cont")
+        } else {
+            output
+        }
+    };
+    assert_eq!(
+        run_with_line_20("20 stop", true),
+        run_with_line_20("20 rem", false)
+    );
+}
+
+#[test]
+fn immediate_assignments_are_visible_to_program() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 x = 1\n20 stop\n30 print x")
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "run");
+    eval_line_and_expect_success(&mut interpreter, "x = 5");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "cont"),
+        "5\n"
+    );
+
+    // GOTO doesn't clear variables, so this is how a program can be run
+    // with variables set in immediate mode.
+    eval_line_and_expect_success(&mut interpreter, "x = 6");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "goto 30"),
+        "6\n"
+    );
+}
+
+#[test]
+fn run_clears_immediate_assignments() {
+    // This is how Applesoft BASIC behaves: RUN clears all variables before
+    // starting the program.
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print x").unwrap();
+    eval_line_and_expect_success(&mut interpreter, "x = 5");
+    assert_eq!(eval_line_and_expect_success(&mut interpreter, "run"), "0\n");
+}
+
+#[test]
+fn end_emits_ended_and_stop_emits_break() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print \"hi\"\n20 end\n30 print \"nope\"")
+        .unwrap();
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    let output = interpreter.take_output();
+    assert!(matches!(output.last(), Some(InterpreterOutput::Ended)));
+
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print \"hi\"\n20 stop")
+        .unwrap();
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    let output = interpreter.take_output();
+    assert!(matches!(
+        output.last(),
+        Some(InterpreterOutput::Break(Some(20)))
+    ));
+    assert!(!output
+        .iter()
+        .any(|output| matches!(output, InterpreterOutput::Ended)));
+}
+
+#[test]
+fn for_loops_with_string_variables_fail() {
+    assert_eval_error("for i$ = 1 to 3: next i$", InterpreterError::TypeMismatch);
+}
+
+#[test]
+fn last_run_outcome_works() {
+    let mut interpreter = create_interpreter();
+    assert_eq!(interpreter.last_run_outcome(), Some(RunOutcome::Completed));
+    interpreter
+        .load_program("10 print \"hi\"\n20 stop\n30 input a\n40 print 1/a")
+        .unwrap();
+
+    evaluate_line_while_running(&mut interpreter, "run").unwrap();
+    assert_eq!(interpreter.last_run_outcome(), Some(RunOutcome::Broke));
+
+    evaluate_line_while_running(&mut interpreter, "cont").unwrap();
+    assert_eq!(
+        interpreter.last_run_outcome(),
+        Some(RunOutcome::AwaitingInput)
+    );
+
+    interpreter.provide_input("0".to_string());
+    evaluate_while_running(&mut interpreter).unwrap_err();
+    assert_eq!(interpreter.last_run_outcome(), Some(RunOutcome::Errored));
+
+    evaluate_line_while_running(&mut interpreter, "goto 30").unwrap();
+    interpreter.break_at_current_location();
+    assert_eq!(
+        interpreter.last_run_outcome(),
+        Some(RunOutcome::Interrupted)
+    );
+
+    evaluate_line_while_running(&mut interpreter, "print 5").unwrap();
+    assert_eq!(interpreter.last_run_outcome(), Some(RunOutcome::Completed));
+
+    interpreter.start_evaluating("goto 10").unwrap();
+    assert_eq!(interpreter.get_state(), InterpreterState::Running);
+    assert_eq!(interpreter.last_run_outcome(), None);
+}
+
+#[test]
+fn breakpoints_work() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print 1\n20 print 2: stop\n30 print 3\n40 i = i + 1: if i < 2 then 30")
+        .unwrap();
+    interpreter.set_breakpoint(30);
+
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "1\n2\nBREAK IN 20\n"
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "cont"),
+        "BREAK IN 30\n"
+    );
+    assert_eq!(interpreter.last_run_outcome(), Some(RunOutcome::Broke));
+
+    // Resuming doesn't immediately break again, but reaching the line again
+    // does.
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "cont"),
+        "3\nBREAK IN 30\n"
+    );
+
+    interpreter.clear_breakpoint(30);
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "cont"),
+        "3\n"
+    );
+    assert_eq!(interpreter.last_run_outcome(), Some(RunOutcome::Completed));
+}
+
+#[test]
+fn breakpoints_follow_program_changes() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print 1\n20 print 2\n30 print 3")
+        .unwrap();
+    interpreter.set_breakpoint(20);
+    interpreter.set_breakpoint(30);
+    eval_line_and_expect_success(&mut interpreter, "renumber 100, 5");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "1\nBREAK IN 105\n"
+    );
+
+    eval_line_and_expect_success(&mut interpreter, "delete 105");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "1\nBREAK IN 110\n"
+    );
+
+    // A new line with a deleted line's number doesn't inherit its breakpoint.
+    eval_line_and_expect_success(&mut interpreter, "105 print 2.5");
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "1\n2.5\nBREAK IN 110\n"
+    );
+}
+
+#[test]
+fn breakpoints_do_not_break_again_after_input() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 input a\n20 print a").unwrap();
+    interpreter.set_breakpoint(10);
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        "BREAK IN 10\n"
+    );
+    evaluate_line_while_running(&mut interpreter, "cont").unwrap();
+    assert_eq!(
+        interpreter.last_run_outcome(),
+        Some(RunOutcome::AwaitingInput)
+    );
+    interpreter.provide_input("5".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(take_output_as_string(&mut interpreter), "5\n");
+}
+
+#[test]
+fn profiling_counts_line_executions() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_profiling = true;
+    interpreter
+        .load_program(
+            "10 for i = 1 to 5\n20 print i;\n30 next i\n40 if 1 then print: print \"done\"",
+        )
+        .unwrap();
+    eval_line_and_expect_success(&mut interpreter, "run");
+    assert_eq!(
+        interpreter.get_line_execution_counts(),
+        vec![(10, 1), (20, 5), (30, 5), (40, 1)]
+    );
+
+    // Running again should reset the counts.
+    eval_line_and_expect_success(&mut interpreter, "run");
+    assert_eq!(interpreter.get_line_execution_counts()[1], (20, 5));
+}
+
+#[test]
+fn profiling_does_not_double_count_input() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_profiling = true;
+    interpreter.load_program("10 input a\n20 print a").unwrap();
+    eval_line_and_expect_success(&mut interpreter, "run");
+    interpreter.provide_input("5".to_string());
+    evaluate_while_running(&mut interpreter).unwrap();
+    assert_eq!(
+        interpreter.get_line_execution_counts(),
+        vec![(10, 1), (20, 1)]
+    );
+}
+
+#[test]
+fn profiling_is_disabled_by_default() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print 1").unwrap();
+    eval_line_and_expect_success(&mut interpreter, "run");
+    assert!(interpreter.get_line_execution_counts().is_empty());
+}
+
+#[test]
+fn binary_program_round_trip_works() {
+    let program = r#"
+        10 rem this is a program
+        20 data 1,"hello",there
+        30 dim a(3): for i = 1 to 3: read a$: print a$;" ";: next i
+        40 def fna(x) = x * 2 + .5
+        50 if fna(2) >= 4 and not 0 then print "yes" else print "no"
+        60 gosub 100: print chr$(65): end
+        100 print -1.25;"!": return
+    "#;
+    let mut interpreter = create_interpreter();
+    interpreter.load_program(program).unwrap();
+    let expected_output = eval_line_and_expect_success(&mut interpreter, "run");
+    let expected_listing = eval_line_and_expect_success(&mut interpreter, "list");
+    let bytes = interpreter.save_binary_program();
+
+    let mut interpreter = create_interpreter();
+    interpreter.load_binary_program(&bytes).unwrap();
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "run"),
+        expected_output
+    );
+    assert_eq!(
+        eval_line_and_expect_success(&mut interpreter, "list"),
+        expected_listing
+    );
+    assert_eq!(interpreter.save_binary_program(), bytes);
+}
+
+#[test]
+fn loading_invalid_binary_program_fails() {
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("10 print \"hi\"").unwrap();
+    let bytes = interpreter.save_binary_program();
+
+    let mut interpreter = create_interpreter();
+    assert_eq!(
+        interpreter.load_binary_program(b"10 print 1"),
+        Err(BinaryProgramError::InvalidHeader)
+    );
+    assert_eq!(
+        interpreter.load_binary_program(&bytes[..bytes.len() - 1]),
+        Err(BinaryProgramError::UnexpectedEnd)
+    );
+    let mut wrong_version = bytes.clone();
+    wrong_version[4] = 255;
+    assert_eq!(
+        interpreter.load_binary_program(&wrong_version),
+        Err(BinaryProgramError::UnsupportedVersion(255))
+    );
+}
+
+#[test]
+fn loading_binary_program_with_non_finite_numbers_fails() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 print 1.5\n20 data 2.5")
+        .unwrap();
+    let bytes = interpreter.save_binary_program();
+
+    let replace_number = |from: f64, to: f64| {
+        let from = from.to_le_bytes();
+        let mut bytes = bytes.clone();
+        let index = bytes.windows(8).position(|w| w == from).unwrap();
+        bytes[index..index + 8].copy_from_slice(&to.to_le_bytes());
+        bytes
+    };
+    let mut interpreter = create_interpreter();
+    assert_eq!(
+        interpreter.load_binary_program(&replace_number(1.5, f64::NAN)),
+        Err(BinaryProgramError::NonFiniteNumber)
+    );
+    assert_eq!(
+        interpreter.load_binary_program(&replace_number(2.5, f64::INFINITY)),
+        Err(BinaryProgramError::NonFiniteNumber)
+    );
+}
+
+#[test]
+fn loading_binary_program_validates_against_settings() {
+    let mut interpreter = create_interpreter();
+    interpreter.enable_while_loops = true;
+    interpreter
+        .load_program("10 while 0: wend\n50000 print 1")
+        .unwrap();
+    let bytes = interpreter.save_binary_program();
+
+    let mut interpreter = create_interpreter();
+    interpreter.enable_while_loops = true;
+    interpreter.max_line_number = 100;
+    assert_eq!(
+        interpreter.load_binary_program(&bytes),
+        Err(BinaryProgramError::LineNumberTooLarge(50000))
+    );
+
+    let mut interpreter = create_interpreter();
+    interpreter.load_program("20 print 2").unwrap();
+    assert_eq!(
+        interpreter.load_binary_program(&bytes),
+        Err(BinaryProgramError::DisabledToken(Token::While))
+    );
+    assert_eq!(interpreter.line_numbers(), vec![20]);
+}
+
+#[test]
+fn changed_variables_works() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program(
+            "10 x = 1\n20 dim a(5)\n30 a(1) = 2\n40 print x\n50 for i = 1 to 2\n60 next i",
+        )
+        .unwrap();
+
+    // Note that RUN evaluates the first statement of the program.
+    interpreter.start_evaluating("run").unwrap();
+    let mut steps = vec![(
+        interpreter.changed_variables(),
+        interpreter.changed_arrays(),
+    )];
+    while interpreter.get_state() == InterpreterState::Running {
+        interpreter.continue_evaluating().unwrap();
+        steps.push((
+            interpreter.changed_variables(),
+            interpreter.changed_arrays(),
+        ));
+    }
+    let x = || vec!["X".to_string()];
+    let a = || vec!["A".to_string()];
+    let i = || vec!["I".to_string()];
+    assert_eq!(
+        steps,
+        vec![
+            (x(), vec![]),
+            (vec![], a()),
+            (vec![], a()),
+            (vec![], vec![]),
+            (i(), vec![]),
+            (i(), vec![]),
+            (i(), vec![]),
+        ]
+    );
+}
+
+fn count_calls_with_token_budget(interpreter: &mut Interpreter, token_budget: usize) -> usize {
+    let mut calls = 0;
+    while interpreter.get_state() == InterpreterState::Running {
+        interpreter
+            .continue_evaluating_with_token_budget(token_budget)
+            .unwrap();
+        calls += 1;
+    }
+    calls
+}
+
+#[test]
+fn token_budget_completes_long_print() {
+    let mut interpreter = create_interpreter();
+    let items = (1..=50).map(|i| i.to_string()).collect::<Vec<_>>();
+    interpreter
+        .load_program(&format!(
+            "10 rem\n20 print {0}\n30 print {0}",
+            items.join(";")
+        ))
+        .unwrap();
+    // Note that RUN evaluates the first statement of the program.
+    interpreter.start_evaluating("run").unwrap();
+    // Each PRINT exceeds the budget on its own, but since we can only yield
+    // at statement boundaries, each call should still run one of them.
+    let calls = count_calls_with_token_budget(&mut interpreter, 5);
+    assert_eq!(calls, 2);
+    assert_eq!(
+        take_output_as_string(&mut interpreter),
+        format!("{0}\n{0}\n", items.join(""))
+    );
+}
+
+#[test]
+fn token_budget_runs_multiple_statements_per_call() {
+    let mut interpreter = create_interpreter();
+    interpreter
+        .load_program("10 for i = 1 to 100: x = x + i: next i\n20 print x")
+        .unwrap();
+    interpreter.start_evaluating("run").unwrap();
+    let calls = count_calls_with_token_budget(&mut interpreter, 1000);
+    assert!(calls < 100, "expected fewer than 100 calls, got {}", calls);
+    assert_eq!(take_output_as_string(&mut interpreter), "5050\n");
+}
@@ -3,19 +3,31 @@
 /// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/
 use std::{collections::HashMap, error::Error};
 
-use abasic_core::{DiagnosticMessage, SourceFileAnalyzer, TokenType};
+use abasic_core::{
+    DiagnosticMessage, OutlineSymbol, OutlineSymbolKind, SourceFileAnalyzer, TokenType,
+    VariableSymbol,
+};
 use clap::Parser;
 use lsp_server::{
     Connection, ErrorCode, ExtractError, IoThreads, Message, Notification as ServerNotification,
     Request as ServerRequest, RequestId, Response, ResponseError,
 };
 use lsp_types::{
-    notification::{DidChangeTextDocument, DidOpenTextDocument, PublishDiagnostics},
-    request::SemanticTokensFullRequest,
-    Diagnostic, DiagnosticSeverity, InitializeParams, Position, PublishDiagnosticsParams, Range,
-    SemanticToken, SemanticTokenType, SemanticTokens, SemanticTokensFullOptions,
-    SemanticTokensLegend, SemanticTokensOptions, ServerCapabilities, TextDocumentSyncCapability,
-    TextDocumentSyncKind, TextDocumentSyncOptions, WorkDoneProgressOptions,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest,
+        SemanticTokensFullDeltaRequest, SemanticTokensFullRequest,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, DocumentSymbol, DocumentSymbolResponse, GotoDefinitionResponse, Hover,
+    HoverContents, HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind,
+    OneOf, Position, PublishDiagnosticsParams, Range, SemanticToken, SemanticTokenType,
+    SemanticTokens, SemanticTokensDelta, SemanticTokensEdit, SemanticTokensFullDeltaResult,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, ServerCapabilities,
+    SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    WorkDoneProgressOptions,
 };
 
 #[derive(Parser)]
@@ -26,7 +38,7 @@ struct CliArgs {
     listen: bool,
 }
 
-const LISTEN_ADDR: &'static str = "127.0.0.1:5007";
+const LISTEN_ADDR: &str = "127.0.0.1:5007";
 
 type LspResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
 
@@ -55,31 +67,30 @@ fn main() -> LspResult<()> {
     }
 }
 
-const TOKEN_TYPES: &[SemanticTokenType; 8] = &[
-    SemanticTokenType::VARIABLE, // 0
-    SemanticTokenType::STRING,   // 1
-    SemanticTokenType::NUMBER,   // 2
-    SemanticTokenType::OPERATOR, // 3
-    SemanticTokenType::COMMENT,  // 4
-    SemanticTokenType::KEYWORD,  // 5
-    SemanticTokenType::MODIFIER, // 6
-    SemanticTokenType::REGEXP,   // 7
-];
-
-fn abasic_token_type_to_lsp_token_type(abasic_token_type: TokenType) -> u32 {
+fn lsp_token_type(abasic_token_type: TokenType) -> SemanticTokenType {
     match abasic_token_type {
-        // These numbers are indices into `TOKEN_TYPES`.
-        TokenType::Symbol => 0,
-        TokenType::String => 1,
-        TokenType::Number => 2,
-        TokenType::Operator => 3,
-        TokenType::Comment => 4,
-        TokenType::Keyword => 5,
-        TokenType::Delimiter => 6,
-        TokenType::Data => 7,
+        TokenType::Symbol => SemanticTokenType::VARIABLE,
+        TokenType::String => SemanticTokenType::STRING,
+        TokenType::Number => SemanticTokenType::NUMBER,
+        TokenType::Operator => SemanticTokenType::OPERATOR,
+        TokenType::Comment => SemanticTokenType::COMMENT,
+        TokenType::Keyword => SemanticTokenType::KEYWORD,
+        TokenType::Delimiter | TokenType::PrintDelimiter => SemanticTokenType::MODIFIER,
+        TokenType::Data => SemanticTokenType::REGEXP,
     }
 }
 
+/// The legend of token types we send to the client, which has one entry
+/// for each of `TokenType::ALL`, in the same order.
+fn token_types_legend() -> Vec<SemanticTokenType> {
+    TokenType::ALL.into_iter().map(lsp_token_type).collect()
+}
+
+fn abasic_token_type_to_lsp_token_type(abasic_token_type: TokenType) -> u32 {
+    // This is an index into `token_types_legend()`.
+    abasic_token_type.index() as u32
+}
+
 fn handle_one_connection(connection: Connection, io_threads: IoThreads) -> LspResult<()> {
     let server_capabilities = serde_json::to_value(&ServerCapabilities {
         semantic_tokens_provider: Some(
@@ -89,14 +100,11 @@ fn handle_one_connection(connection: Connection, io_threads: IoThreads) -> LspRe
                         work_done_progress: None,
                     },
                     legend: SemanticTokensLegend {
-                        token_types: Vec::from(TOKEN_TYPES),
+                        token_types: token_types_legend(),
                         token_modifiers: vec![],
                     },
                     range: None,
-                    // TODO: It'd be nice to be more incremental in our approach,
-                    // it's expensive to send a full re-tokenization on every
-                    // keystroke.
-                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                 },
             ),
         ),
@@ -111,6 +119,10 @@ fn handle_one_connection(connection: Connection, io_threads: IoThreads) -> LspRe
                 save: None,
             },
         )),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
         ..Default::default()
     })
     .unwrap();
@@ -134,6 +146,7 @@ fn main_loop(connection: Connection, params: serde_json::Value) -> LspResult<()>
     eprintln!("Starting main loop.");
 
     let mut files: HashMap<String, SourceFileAnalyzer> = HashMap::new();
+    let mut semantic_tokens_cache = SemanticTokensCache::default();
 
     for msg in &connection.receiver {
         match msg {
@@ -154,7 +167,184 @@ fn main_loop(connection: Connection, params: serde_json::Value) -> LspResult<()>
                             continue;
                         };
 
-                        let result = Some(get_semantic_tokens(analyzer));
+                        let (tokens, _) = semantic_tokens_cache.remember(
+                            params.text_document.uri.to_string(),
+                            get_semantic_tokens(analyzer),
+                        );
+                        let result = Some(tokens);
+                        let result = serde_json::to_value(&result).unwrap();
+                        connection.sender.send(Message::Response(Response {
+                            id,
+                            result: Some(result),
+                            error: None,
+                        }))?;
+                        continue;
+                    }
+                    CastResult::NoMatch(req) => req,
+                };
+                let req = match cast_request::<SemanticTokensFullDeltaRequest>(req) {
+                    CastResult::Match((id, params)) => {
+                        let Some(analyzer) = files.get(&params.text_document.uri.to_string())
+                        else {
+                            send_request_failed_error(
+                                &connection,
+                                id,
+                                "File contents have not been sent by client".to_string(),
+                            )?;
+                            continue;
+                        };
+
+                        let (tokens, previous_tokens) = semantic_tokens_cache.remember(
+                            params.text_document.uri.to_string(),
+                            get_semantic_tokens(analyzer),
+                        );
+                        let result = match previous_tokens {
+                            Some(previous_tokens)
+                                if previous_tokens.result_id.as_ref()
+                                    == Some(&params.previous_result_id) =>
+                            {
+                                SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                                    edits: get_semantic_token_edits(
+                                        &previous_tokens.data,
+                                        &tokens.data,
+                                    ),
+                                    result_id: tokens.result_id,
+                                })
+                            }
+                            // The client is asking for changes since some
+                            // tokens we no longer have, so just send them all.
+                            _ => SemanticTokensFullDeltaResult::Tokens(tokens),
+                        };
+                        let result = Some(result);
+                        let result = serde_json::to_value(&result).unwrap();
+                        connection.sender.send(Message::Response(Response {
+                            id,
+                            result: Some(result),
+                            error: None,
+                        }))?;
+                        continue;
+                    }
+                    CastResult::NoMatch(req) => req,
+                };
+                let req = match cast_request::<GotoDefinition>(req) {
+                    CastResult::Match((id, params)) => {
+                        let params = params.text_document_position_params;
+                        let Some(analyzer) = files.get(&params.text_document.uri.to_string())
+                        else {
+                            send_request_failed_error(
+                                &connection,
+                                id,
+                                "File contents have not been sent by client".to_string(),
+                            )?;
+                            continue;
+                        };
+
+                        let result = analyzer
+                            .find_definition(
+                                params.position.line as usize,
+                                params.position.character as usize,
+                            )
+                            .map(|(line, range)| {
+                                GotoDefinitionResponse::Scalar(Location::new(
+                                    params.text_document.uri,
+                                    Range::new(
+                                        Position::new(line as u32, range.start as u32),
+                                        Position::new(line as u32, range.end as u32),
+                                    ),
+                                ))
+                            });
+                        let result = serde_json::to_value(&result).unwrap();
+                        connection.sender.send(Message::Response(Response {
+                            id,
+                            result: Some(result),
+                            error: None,
+                        }))?;
+                        continue;
+                    }
+                    CastResult::NoMatch(req) => req,
+                };
+                let req = match cast_request::<HoverRequest>(req) {
+                    CastResult::Match((id, params)) => {
+                        let params = params.text_document_position_params;
+                        let Some(analyzer) = files.get(&params.text_document.uri.to_string())
+                        else {
+                            send_request_failed_error(
+                                &connection,
+                                id,
+                                "File contents have not been sent by client".to_string(),
+                            )?;
+                            continue;
+                        };
+
+                        let result = analyzer
+                            .hover(
+                                params.position.line as usize,
+                                params.position.character as usize,
+                            )
+                            .map(|value| Hover {
+                                contents: HoverContents::Markup(MarkupContent {
+                                    kind: MarkupKind::Markdown,
+                                    value,
+                                }),
+                                range: None,
+                            });
+                        let result = serde_json::to_value(&result).unwrap();
+                        connection.sender.send(Message::Response(Response {
+                            id,
+                            result: Some(result),
+                            error: None,
+                        }))?;
+                        continue;
+                    }
+                    CastResult::NoMatch(req) => req,
+                };
+                let req = match cast_request::<DocumentSymbolRequest>(req) {
+                    CastResult::Match((id, params)) => {
+                        let Some(analyzer) = files.get(&params.text_document.uri.to_string())
+                        else {
+                            send_request_failed_error(
+                                &connection,
+                                id,
+                                "File contents have not been sent by client".to_string(),
+                            )?;
+                            continue;
+                        };
+
+                        let symbols = analyzer
+                            .outline()
+                            .into_iter()
+                            .map(convert_outline_symbol)
+                            .collect();
+                        let result = Some(DocumentSymbolResponse::Nested(symbols));
+                        let result = serde_json::to_value(&result).unwrap();
+                        connection.sender.send(Message::Response(Response {
+                            id,
+                            result: Some(result),
+                            error: None,
+                        }))?;
+                        continue;
+                    }
+                    CastResult::NoMatch(req) => req,
+                };
+                let req = match cast_request::<Completion>(req) {
+                    CastResult::Match((id, params)) => {
+                        let params = params.text_document_position;
+                        let Some(analyzer) = files.get(&params.text_document.uri.to_string())
+                        else {
+                            send_request_failed_error(
+                                &connection,
+                                id,
+                                "File contents have not been sent by client".to_string(),
+                            )?;
+                            continue;
+                        };
+
+                        let items = analyzer
+                            .variables()
+                            .into_iter()
+                            .map(convert_variable_symbol)
+                            .collect();
+                        let result = Some(CompletionResponse::Array(items));
                         let result = serde_json::to_value(&result).unwrap();
                         connection.sender.send(Message::Response(Response {
                             id,
@@ -209,9 +399,11 @@ fn main_loop(connection: Connection, params: serde_json::Value) -> LspResult<()>
                     }
                     CastResult::NoMatch(not) => not,
                 };
-                let not = match cast_notification::<DidChangeTextDocument>(not) {
+                let not = match cast_notification::<DidCloseTextDocument>(not) {
                     CastResult::Match(params) => {
-                        files.remove(&params.text_document.uri.to_string());
+                        let uri = params.text_document.uri.to_string();
+                        files.remove(&uri);
+                        semantic_tokens_cache.forget(&uri);
                         continue;
                     }
                     CastResult::NoMatch(not) => not,
@@ -240,6 +432,68 @@ fn send_request_failed_error(
     }))
 }
 
+/// Remembers the semantic tokens we last sent for each document, so that we
+/// can send only what's changed the next time the client asks for them.
+#[derive(Default)]
+struct SemanticTokensCache {
+    tokens: HashMap<String, SemanticTokens>,
+    next_result_id: u64,
+}
+
+impl SemanticTokensCache {
+    /// Gives the tokens a new result ID and remembers them as the latest
+    /// ones for the given document. Returns them along with the tokens
+    /// that were previously remembered for the document, if any.
+    fn remember(
+        &mut self,
+        uri: String,
+        mut tokens: SemanticTokens,
+    ) -> (SemanticTokens, Option<SemanticTokens>) {
+        tokens.result_id = Some(self.next_result_id.to_string());
+        self.next_result_id += 1;
+        let previous_tokens = self.tokens.insert(uri, tokens.clone());
+        (tokens, previous_tokens)
+    }
+
+    fn forget(&mut self, uri: &str) {
+        self.tokens.remove(uri);
+    }
+}
+
+/// Returns the edits needed to turn the old tokens into the new ones. Since
+/// most changes are made in one place, this is just a single edit replacing
+/// everything between the tokens they have in common at their start and end.
+fn get_semantic_token_edits(
+    old_tokens: &[SemanticToken],
+    new_tokens: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    let prefix_len = old_tokens
+        .iter()
+        .zip(new_tokens)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let max_suffix_len = old_tokens.len().min(new_tokens.len()) - prefix_len;
+    let suffix_len = old_tokens
+        .iter()
+        .rev()
+        .zip(new_tokens.iter().rev())
+        .take(max_suffix_len)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let deleted_len = old_tokens.len() - prefix_len - suffix_len;
+    let inserted = &new_tokens[prefix_len..new_tokens.len() - suffix_len];
+    if deleted_len == 0 && inserted.is_empty() {
+        return vec![];
+    }
+    // Edits are expressed in terms of the integers that the tokens are
+    // encoded as, and each token is encoded as five integers.
+    vec![SemanticTokensEdit {
+        start: (prefix_len * 5) as u32,
+        delete_count: (deleted_len * 5) as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
 fn get_semantic_tokens(analyzer: &SourceFileAnalyzer) -> SemanticTokens {
     let mut data: Vec<SemanticToken> = vec![];
     let mut prev_line_number = 0;
@@ -268,12 +522,52 @@ fn get_semantic_tokens(analyzer: &SourceFileAnalyzer) -> SemanticTokens {
     }
 }
 
+fn convert_outline_symbol(symbol: OutlineSymbol) -> DocumentSymbol {
+    let line = symbol.file_line_number as u32;
+    let to_lsp_range = |range: std::ops::Range<usize>| {
+        Range::new(
+            Position::new(line, range.start as u32),
+            Position::new(line, range.end as u32),
+        )
+    };
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: symbol.name,
+        detail: symbol.detail,
+        kind: match symbol.kind {
+            OutlineSymbolKind::Line => SymbolKind::KEY,
+            OutlineSymbolKind::Function => SymbolKind::FUNCTION,
+        },
+        tags: None,
+        // This is deprecated, but we still need to provide it.
+        deprecated: None,
+        range: to_lsp_range(symbol.range),
+        selection_range: to_lsp_range(symbol.selection_range),
+        children: Some(
+            symbol
+                .children
+                .into_iter()
+                .map(convert_outline_symbol)
+                .collect(),
+        ),
+    }
+}
+
+fn convert_variable_symbol(variable: VariableSymbol) -> CompletionItem {
+    CompletionItem {
+        detail: Some(variable.detail()),
+        label: variable.name,
+        kind: Some(CompletionItemKind::VARIABLE),
+        ..Default::default()
+    }
+}
+
 fn analyze_source_file(analyzer: &SourceFileAnalyzer) -> Vec<Diagnostic> {
     let messages = analyzer.messages();
     let mut diagnostics: Vec<Diagnostic> = vec![];
     let source_map = analyzer.source_file_map();
     for message in messages {
-        if let Some((line, range)) = source_map.map_to_source(&message) {
+        if let Some((line, range)) = source_map.map_to_source(message) {
             let diag_range = Range::new(
                 Position::new(line as u32, range.start as u32),
                 Position::new(line as u32, range.end as u32),
@@ -282,6 +576,9 @@ fn analyze_source_file(analyzer: &SourceFileAnalyzer) -> Vec<Diagnostic> {
                 DiagnosticMessage::Warning(_line, _loc, msg) => {
                     (DiagnosticSeverity::WARNING, msg.clone())
                 }
+                DiagnosticMessage::Hint(_line, _loc, msg) => {
+                    (DiagnosticSeverity::HINT, msg.clone())
+                }
                 DiagnosticMessage::Error(_line, err) => {
                     (DiagnosticSeverity::ERROR, err.to_string())
                 }
@@ -338,3 +635,107 @@ fn send_notification<N: lsp_types::notification::Notification>(
     connection.sender.send(not.into())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use abasic_core::TokenType;
+
+    use lsp_types::{SemanticToken, SemanticTokensEdit};
+
+    use super::{
+        abasic_token_type_to_lsp_token_type, get_semantic_token_edits, lsp_token_type,
+        token_types_legend,
+    };
+
+    fn token(delta_line: u32, delta_start: u32, length: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    #[test]
+    fn semantic_token_edits_are_empty_when_nothing_changed() {
+        let tokens = [token(0, 0, 2), token(0, 3, 5)];
+        assert_eq!(get_semantic_token_edits(&tokens, &tokens), vec![]);
+        assert_eq!(get_semantic_token_edits(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn semantic_token_edits_replace_only_what_changed() {
+        let a = token(0, 0, 2);
+        let b = token(0, 3, 5);
+        let c = token(0, 6, 1);
+        let d = token(1, 0, 2);
+        assert_eq!(
+            get_semantic_token_edits(&[a, b, d], &[a, c, d]),
+            vec![SemanticTokensEdit {
+                start: 5,
+                delete_count: 5,
+                data: Some(vec![c]),
+            }]
+        );
+        assert_eq!(
+            get_semantic_token_edits(&[a, d], &[a, b, c, d]),
+            vec![SemanticTokensEdit {
+                start: 5,
+                delete_count: 0,
+                data: Some(vec![b, c]),
+            }]
+        );
+        assert_eq!(
+            get_semantic_token_edits(&[a, b, c, d], &[a]),
+            vec![SemanticTokensEdit {
+                start: 5,
+                delete_count: 15,
+                data: Some(vec![]),
+            }]
+        );
+        assert_eq!(
+            get_semantic_token_edits(&[], &[a]),
+            vec![SemanticTokensEdit {
+                start: 0,
+                delete_count: 0,
+                data: Some(vec![a]),
+            }]
+        );
+    }
+
+    #[test]
+    fn semantic_token_edits_do_not_overlap_when_tokens_repeat() {
+        // The common prefix and suffix here could both claim the middle `a`,
+        // but it must only be counted once.
+        let a = token(0, 0, 2);
+        assert_eq!(
+            get_semantic_token_edits(&[a, a], &[a, a, a]),
+            vec![SemanticTokensEdit {
+                start: 10,
+                delete_count: 0,
+                data: Some(vec![a]),
+            }]
+        );
+    }
+
+    #[test]
+    fn token_types_legend_matches_core_token_types() {
+        let legend = token_types_legend();
+        assert_eq!(legend.len(), TokenType::ALL.len());
+        for token_type in TokenType::ALL {
+            let index = abasic_token_type_to_lsp_token_type(token_type) as usize;
+            assert_eq!(legend[index], lsp_token_type(token_type), "{token_type:?}");
+        }
+    }
+
+    #[test]
+    fn core_token_types_are_listed_in_index_order() {
+        for (i, token_type) in TokenType::ALL.into_iter().enumerate() {
+            assert_eq!(token_type.index(), i, "{token_type:?}");
+        }
+        // If a variant is added after this one, it needs to be added to
+        // `TokenType::ALL` too.
+        assert_eq!(TokenType::Data.index(), TokenType::ALL.len() - 1);
+    }
+}
@@ -1,8 +1,13 @@
+mod local_storage_program_storage;
 mod utils;
 
-use abasic_core::{Interpreter, InterpreterOutput, InterpreterState};
+use abasic_core::{
+    GraphicsMode, InputTarget, Interpreter, InterpreterOutput, InterpreterState, RunOutcome,
+};
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+use crate::local_storage_program_storage::LocalStorageProgramStorage;
 use crate::utils::set_panic_hook;
 
 #[wasm_bindgen]
@@ -13,6 +18,16 @@ pub enum JsInterpreterState {
     Errored,
 }
 
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum JsRunOutcome {
+    Completed,
+    Broke,
+    AwaitingInput,
+    Errored,
+    Interrupted,
+}
+
 #[wasm_bindgen]
 #[derive(Copy, Clone)]
 pub enum JsInterpreterOutputType {
@@ -22,11 +37,15 @@ pub enum JsInterpreterOutputType {
     Trace,
     ExtraIgnored,
     Reenter,
+    Ended,
+    FillScreen,
+    MoveCursor,
 }
 
 #[wasm_bindgen]
-/// wasm-bindgen only supports C-Style enums, for tagged unions it looks like
-/// I need to use serde, but for now I'm just gonna use this struct instead.
+/// wasm-bindgen only supports C-Style enums, so this flattens each output to
+/// a string. See `JsInterpreter::take_latest_output_structured` for a
+/// serde-based alternative that preserves each variant's fields.
 pub struct JsInterpreterOutput {
     pub output_type: JsInterpreterOutputType,
     value: String,
@@ -47,6 +66,9 @@ fn convert_interpreter_output_for_js(value: InterpreterOutput) -> JsInterpreterO
         InterpreterOutput::Trace(_) => JsInterpreterOutputType::Trace,
         InterpreterOutput::ExtraIgnored => JsInterpreterOutputType::ExtraIgnored,
         InterpreterOutput::Reenter => JsInterpreterOutputType::Reenter,
+        InterpreterOutput::Ended => JsInterpreterOutputType::Ended,
+        InterpreterOutput::FillScreen(_, _) => JsInterpreterOutputType::FillScreen,
+        InterpreterOutput::MoveCursor { .. } => JsInterpreterOutputType::MoveCursor,
     };
     JsInterpreterOutput {
         output_type,
@@ -54,13 +76,104 @@ fn convert_interpreter_output_for_js(value: InterpreterOutput) -> JsInterpreterO
     }
 }
 
+/// A version of `InterpreterOutput` that can be serialized into a JS object
+/// with a `type` tag, so the JS side can access each variant's fields.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+enum StructuredOutput {
+    Print { text: String },
+    Break { line: Option<u64> },
+    Warning { message: String, line: Option<u64> },
+    Trace { line: u64 },
+    ExtraIgnored,
+    Reenter,
+    Ended,
+    FillScreen { high_res: bool, color: u8 },
+    MoveCursor { row: usize, column: usize },
+}
+
+impl From<InterpreterOutput> for StructuredOutput {
+    fn from(value: InterpreterOutput) -> Self {
+        match value {
+            InterpreterOutput::Print(text) => StructuredOutput::Print { text },
+            InterpreterOutput::Break(line) => StructuredOutput::Break { line },
+            InterpreterOutput::Warning(message, line) => {
+                StructuredOutput::Warning { message, line }
+            }
+            InterpreterOutput::Trace(line) => StructuredOutput::Trace { line },
+            InterpreterOutput::ExtraIgnored => StructuredOutput::ExtraIgnored,
+            InterpreterOutput::Reenter => StructuredOutput::Reenter,
+            InterpreterOutput::Ended => StructuredOutput::Ended,
+            InterpreterOutput::FillScreen(mode, color) => StructuredOutput::FillScreen {
+                high_res: mode == GraphicsMode::HighRes,
+                color,
+            },
+            InterpreterOutput::MoveCursor { row, column } => {
+                StructuredOutput::MoveCursor { row, column }
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum JsInputKind {
+    /// A line of text, terminated by the user pressing enter.
+    Line,
+    /// A single keystroke, which shouldn't be echoed.
+    Keystroke,
+}
+
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// Describes the input the interpreter is waiting for, so the UI can
+/// present an appropriate widget for it.
+pub struct JsInputTarget {
+    pub kind: JsInputKind,
+    pub is_string: bool,
+}
+
+fn convert_input_target_for_js(value: InputTarget) -> JsInputTarget {
+    match value {
+        InputTarget::Line { is_string } => JsInputTarget {
+            kind: JsInputKind::Line,
+            is_string,
+        },
+        InputTarget::Keystroke { is_string } => JsInputTarget {
+            kind: JsInputKind::Keystroke,
+            is_string,
+        },
+    }
+}
+
+fn create_interpreter() -> Interpreter {
+    let mut interpreter = Interpreter::default();
+    interpreter.program_storage = Some(Box::new(LocalStorageProgramStorage));
+    // The front-end supports keystrokes for `GET` and keyboard polling via
+    // `PEEK`/`POKE`, so enable those statements.
+    interpreter.enable_applesoft_statements = true;
+    interpreter
+}
+
 #[wasm_bindgen]
-#[derive(Default)]
 pub struct JsInterpreter {
     interpreter: Interpreter,
     latest_error: Option<String>,
 }
 
+impl Default for JsInterpreter {
+    fn default() -> Self {
+        JsInterpreter {
+            interpreter: create_interpreter(),
+            latest_error: None,
+        }
+    }
+}
+
 #[wasm_bindgen]
 impl JsInterpreter {
     pub fn new() -> Self {
@@ -70,26 +183,71 @@ impl JsInterpreter {
 
     fn maybe_replace_interpreter(&mut self) {
         if self.interpreter.get_state() == InterpreterState::NewInterpreterRequested {
-            self.interpreter = Interpreter::default();
+            self.interpreter = create_interpreter();
         }
     }
 
-    pub fn randomize(&mut self, seed: u64) {
+    /// Seeds the interpreter's random number generator, so that the
+    /// sequence of numbers returned by `RND` is reproducible (e.g. for
+    /// a puzzle of the day).
+    ///
+    /// Note that `RUN` doesn't reseed the generator, so for a program's
+    /// run to be reproducible, this must be called before `RUN`.
+    pub fn seed_rng(&mut self, seed: u64) {
         self.interpreter.randomize(seed);
     }
 
+    /// An alias for `seed_rng`, kept for existing callers.
+    pub fn randomize(&mut self, seed: u64) {
+        self.seed_rng(seed);
+    }
+
+    /// Replaces the underlying interpreter with a fresh one, returning to
+    /// the idle state regardless of what the current state is. This is
+    /// like evaluating `NEW`, but also clears any pending output and error.
+    pub fn reset(&mut self) {
+        self.interpreter = create_interpreter();
+        self.latest_error = None;
+    }
+
     pub fn provide_input(&mut self, input: String) {
         self.interpreter.provide_input(input);
     }
 
+    /// Queues a key press (as an ASCII code) for programs that poll the
+    /// keyboard via `PEEK(49152)`.
+    pub fn push_key(&mut self, key: u8) {
+        self.interpreter.push_key(key);
+    }
+
+    /// Queues up multiple lines of input, e.g. for replaying a recorded
+    /// session. Each line is consumed by the next statement that would
+    /// otherwise make the interpreter await input.
+    pub fn queue_input(&mut self, lines: Vec<String>) {
+        self.interpreter.queue_input(lines);
+    }
+
     pub fn take_latest_output(&mut self) -> Vec<JsInterpreterOutput> {
         self.interpreter
             .take_output()
             .into_iter()
-            .map(|output| convert_interpreter_output_for_js(output))
+            .map(convert_interpreter_output_for_js)
             .collect::<Vec<_>>()
     }
 
+    /// Like `take_latest_output`, but returns an array of objects with a
+    /// `type` tag (e.g. `"warning"`) and fields specific to each type (e.g.
+    /// `message` and `line`), rather than flattening everything to strings.
+    pub fn take_latest_output_structured(&mut self) -> JsValue {
+        let output = self
+            .interpreter
+            .take_output()
+            .into_iter()
+            .map(StructuredOutput::from)
+            .collect::<Vec<_>>();
+        serde_wasm_bindgen::to_value(&output).unwrap()
+    }
+
     pub fn take_latest_error(&mut self) -> Option<String> {
         self.latest_error.take()
     }
@@ -118,6 +276,73 @@ impl JsInterpreter {
         }
     }
 
+    /// Like `continue_evaluating`, but runs statements until roughly
+    /// `token_budget` tokens have been evaluated. Evaluation only yields
+    /// at statement boundaries, so at least one statement is always run.
+    pub fn continue_evaluating_with_token_budget(&mut self, token_budget: usize) {
+        assert!(self.latest_error.is_none());
+        if let Err(err) = self
+            .interpreter
+            .continue_evaluating_with_token_budget(token_budget)
+        {
+            self.latest_error = Some(err.to_string());
+        } else {
+            self.maybe_replace_interpreter();
+        }
+    }
+
+    /// Returns whether tracing is enabled, e.g. via `TRACE` or `NOTRACE`.
+    pub fn is_tracing_enabled(&self) -> bool {
+        self.interpreter.enable_tracing
+    }
+
+    /// Returns whether warnings (e.g. about undeclared variables) are
+    /// enabled.
+    pub fn are_warnings_enabled(&self) -> bool {
+        self.interpreter.enable_warnings
+    }
+
+    /// Returns the current program in the interpreter's compact tokenized
+    /// format, e.g. so the user's work can be persisted and later restored
+    /// via `load_tokenized_program`.
+    pub fn save_tokenized_program(&self) -> Vec<u8> {
+        self.interpreter.save_binary_program()
+    }
+
+    /// Replaces the current program with one previously returned by
+    /// `save_tokenized_program`. This should only be called when the
+    /// interpreter is idle. If the data is invalid, the current program is
+    /// left untouched and an error message is returned.
+    pub fn load_tokenized_program(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.interpreter
+            .load_binary_program(bytes)
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn get_column(&self) -> usize {
+        self.interpreter.get_column()
+    }
+
+    pub fn get_pending_input_target(&self) -> Option<JsInputTarget> {
+        self.interpreter
+            .pending_input_target()
+            .map(convert_input_target_for_js)
+    }
+
+    /// Returns how the most recent evaluation ended, or nothing if it's
+    /// still running.
+    pub fn get_last_run_outcome(&self) -> Option<JsRunOutcome> {
+        self.interpreter
+            .last_run_outcome()
+            .map(|outcome| match outcome {
+                RunOutcome::Completed => JsRunOutcome::Completed,
+                RunOutcome::Broke => JsRunOutcome::Broke,
+                RunOutcome::AwaitingInput => JsRunOutcome::AwaitingInput,
+                RunOutcome::Errored => JsRunOutcome::Errored,
+                RunOutcome::Interrupted => JsRunOutcome::Interrupted,
+            })
+    }
+
     pub fn get_state(&self) -> JsInterpreterState {
         if self.latest_error.is_some() {
             return JsInterpreterState::Errored;
@@ -126,9 +351,230 @@ impl JsInterpreter {
             abasic_core::InterpreterState::Idle => JsInterpreterState::Idle,
             abasic_core::InterpreterState::Running => JsInterpreterState::Running,
             abasic_core::InterpreterState::AwaitingInput => JsInterpreterState::AwaitingInput,
-            abasic_core::InterpreterState::NewInterpreterRequested => {
-                panic!("Underlying interpreter should never be in this state")
-            }
+            // We normally replace the interpreter as soon as it requests
+            // this, at which point the new one will be idle anyways.
+            abasic_core::InterpreterState::NewInterpreterRequested => JsInterpreterState::Idle,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use abasic_core::{GraphicsMode, InterpreterOutput};
+    use serde_json::json;
+
+    use super::{
+        JsInputKind, JsInputTarget, JsInterpreter, JsInterpreterState, JsRunOutcome,
+        StructuredOutput,
+    };
+
+    fn evaluate_while_running(interpreter: &mut JsInterpreter, line: &str) {
+        interpreter.start_evaluating(line.to_string());
+        while let JsInterpreterState::Running = interpreter.get_state() {
+            interpreter.continue_evaluating();
+        }
+    }
+
+    fn get_pending_input_target(line: &str) -> Option<JsInputTarget> {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, line);
+        interpreter.get_pending_input_target()
+    }
+
+    #[test]
+    fn pending_input_target_works() {
+        assert_eq!(get_pending_input_target("print 1"), None);
+        assert_eq!(
+            get_pending_input_target("input a$"),
+            Some(JsInputTarget {
+                kind: JsInputKind::Line,
+                is_string: true
+            })
+        );
+        assert_eq!(
+            get_pending_input_target("input a"),
+            Some(JsInputTarget {
+                kind: JsInputKind::Line,
+                is_string: false
+            })
+        );
+        assert_eq!(
+            get_pending_input_target("get a$"),
+            Some(JsInputTarget {
+                kind: JsInputKind::Keystroke,
+                is_string: true
+            })
+        );
+    }
+
+    #[test]
+    fn trace_and_notrace_are_reflected_in_tracing_state() {
+        let mut interpreter = JsInterpreter::new();
+        assert!(!interpreter.is_tracing_enabled());
+        evaluate_while_running(&mut interpreter, "trace");
+        assert!(interpreter.is_tracing_enabled());
+        evaluate_while_running(&mut interpreter, "notrace");
+        assert!(!interpreter.is_tracing_enabled());
+    }
+
+    #[test]
+    fn warnings_are_disabled_by_default() {
+        assert!(!JsInterpreter::new().are_warnings_enabled());
+    }
+
+    #[test]
+    fn running_empty_program_does_nothing() {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, "run");
+        assert!(matches!(interpreter.get_state(), JsInterpreterState::Idle));
+        assert!(interpreter.take_latest_output().is_empty());
+        assert_eq!(interpreter.take_latest_error(), None);
+    }
+
+    #[test]
+    fn cont_without_running_fails() {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, "cont");
+        assert!(matches!(
+            interpreter.get_state(),
+            JsInterpreterState::Errored
+        ));
+        // The error is followed by a backtrace if RUST_BACKTRACE is set.
+        let error = interpreter.take_latest_error().unwrap();
+        assert_eq!(error.lines().next(), Some("CAN'T CONTINUE ERROR"));
+    }
+
+    #[test]
+    fn pushed_keys_can_be_polled() {
+        let mut interpreter = JsInterpreter::new();
+        interpreter.push_key(b'Y');
+        evaluate_while_running(
+            &mut interpreter,
+            "k = peek(49152): poke 49168,0: print chr$(k - 128)",
+        );
+        assert_eq!(take_output_as_string(&mut interpreter), "Y\n");
+    }
+
+    fn take_output_as_string(interpreter: &mut JsInterpreter) -> String {
+        interpreter
+            .take_latest_output()
+            .into_iter()
+            .map(|output| output.into_string())
+            .collect::<String>()
+    }
+
+    #[test]
+    fn tokenized_program_round_trip_works() {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, "10 print \"hi\";5");
+        evaluate_while_running(&mut interpreter, "20 goto 10");
+        let bytes = interpreter.save_tokenized_program();
+
+        let mut interpreter = JsInterpreter::new();
+        interpreter.load_tokenized_program(&bytes).unwrap();
+        evaluate_while_running(&mut interpreter, "list");
+        assert_eq!(
+            take_output_as_string(&mut interpreter),
+            "10 PRINT \"hi\" ; 5\n20 GOTO 10\n"
+        );
+        assert_eq!(interpreter.save_tokenized_program(), bytes);
+    }
+
+    #[test]
+    fn loading_invalid_tokenized_program_fails() {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, "10 print 1");
+        assert!(interpreter.load_tokenized_program(&[1, 2, 3]).is_err());
+        evaluate_while_running(&mut interpreter, "list");
+        assert_eq!(take_output_as_string(&mut interpreter), "10 PRINT 1\n");
+    }
+
+    #[test]
+    fn reset_works_in_any_state() {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, "10 print 1");
+        evaluate_while_running(&mut interpreter, "input a");
+        assert!(matches!(
+            interpreter.get_state(),
+            JsInterpreterState::AwaitingInput
+        ));
+        interpreter.reset();
+        assert!(matches!(interpreter.get_state(), JsInterpreterState::Idle));
+        evaluate_while_running(&mut interpreter, "list");
+        assert_eq!(take_output_as_string(&mut interpreter), "");
+
+        evaluate_while_running(&mut interpreter, "cont");
+        assert!(matches!(
+            interpreter.get_state(),
+            JsInterpreterState::Errored
+        ));
+        interpreter.reset();
+        assert!(matches!(interpreter.get_state(), JsInterpreterState::Idle));
+        assert_eq!(interpreter.take_latest_error(), None);
+    }
+
+    fn structured_output_json(output: InterpreterOutput) -> serde_json::Value {
+        serde_json::to_value(StructuredOutput::from(output)).unwrap()
+    }
+
+    #[test]
+    fn structured_output_is_tagged() {
+        assert_eq!(
+            structured_output_json(InterpreterOutput::Print("hi".to_string())),
+            json!({"type": "print", "text": "hi"})
+        );
+        assert_eq!(
+            structured_output_json(InterpreterOutput::Warning("uh oh".to_string(), Some(10))),
+            json!({"type": "warning", "message": "uh oh", "line": 10})
+        );
+        assert_eq!(
+            structured_output_json(InterpreterOutput::Break(None)),
+            json!({"type": "break", "line": null})
+        );
+        assert_eq!(
+            structured_output_json(InterpreterOutput::Trace(20)),
+            json!({"type": "trace", "line": 20})
+        );
+        assert_eq!(
+            structured_output_json(InterpreterOutput::ExtraIgnored),
+            json!({"type": "extraIgnored"})
+        );
+        assert_eq!(
+            structured_output_json(InterpreterOutput::FillScreen(GraphicsMode::HighRes, 3)),
+            json!({"type": "fillScreen", "highRes": true, "color": 3})
+        );
+        assert_eq!(
+            structured_output_json(InterpreterOutput::MoveCursor { row: 1, column: 2 }),
+            json!({"type": "moveCursor", "row": 1, "column": 2})
+        );
+    }
+
+    #[test]
+    fn queued_input_satisfies_consecutive_input_statements() {
+        let mut interpreter = JsInterpreter::new();
+        interpreter.queue_input(vec!["1".to_string(), "hi".to_string()]);
+        evaluate_while_running(&mut interpreter, "input a: input b$: print a;b$");
+        assert!(matches!(interpreter.get_state(), JsInterpreterState::Idle));
+        let output = interpreter
+            .take_latest_output()
+            .into_iter()
+            .map(|output| output.into_string())
+            .collect::<String>();
+        assert_eq!(output, "1hi\n");
+    }
+
+    #[test]
+    fn last_run_outcome_works() {
+        let mut interpreter = JsInterpreter::new();
+        evaluate_while_running(&mut interpreter, "print 1");
+        assert_eq!(
+            interpreter.get_last_run_outcome(),
+            Some(JsRunOutcome::Completed)
+        );
+        evaluate_while_running(&mut interpreter, "stop");
+        assert_eq!(
+            interpreter.get_last_run_outcome(),
+            Some(JsRunOutcome::Broke)
+        );
+    }
+}
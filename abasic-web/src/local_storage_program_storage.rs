@@ -0,0 +1,31 @@
+use abasic_core::ProgramStorage;
+use wasm_bindgen::prelude::*;
+
+/// Prefixed to program names to form their keys in local storage, so they
+/// don't collide with anything else the web app stores there.
+const KEY_PREFIX: &str = "abasic-program:";
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = localStorage, js_name = getItem)]
+    fn get_item(key: &str) -> Option<String>;
+
+    #[wasm_bindgen(catch, js_namespace = localStorage, js_name = setItem)]
+    fn set_item(key: &str, value: &str) -> Result<(), JsValue>;
+}
+
+/// Saves and loads programs via the browser's local storage.
+pub struct LocalStorageProgramStorage;
+
+impl ProgramStorage for LocalStorageProgramStorage {
+    fn save(&mut self, name: &str, listing: &[String]) -> Result<(), String> {
+        // This only fails if storage is full or disabled.
+        set_item(&format!("{KEY_PREFIX}{name}"), &listing.concat())
+            .map_err(|_| "unable to write to local storage".to_string())
+    }
+
+    fn load(&self, name: &str) -> Option<Vec<String>> {
+        let source = get_item(&format!("{KEY_PREFIX}{name}"))?;
+        Some(source.lines().map(|line| line.to_string()).collect())
+    }
+}
@@ -3,6 +3,8 @@ import {
   JsInterpreter,
   JsInterpreterState,
   JsInterpreterOutputType,
+  JsRunOutcome,
+  JsInputKind,
 } from "../pkg/abasic_web.js";
 import * as ui from "./ui.js";
 import { unreachable } from "./util.js";
@@ -11,7 +13,7 @@ const VERSION = "0.3.0";
 
 class Interpreter {
   constructor(private readonly impl: JsInterpreter) {
-    this.impl.randomize(BigInt(Date.now()));
+    this.impl.seed_rng(BigInt(Date.now()));
   }
 
   /**
@@ -19,7 +21,8 @@ class Interpreter {
    * program execution normally will stop the interpreter and
    * not allow further user input. However, pressing CTRL-C
    * to break the program is still allowed, at which point we
-   * will be fully interactive.
+   * will be fully interactive. The same goes for the program
+   * breaking on its own, e.g. via `STOP`.
    *
    * When we're fully interactive, we will present the user
    * with a BASIC prompt at the end of program execution.
@@ -62,6 +65,34 @@ class Interpreter {
     return state !== JsInterpreterState.Idle;
   }
 
+  isAwaitingKeystroke(): boolean {
+    return (
+      this.impl.get_state() === JsInterpreterState.AwaitingInput &&
+      this.impl.get_pending_input_target()?.kind === JsInputKind.Keystroke
+    );
+  }
+
+  isRunning(): boolean {
+    return this.impl.get_state() === JsInterpreterState.Running;
+  }
+
+  /**
+   * Queues a key press for programs that poll the keyboard via
+   * `PEEK(49152)`.
+   */
+  pushKey(key: string) {
+    this.impl.push_key(key.charCodeAt(0));
+  }
+
+  /**
+   * Provides a single keystroke for a `GET` statement. Unlike other input,
+   * it isn't echoed.
+   */
+  submitKeystroke(key: string) {
+    this.impl.provide_input(key);
+    this.handleCurrentState();
+  }
+
   submitUserInput(input: string) {
     const state = this.impl.get_state();
     if (state === JsInterpreterState.Idle) {
@@ -102,6 +133,20 @@ class Interpreter {
         case JsInterpreterOutputType.Trace:
           ui.printSpanWithClass(`${item.into_string()} `, "info");
           break;
+        case JsInterpreterOutputType.Ended:
+          // The program ending will be reflected by the interpreter's
+          // state, so there's nothing to show.
+          break;
+        case JsInterpreterOutputType.FillScreen:
+          // We don't support graphics yet, so just describe what would
+          // have been drawn.
+          ui.printSpanWithClass(`${item.into_string()}\n`, "info");
+          break;
+        case JsInterpreterOutputType.MoveCursor:
+          // We don't support cursor positioning yet, so just describe
+          // where the cursor would have moved.
+          ui.printSpanWithClass(`${item.into_string()}\n`, "info");
+          break;
         case JsInterpreterOutputType.Break:
         case JsInterpreterOutputType.ExtraIgnored:
         case JsInterpreterOutputType.Reenter:
@@ -119,6 +164,9 @@ class Interpreter {
     const state = this.impl.get_state();
     switch (state) {
       case JsInterpreterState.Idle:
+        if (this.impl.get_last_run_outcome() === JsRunOutcome.Broke) {
+          this.isFullyInteractive = true;
+        }
         if (!this.isFullyInteractive) {
           ui.clearPromptAndDisableInput();
           return;
@@ -126,7 +174,7 @@ class Interpreter {
         ui.setPrompt("] ");
         break;
       case JsInterpreterState.AwaitingInput:
-        ui.setPrompt("? ");
+        ui.setPrompt(this.isAwaitingKeystroke() ? "" : "? ");
         break;
       case JsInterpreterState.Errored:
         const err = this.impl.take_latest_error();
@@ -205,6 +253,21 @@ wasm().then(async (module) => {
     if (event.ctrlKey && event.key.toUpperCase() === "C") {
       event.preventDefault();
       interpreter.breakAtCurrentLocation();
+      return;
+    }
+    if (interpreter.isAwaitingKeystroke()) {
+      const key = event.key === "Enter" ? "\r" : event.key;
+      if (key.length === 1 && !event.ctrlKey && !event.metaKey) {
+        event.preventDefault();
+        interpreter.submitKeystroke(key);
+      }
+    } else if (interpreter.isRunning()) {
+      const key = event.key === "Enter" ? "\r" : event.key;
+      const isAscii = key.length === 1 && key.charCodeAt(0) < 128;
+      if (isAscii && !event.ctrlKey && !event.metaKey) {
+        event.preventDefault();
+        interpreter.pushKey(key);
+      }
     }
   });
 